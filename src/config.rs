@@ -0,0 +1,755 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Arc, time::Duration};
+
+use crate::{
+    file_store::{DiskStore, EmbeddedAssets, FileStore},
+    handlers,
+    http::{BufferGrowth, BufferStrategy, DecodePolicy, Limits},
+    intercept::Interceptor,
+    router::Router,
+    server::Shutdown,
+    upgrade::Upgrades,
+};
+
+/// Host the server listens on when no `--host` argument is given
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+/// Port the server listens on when no `--port` argument is given
+pub const DEFAULT_PORT: u16 = 4221;
+
+/// MIME type prefixes eligible for compression when no `--compress-types` argument is given
+pub const DEFAULT_COMPRESSIBLE_TYPES: [&str; 5] = [
+    "text/",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "image/svg+xml",
+];
+
+/// Maximum number of pipelined requests answered on a connection when no
+/// `--max-pipelined-requests` argument is given
+pub const DEFAULT_MAX_PIPELINED_REQUESTS: usize = 16;
+
+/// Maximum number of requests answered on a connection when no
+/// `--max-requests-per-connection` argument is given
+pub const DEFAULT_MAX_REQUESTS_PER_CONNECTION: usize = 1000;
+
+/// Largest response of the `/bytes/<n>` endpoint when no `--max-generated-bytes` argument is
+/// given
+pub const DEFAULT_MAX_GENERATED_BYTES: usize = 10 * 1024 * 1024;
+
+/// Longest delay of the `/delay/<seconds>` endpoint when no `--max-delay` argument is given
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Length of the accept queue of every listener when no `--backlog` argument is given
+pub const DEFAULT_BACKLOG: u32 = 1024;
+/// Largest accepted `--backlog` value. The kernel silently caps larger values anyway
+/// (`net.core.somaxconn` on Linux).
+pub const MAX_BACKLOG: u32 = 65535;
+
+/// # Server configuration.
+///
+/// The configuration is built once from the command line arguments in `main` and shared
+/// between all connections.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Hosts to listen on (`--host <host>`, can be repeated). Defaults to [`DEFAULT_HOST`].
+    pub hosts: Vec<String>,
+    /// Ports to listen on (`--port <port>`, can be repeated). Defaults to [`DEFAULT_PORT`].
+    pub ports: Vec<u16>,
+    /// Maximum number of connections waiting to be accepted on every listener (`--backlog
+    /// <n>`, between 1 and [`MAX_BACKLOG`]). Defaults to [`DEFAULT_BACKLOG`].
+    pub backlog: u32,
+    /// Directory the `/files/` endpoints read from and write to (`--directory <dir>`). Empty
+    /// when not configured, in which case the working directory is used (see
+    /// [`file_path`](ServerConfig::file_path)).
+    pub files_dir: String,
+    /// Detect the `Content-Type` of served files without a known extension from their first
+    /// bytes instead of always using `application/octet-stream` (`--sniff-content-type`)
+    pub sniff_content_type: bool,
+    /// `Content-Type` of files, taking precedence over the built-in extension table
+    /// (`--mime <.ext|file>=<type>`, can be repeated). Keyed by lowercase extension with the
+    /// leading dot (`.md`) or by exact file name (`Makefile`). Empty by default.
+    pub mime_overrides: HashMap<String, String>,
+    /// How malformed percent-encoding in the request path is handled (`--path-decoding
+    /// strict|lenient`). Defaults to strict, which answers such requests with `400 Bad Request`.
+    pub path_decoding: DecodePolicy,
+    /// How malformed percent-encoding in the query string is handled (`--query-decoding
+    /// strict|lenient`). Defaults to lenient.
+    pub query_decoding: DecodePolicy,
+    /// MIME type prefixes of responses that may be compressed (`--compress-types
+    /// text/,application/json`). Responses of any other type, or without a `Content-Type`, are
+    /// never compressed. Defaults to [`DEFAULT_COMPRESSIBLE_TYPES`].
+    pub compressible_types: Vec<String>,
+    /// Safety limits requests are checked against (`--max-request-line <n>`, `--max-headers
+    /// <n>`, `--max-header-bytes <n>`, `--max-body-size <n>`, `--read-timeout <seconds>`)
+    pub limits: Limits,
+    /// How the buffer requests are read into is sized (`--read-buffer-size <n>`,
+    /// `--read-buffer-growth double|<n>`). Defaults to 8 KiB, doubled whenever it's full.
+    pub read_buffer: BufferStrategy,
+    /// Storage used by the `/files/` endpoints. Defaults to the real filesystem.
+    pub file_store: Arc<dyn FileStore>,
+    /// Answer successful `POST`/`PUT` uploads to `/files/` with `204 No Content` instead of
+    /// `201 Created` (`--upload-no-content`). Disabled by default.
+    pub upload_no_content: bool,
+    /// Let the `/files/` endpoints serve, write and delete dotfiles, i.e. paths with a segment
+    /// starting with `.` (`--allow-dotfiles`). Disabled by default: such files (`.env`,
+    /// `.git/config`) often hold secrets, so they're answered with `404 Not Found`.
+    pub allow_dotfiles: bool,
+    /// Files compiled into the binary, served by the `/files/` endpoint before the file store is
+    /// asked. None by default.
+    pub embedded_assets: EmbeddedAssets,
+    /// Maximum number of consecutive pipelined requests answered on a keep-alive connection
+    /// before it's closed (`--max-pipelined-requests <n>`). Defaults to
+    /// [`DEFAULT_MAX_PIPELINED_REQUESTS`].
+    pub max_pipelined_requests: usize,
+    /// Maximum number of requests answered on a keep-alive connection before it's closed
+    /// (`--max-requests-per-connection <n>`). The `max` of the `Keep-Alive` header counts down
+    /// from it. Defaults to [`DEFAULT_MAX_REQUESTS_PER_CONNECTION`].
+    pub max_requests_per_connection: usize,
+    /// Accept HTTP/0.9 simple requests (`GET /path` without version and headers) and answer them
+    /// with the bare body (`--http09`). Disabled by default.
+    pub allow_http09: bool,
+    /// Decompress request bodies starting with the gzip magic bytes even without a
+    /// `Content-Encoding` header (`--detect-gzip-bodies`). Disabled by default, since binary
+    /// uploads may start with these bytes by chance.
+    pub detect_gzip_bodies: bool,
+    /// Answer every request with `503 Service Unavailable` (`--maintenance`). Disabled by
+    /// default.
+    pub maintenance: bool,
+    /// Send response headers in a canonical order (general, response, content, other headers)
+    /// instead of the order they were set in (`--canonical-header-order`). Disabled by default.
+    pub canonical_header_order: bool,
+    /// Read the body of `POST`/`PUT`/`PATCH` requests without `Content-Length` and
+    /// `Transfer-Encoding` until the client closes its side of the connection (`--eof-body`).
+    /// Disabled by default, such requests have no body.
+    pub eof_body: bool,
+    /// Keep the bytes of every request exactly as received, available to handlers with
+    /// [`Request::raw`](crate::http::Request::raw) (`--keep-raw-requests`). Disabled by default.
+    pub keep_raw_requests: bool,
+    /// Routes requests are dispatched to. Defaults to the endpoints of [`handlers::router`].
+    pub router: Router,
+    /// List the registered routes closest to the requested path in the body of `404 Not Found`
+    /// responses (`--suggest-routes`). Meant for debugging, disabled by default.
+    pub suggest_routes: bool,
+    /// Largest number of bytes the `/bytes/<n>` endpoint generates (`--max-generated-bytes
+    /// <n>`). Larger requests are answered with `413 Payload Too Large`. Defaults to
+    /// [`DEFAULT_MAX_GENERATED_BYTES`].
+    pub max_generated_bytes: usize,
+    /// Serve a built-in icon at `/favicon.ico` instead of answering browsers' automatic requests
+    /// for it with `404 Not Found` (`--favicon`). Disabled by default.
+    pub favicon: bool,
+    /// Longest delay of the `/delay/<seconds>` endpoint (`--max-delay <seconds>`). Longer
+    /// delays are cut to it. Defaults to [`DEFAULT_MAX_DELAY`].
+    pub max_delay: Duration,
+    /// Longest time a single request-response exchange may take, from the moment the request
+    /// starts arriving until its response is written (`--request-deadline <seconds>`). A request
+    /// that takes longer is answered with `504 Gateway Timeout`, or its response cut off, and the
+    /// connection closed. No deadline by default.
+    pub request_deadline: Option<Duration>,
+    /// Protocols connections can be upgraded to with `Connection: Upgrade`. None by default.
+    pub upgrades: Upgrades,
+    /// Secret enabling the `POST /admin/shutdown` endpoint, which clients must send as a bearer
+    /// token (`--shutdown-token <token>`). The endpoint is disabled (`404 Not Found`) without it.
+    pub shutdown_token: Option<String>,
+    /// Signal shutting the server down gracefully, shared by all clones of the configuration
+    pub shutdown: Shutdown,
+    /// Hooks called with every request and response. None by default.
+    pub interceptor: Interceptor,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            hosts: Vec::new(),
+            ports: Vec::new(),
+            backlog: DEFAULT_BACKLOG,
+            files_dir: String::new(),
+            sniff_content_type: false,
+            mime_overrides: HashMap::new(),
+            path_decoding: DecodePolicy::Strict,
+            query_decoding: DecodePolicy::Lenient,
+            compressible_types: DEFAULT_COMPRESSIBLE_TYPES
+                .iter()
+                .map(|mime| mime.to_string())
+                .collect(),
+            limits: Limits::default(),
+            read_buffer: BufferStrategy::default(),
+            file_store: Arc::new(DiskStore),
+            upload_no_content: false,
+            allow_dotfiles: false,
+            embedded_assets: EmbeddedAssets::default(),
+            max_pipelined_requests: DEFAULT_MAX_PIPELINED_REQUESTS,
+            max_requests_per_connection: DEFAULT_MAX_REQUESTS_PER_CONNECTION,
+            allow_http09: false,
+            canonical_header_order: false,
+            maintenance: false,
+            detect_gzip_bodies: false,
+            eof_body: false,
+            keep_raw_requests: false,
+            router: handlers::router(),
+            suggest_routes: false,
+            max_generated_bytes: DEFAULT_MAX_GENERATED_BYTES,
+            favicon: false,
+            max_delay: DEFAULT_MAX_DELAY,
+            request_deadline: None,
+            upgrades: Upgrades::default(),
+            shutdown_token: None,
+            shutdown: Shutdown::new(),
+            interceptor: Interceptor::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// # Build the configuration from the command line arguments.
+    ///
+    /// Unknown arguments are ignored. Returns an error if an option is missing its value or
+    /// the value is invalid.
+    pub fn from_args(args: &[String]) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--directory" => {
+                    let value = next_value(&mut args, arg)?;
+                    if value.is_empty() {
+                        return Err(ConfigError::invalid_value(arg, value));
+                    }
+                    config.files_dir = value.clone();
+                }
+                "--host" => config.hosts.push(next_value(&mut args, arg)?.clone()),
+                "--port" => config.ports.push(parse_value(&mut args, arg)?),
+                "--backlog" => {
+                    let value = next_value(&mut args, arg)?;
+                    config.backlog = value
+                        .parse()
+                        .ok()
+                        .filter(|backlog| (1..=MAX_BACKLOG).contains(backlog))
+                        .ok_or_else(|| ConfigError::invalid_value(arg, value))?;
+                }
+                "--sniff-content-type" => config.sniff_content_type = true,
+                "--mime" => {
+                    let value = next_value(&mut args, arg)?;
+                    let (key, mime) = value
+                        .split_once('=')
+                        .map(|(key, mime)| (key.trim(), mime.trim()))
+                        .filter(|(key, mime)| {
+                            !key.trim_start_matches('.').is_empty() && !mime.is_empty()
+                        })
+                        .ok_or_else(|| ConfigError::invalid_value(arg, value))?;
+                    let key = match key.starts_with('.') {
+                        true => key.to_ascii_lowercase(),
+                        false => key.to_string(),
+                    };
+                    config.mime_overrides.insert(key, mime.to_string());
+                }
+                "--suggest-routes" => config.suggest_routes = true,
+                "--http09" => config.allow_http09 = true,
+                "--keep-raw-requests" => config.keep_raw_requests = true,
+                "--eof-body" => config.eof_body = true,
+                "--canonical-header-order" => config.canonical_header_order = true,
+                "--maintenance" => config.maintenance = true,
+                "--shutdown-token" => {
+                    let value = next_value(&mut args, arg)?;
+                    if value.is_empty() {
+                        return Err(ConfigError::invalid_value(arg, value));
+                    }
+                    config.shutdown_token = Some(value.clone());
+                }
+                "--detect-gzip-bodies" => config.detect_gzip_bodies = true,
+                "--favicon" => config.favicon = true,
+                "--upload-no-content" => config.upload_no_content = true,
+                "--allow-dotfiles" => config.allow_dotfiles = true,
+                "--max-pipelined-requests" => {
+                    config.max_pipelined_requests = parse_value(&mut args, arg)?
+                }
+                "--max-requests-per-connection" => {
+                    config.max_requests_per_connection = parse_value(&mut args, arg)?
+                }
+                "--max-generated-bytes" => {
+                    config.max_generated_bytes = parse_value(&mut args, arg)?
+                }
+                "--max-delay" => {
+                    config.max_delay = Duration::from_secs(parse_value(&mut args, arg)?)
+                }
+                "--request-deadline" => {
+                    let value = next_value(&mut args, arg)?;
+                    let seconds = value
+                        .parse()
+                        .ok()
+                        .filter(|&seconds| seconds > 0)
+                        .ok_or_else(|| ConfigError::invalid_value(arg, value))?;
+                    config.request_deadline = Some(Duration::from_secs(seconds));
+                }
+                "--path-decoding" => config.path_decoding = parse_value(&mut args, arg)?,
+                "--query-decoding" => config.query_decoding = parse_value(&mut args, arg)?,
+                "--max-request-line" => {
+                    config.limits.max_request_line = parse_value(&mut args, arg)?
+                }
+                "--max-headers" => config.limits.max_header_count = parse_value(&mut args, arg)?,
+                "--max-header-bytes" => {
+                    config.limits.max_header_bytes = parse_value(&mut args, arg)?
+                }
+                "--max-body-size" => config.limits.max_body_size = parse_value(&mut args, arg)?,
+                "--read-timeout" => {
+                    config.limits.read_timeout = Duration::from_secs(parse_value(&mut args, arg)?)
+                }
+                // An empty buffer or growth would read a single byte at a time
+                "--read-buffer-size" => {
+                    let value = next_value(&mut args, arg)?;
+                    config.read_buffer.initial_size =
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|&size| size > 0)
+                            .ok_or_else(|| ConfigError::invalid_value(arg, value))?;
+                }
+                "--read-buffer-growth" => {
+                    let value = next_value(&mut args, arg)?;
+                    config.read_buffer.growth = value
+                        .parse()
+                        .ok()
+                        .filter(|&growth| growth != BufferGrowth::Fixed(0))
+                        .ok_or_else(|| ConfigError::invalid_value(arg, value))?;
+                }
+                "--compress-types" => {
+                    config.compressible_types = next_value(&mut args, arg)?
+                        .split(',')
+                        .map(|mime| mime.trim().to_ascii_lowercase())
+                        .filter(|mime| !mime.is_empty())
+                        .collect();
+                }
+                _ => (),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// # Check whether a response with the given `Content-Type` may be compressed.
+    ///
+    /// Parameters such as `; charset=utf-8` are ignored and the comparison is case-insensitive.
+    pub fn is_compressible(&self, content_type: &str) -> bool {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        self.compressible_types
+            .iter()
+            .any(|prefix| mime.starts_with(prefix.as_str()))
+    }
+
+    /// # Path of a file under the files directory.
+    ///
+    /// Without a configured directory, files are resolved against the working directory. Joining
+    /// the empty directory would give `/<file_name>`, a path at the root of the filesystem.
+    pub fn file_path(&self, file_name: &str) -> String {
+        let dir = match self.files_dir.is_empty() {
+            true => ".",
+            false => &self.files_dir,
+        };
+
+        format!("{}/{}", dir, file_name)
+    }
+
+    /// # Get the configured `Content-Type` of a file.
+    ///
+    /// An override of the exact file name wins over one of its extension, which is looked up
+    /// case-insensitively. Returns `None` if neither is overridden.
+    pub fn mime_override(&self, file_name: &str) -> Option<&str> {
+        let base_name = file_name.rsplit('/').next().unwrap_or(file_name);
+        let extension = std::path::Path::new(base_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| format!(".{}", extension.to_ascii_lowercase()));
+
+        self.mime_overrides
+            .get(base_name)
+            .or_else(|| self.mime_overrides.get(&extension?))
+            .map(String::as_str)
+    }
+
+    /// # Addresses the server listens on.
+    ///
+    /// Every configured host is combined with every configured port, so `--host 127.0.0.1
+    /// --host ::1 --port 4221` listens on both the IPv4 and the IPv6 loopback.
+    pub fn listen_addrs(&self) -> Vec<(String, u16)> {
+        let hosts = match self.hosts.is_empty() {
+            true => vec![DEFAULT_HOST.to_string()],
+            false => self.hosts.clone(),
+        };
+        let ports = match self.ports.is_empty() {
+            true => vec![DEFAULT_PORT],
+            false => self.ports.clone(),
+        };
+
+        hosts
+            .iter()
+            .flat_map(|host| ports.iter().map(move |port| (host.clone(), *port)))
+            .collect()
+    }
+}
+
+fn next_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<&'a String, ConfigError> {
+    args.next()
+        .ok_or_else(|| ConfigError(format!("Missing value for {}", option)))
+}
+
+fn parse_value<'a, T: FromStr>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<T, ConfigError> {
+    let value = next_value(args, option)?;
+    value
+        .parse::<T>()
+        .map_err(|_| ConfigError::invalid_value(option, value))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConfigError(String);
+
+impl ConfigError {
+    fn invalid_value(option: &str, value: &str) -> Self {
+        Self(format!("Invalid value for {}: {}", option, value))
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Configuration error: {}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let config = ServerConfig::from_args(&args(&["server"])).unwrap();
+        assert!(config.hosts.is_empty(), "No hosts");
+        assert!(config.ports.is_empty(), "No ports");
+        assert_eq!(config.files_dir, "", "No files directory");
+        assert!(!config.sniff_content_type, "Content-Type sniffing disabled");
+        assert_eq!(
+            config.path_decoding,
+            DecodePolicy::Strict,
+            "Strict path decoding"
+        );
+        assert_eq!(
+            config.query_decoding,
+            DecodePolicy::Lenient,
+            "Lenient query decoding"
+        );
+
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--directory",
+            "/tmp/files",
+            "--sniff-content-type",
+        ]))
+        .unwrap();
+        assert_eq!(config.files_dir, "/tmp/files", "Files directory");
+        assert!(config.sniff_content_type, "Content-Type sniffing enabled");
+
+        assert!(
+            ServerConfig::from_args(&args(&["server", "--directory", ""])).is_err(),
+            "Empty files directory"
+        );
+
+        let config = ServerConfig::from_args(&args(&["server", "--favicon"])).unwrap();
+        assert!(config.favicon, "Favicon enabled");
+
+        let config = ServerConfig::from_args(&args(&["server", "--upload-no-content"])).unwrap();
+        assert!(config.upload_no_content, "204 for uploads");
+        assert!(!config.allow_dotfiles, "Dotfiles denied");
+
+        let config = ServerConfig::from_args(&args(&["server", "--allow-dotfiles"])).unwrap();
+        assert!(config.allow_dotfiles, "Dotfiles allowed");
+
+        let config = ServerConfig::from_args(&args(&["server", "--keep-raw-requests"])).unwrap();
+        assert!(config.keep_raw_requests, "Raw requests kept");
+
+        let config = ServerConfig::from_args(&args(&["server", "--eof-body"])).unwrap();
+        assert!(config.eof_body, "EOF-delimited bodies");
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--canonical-header-order"])).unwrap();
+        assert!(config.canonical_header_order, "Canonical header order");
+
+        let config = ServerConfig::from_args(&args(&["server", "--maintenance"])).unwrap();
+        assert!(config.maintenance, "Maintenance mode");
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--shutdown-token", "secret"])).unwrap();
+        assert_eq!(
+            config.shutdown_token.as_deref(),
+            Some("secret"),
+            "Shutdown token"
+        );
+        assert!(
+            ServerConfig::from_args(&args(&["server", "--shutdown-token", ""])).is_err(),
+            "Empty shutdown token"
+        );
+
+        let config = ServerConfig::from_args(&args(&["server", "--detect-gzip-bodies"])).unwrap();
+        assert!(config.detect_gzip_bodies, "Gzip bodies detected");
+
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--path-decoding",
+            "lenient",
+            "--query-decoding",
+            "strict",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.path_decoding,
+            DecodePolicy::Lenient,
+            "Lenient path decoding"
+        );
+        assert_eq!(
+            config.query_decoding,
+            DecodePolicy::Strict,
+            "Strict query decoding"
+        );
+
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--max-request-line",
+            "100",
+            "--max-headers",
+            "10",
+            "--max-header-bytes",
+            "1000",
+            "--max-body-size",
+            "2000",
+            "--read-timeout",
+            "3",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.limits,
+            Limits {
+                max_request_line: 100,
+                max_header_count: 10,
+                max_header_bytes: 1000,
+                max_body_size: 2000,
+                read_timeout: Duration::from_secs(3),
+            },
+            "Configured limits"
+        );
+
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--read-buffer-size",
+            "512",
+            "--read-buffer-growth",
+            "1024",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.read_buffer,
+            BufferStrategy {
+                initial_size: 512,
+                growth: BufferGrowth::Fixed(1024),
+            },
+            "Read buffer"
+        );
+
+        for (arg, value) in [
+            ("--read-buffer-size", "0"),
+            ("--read-buffer-size", "big"),
+            ("--read-buffer-growth", "0"),
+            ("--read-buffer-growth", "triple"),
+        ] {
+            assert_eq!(
+                ServerConfig::from_args(&args(&["server", arg, value])).unwrap_err(),
+                ConfigError::invalid_value(arg, value),
+                "Invalid {} {}",
+                arg,
+                value
+            );
+        }
+
+        let err = ServerConfig::from_args(&args(&["server", "--port", "abc"])).unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError::invalid_value("--port", "abc"),
+            "Invalid port"
+        );
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--max-generated-bytes", "1024"])).unwrap();
+        assert_eq!(config.max_generated_bytes, 1024, "Max generated bytes");
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--max-requests-per-connection", "10"]))
+                .unwrap();
+        assert_eq!(
+            config.max_requests_per_connection, 10,
+            "Max requests per connection"
+        );
+
+        let config = ServerConfig::from_args(&args(&["server", "--max-delay", "3"])).unwrap();
+        assert_eq!(config.max_delay, Duration::from_secs(3), "Max delay");
+        assert_eq!(config.request_deadline, None, "No request deadline");
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--request-deadline", "30"])).unwrap();
+        assert_eq!(
+            config.request_deadline,
+            Some(Duration::from_secs(30)),
+            "Request deadline"
+        );
+        assert!(
+            ServerConfig::from_args(&args(&["server", "--request-deadline", "0"])).is_err(),
+            "Zero request deadline"
+        );
+
+        let config = ServerConfig::from_args(&args(&["server", "--backlog", "128"])).unwrap();
+        assert_eq!(config.backlog, 128, "Backlog");
+
+        for backlog in ["0", "65536", "-1", "many"] {
+            let err =
+                ServerConfig::from_args(&args(&["server", "--backlog", backlog])).unwrap_err();
+            assert_eq!(
+                err,
+                ConfigError::invalid_value("--backlog", backlog),
+                "Invalid backlog {}",
+                backlog
+            );
+        }
+
+        let err = ServerConfig::from_args(&args(&["server", "--directory"])).unwrap_err();
+        assert_eq!(
+            err,
+            ConfigError("Missing value for --directory".to_string()),
+            "Missing value"
+        );
+    }
+
+    #[test]
+    fn test_is_compressible() {
+        let config = ServerConfig::default();
+        assert!(config.is_compressible("text/plain"), "Text");
+        assert!(
+            config.is_compressible("Application/JSON; charset=utf-8"),
+            "JSON with parameters"
+        );
+        assert!(!config.is_compressible("image/png"), "PNG");
+        assert!(!config.is_compressible(""), "Empty type");
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--compress-types", "image/, text/html"]))
+                .unwrap();
+        assert!(config.is_compressible("image/png"), "Configured prefix");
+        assert!(config.is_compressible("text/html"), "Configured type");
+        assert!(!config.is_compressible("text/plain"), "Not configured");
+    }
+
+    #[test]
+    fn test_file_path() {
+        let config = ServerConfig::default();
+        assert_eq!(
+            config.file_path("etc/passwd"),
+            "./etc/passwd",
+            "Working directory without a configured directory, not the filesystem root"
+        );
+
+        let config =
+            ServerConfig::from_args(&args(&["server", "--directory", "/tmp/files"])).unwrap();
+        assert_eq!(
+            config.file_path("a.txt"),
+            "/tmp/files/a.txt",
+            "Configured directory"
+        );
+    }
+
+    #[test]
+    fn test_mime_override() {
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--mime",
+            ".MD=text/markdown",
+            "--mime",
+            "Makefile=text/x-makefile",
+            "--mime",
+            "notes.md=text/plain",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config.mime_override("README.md"),
+            Some("text/markdown"),
+            "Extension override"
+        );
+        assert_eq!(
+            config.mime_override("docs/INDEX.Md"),
+            Some("text/markdown"),
+            "Case-insensitive extension in a subdirectory"
+        );
+        assert_eq!(
+            config.mime_override("notes.md"),
+            Some("text/plain"),
+            "File name override wins over the extension"
+        );
+        assert_eq!(
+            config.mime_override("Makefile"),
+            Some("text/x-makefile"),
+            "File without extension"
+        );
+        assert_eq!(config.mime_override("a.txt"), None, "No override");
+
+        for invalid in ["md", ".md=", "=text/plain", ".=text/plain"] {
+            assert!(
+                ServerConfig::from_args(&args(&["server", "--mime", invalid])).is_err(),
+                "Invalid override {:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_listen_addrs() {
+        let config = ServerConfig::default();
+        assert_eq!(
+            config.listen_addrs(),
+            vec![(DEFAULT_HOST.to_string(), DEFAULT_PORT)],
+            "Default address"
+        );
+
+        let config = ServerConfig::from_args(&args(&[
+            "server",
+            "--host",
+            "127.0.0.1",
+            "--host",
+            "::1",
+            "--port",
+            "8080",
+            "--port",
+            "8081",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.listen_addrs(),
+            vec![
+                ("127.0.0.1".to_string(), 8080),
+                ("127.0.0.1".to_string(), 8081),
+                ("::1".to_string(), 8080),
+                ("::1".to_string(), 8081),
+            ],
+            "Every host combined with every port"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use crate::http::{Request, ResponseBuilder, StatusCode};
+
+/// # Cross-origin resource sharing policy.
+///
+/// Decides which origins browsers may call the server from, and what the preflight requests
+/// they send first (`OPTIONS` with `Origin` and `Access-Control-Request-Method`) are answered
+/// with. Installed for all routes with [`Router::cors`](crate::router::Router::cors).
+///
+/// The default policy allows any origin and whatever request headers the preflight asks for.
+///
+/// # Example
+///
+/// ```
+/// let policy = CorsPolicy::new()
+///     .allow_origin("https://example.com")
+///     .allow_headers(&["Content-Type", "Authorization"])
+///     .max_age(Duration::from_secs(600));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CorsPolicy {
+    /// Origins allowed to make requests, any origin if empty
+    origins: Vec<String>,
+    /// Request headers allowed, the ones the preflight asks for if `None`
+    headers: Option<Vec<String>>,
+    /// How long browsers may cache the preflight response
+    max_age: Option<Duration>,
+}
+
+impl CorsPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow requests from an origin (`<scheme>://<host>[:<port>]`). Can be called repeatedly;
+    /// once an origin is allowed, the others aren't anymore.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into());
+        self
+    }
+
+    /// Allow these request headers only, instead of any header the preflight asks for
+    pub fn allow_headers(mut self, headers: &[&str]) -> Self {
+        self.headers = Some(headers.iter().map(|header| header.to_string()).collect());
+        self
+    }
+
+    /// Let browsers cache the preflight response for this long
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// # Add the CORS headers to the response to a preflight request.
+    ///
+    /// The allowed methods are the ones of the `Allow` header of the response. A request
+    /// without `Origin`, or from an origin the policy doesn't allow, gets no CORS headers, so
+    /// the browser blocks the actual request.
+    pub fn preflight(
+        &self,
+        request: &Request,
+        response: ResponseBuilder<StatusCode>,
+    ) -> ResponseBuilder<StatusCode> {
+        let Some(origin) = request.header("Origin") else {
+            return response;
+        };
+
+        let mut headers = Vec::new();
+
+        match self.origins.is_empty() {
+            true => headers.push(("Access-Control-Allow-Origin", "*".to_string())),
+            false if self.origins.iter().any(|allowed| allowed == origin) => {
+                headers.push(("Access-Control-Allow-Origin", origin.to_string()));
+                // The response depends on the origin, so caches must keep one per origin
+                headers.push(("Vary", "Origin".to_string()));
+            }
+            false => return response,
+        }
+
+        if let Some(methods) = response.header_value("Allow") {
+            headers.push(("Access-Control-Allow-Methods", methods.to_string()));
+        }
+
+        let allowed_headers = match &self.headers {
+            Some(allowed) => Some(allowed.join(", ")),
+            None => request
+                .header("Access-Control-Request-Headers")
+                .map(str::to_string),
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            headers.push(("Access-Control-Allow-Headers", allowed_headers));
+        }
+
+        if let Some(max_age) = self.max_age {
+            headers.push(("Access-Control-Max-Age", max_age.as_secs().to_string()));
+        }
+
+        response.with_headers(
+            headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preflight(policy: &CorsPolicy, headers: &str) -> Vec<(String, String)> {
+        let request = Request::try_from(
+            format!(
+                "OPTIONS /echo/a HTTP/1.1\r\nAccess-Control-Request-Method: PUT\r\n{}\r\n",
+                headers
+            )
+            .as_str(),
+        )
+        .unwrap();
+        let response = ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .with(("Allow", "GET, PUT, OPTIONS"))
+            .without_content_length_header();
+
+        let mut headers = policy.preflight(&request, response).build().headers;
+        headers.retain(|(name, _)| name != "Allow");
+        headers
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_preflight() {
+        //======================================================================
+        // Test for the default policy
+        let policy = CorsPolicy::new();
+        assert_eq!(
+            preflight(
+                &policy,
+                "Origin: https://a.example\r\nAccess-Control-Request-Headers: X-Token\r\n"
+            ),
+            headers(&[
+                ("Access-Control-Allow-Origin", "*"),
+                ("Access-Control-Allow-Methods", "GET, PUT, OPTIONS"),
+                ("Access-Control-Allow-Headers", "X-Token"),
+            ]),
+            "Any origin, requested headers allowed"
+        );
+        assert!(
+            preflight(&policy, "").is_empty(),
+            "No CORS headers without Origin"
+        );
+
+        //======================================================================
+        // Test for a restrictive policy
+        let policy = CorsPolicy::new()
+            .allow_origin("https://a.example")
+            .allow_headers(&["Content-Type", "Authorization"])
+            .max_age(Duration::from_secs(600));
+        assert_eq!(
+            preflight(
+                &policy,
+                "Origin: https://a.example\r\nAccess-Control-Request-Headers: X-Token\r\n"
+            ),
+            headers(&[
+                ("Access-Control-Allow-Origin", "https://a.example"),
+                ("Vary", "Origin"),
+                ("Access-Control-Allow-Methods", "GET, PUT, OPTIONS"),
+                (
+                    "Access-Control-Allow-Headers",
+                    "Content-Type, Authorization"
+                ),
+                ("Access-Control-Max-Age", "600"),
+            ]),
+            "Allowed origin"
+        );
+        assert!(
+            preflight(&policy, "Origin: https://b.example\r\n").is_empty(),
+            "No CORS headers for another origin"
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::HashMap, fmt::Debug, future::Future, io, pin::Pin, time::SystemTime};
+
+/// Boxed future returned by the object-safe async traits of the server
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// # Storage the file handlers read from and write to.
+///
+/// Paths are passed exactly as the handlers build them with
+/// [`ServerConfig::file_path`](crate::config::ServerConfig::file_path). The server uses
+/// [`DiskStore`] which delegates to `tokio::fs`; tests use the in-memory `MemoryStore` so they
+/// don't touch the real filesystem.
+pub trait FileStore: Debug + Send + Sync {
+    /// Read the whole content of the file
+    fn read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>>;
+
+    /// Create the file (or truncate an existing one) and write the content to it
+    fn write<'a>(&'a self, path: &'a str, content: &'a [u8]) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Delete the file
+    fn delete<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Get the metadata of the file
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<FileMetadata>>;
+
+    /// List the entries of the directory, in no particular order
+    fn list<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<DirEntry>>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Size of the file in bytes
+    pub len: u64,
+    /// Last modification time, if the platform supports it
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+/// Entry of a directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Name of the file, without the directory
+    pub name: String,
+    pub metadata: FileMetadata,
+}
+
+/// # File store backed by the real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskStore;
+
+impl FileStore for DiskStore {
+    fn read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>> {
+        Box::pin(tokio::fs::read(path))
+    }
+
+    fn write<'a>(&'a self, path: &'a str, content: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            use tokio::io::AsyncWriteExt;
+
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .await?;
+
+            file.write_all(content).await?;
+
+            // Make sure the content reaches the file before reporting success
+            file.flush().await
+        })
+    }
+
+    fn delete<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(tokio::fs::remove_file(path))
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<FileMetadata>> {
+        Box::pin(async move {
+            let metadata = tokio::fs::metadata(path).await?;
+
+            Ok(FileMetadata {
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+                is_dir: metadata.is_dir(),
+            })
+        })
+    }
+
+    fn list<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<DirEntry>>> {
+        Box::pin(async move {
+            let mut dir = tokio::fs::read_dir(path).await?;
+            let mut entries = Vec::new();
+
+            while let Some(entry) = dir.next_entry().await? {
+                // Follow symlinks, so they're listed like their targets
+                let metadata = tokio::fs::metadata(entry.path()).await?;
+
+                entries.push(DirEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    metadata: FileMetadata {
+                        len: metadata.len(),
+                        modified: metadata.modified().ok(),
+                        is_dir: metadata.is_dir(),
+                    },
+                });
+            }
+
+            Ok(entries)
+        })
+    }
+}
+
+/// # Files compiled into the binary.
+///
+/// Lets a single binary serve static assets without a files directory: the map is built at
+/// startup from `include_bytes!` and keyed by the file name as requested under `/files/`.
+/// The `/files/` handler looks a file up here before it asks the [`FileStore`], so embedded
+/// assets take precedence and are never written or deleted.
+///
+/// # Example
+///
+/// ```
+/// let assets = EmbeddedAssets::new([
+///     ("index.html", include_bytes!("../assets/index.html").as_slice()),
+///     ("app.js", include_bytes!("../assets/app.js").as_slice()),
+/// ]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedAssets {
+    files: HashMap<&'static str, &'static [u8]>,
+}
+
+impl EmbeddedAssets {
+    pub fn new(files: impl IntoIterator<Item = (&'static str, &'static [u8])>) -> Self {
+        Self {
+            files: files.into_iter().collect(),
+        }
+    }
+
+    /// Get the content of an embedded file
+    pub fn get(&self, name: &str) -> Option<&'static [u8]> {
+        self.files.get(name).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// # In-memory file store for tests.
+///
+/// Files are kept in a map keyed by their path. A read-only store rejects all writes and
+/// deletes with `PermissionDenied`, which lets tests exercise the handlers' error paths.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    files: std::sync::Mutex<std::collections::HashMap<String, (Vec<u8>, SystemTime)>>,
+    read_only: bool,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Default::default()
+        }
+    }
+
+    /// Add a file to the store
+    pub fn with_file(self, path: &str, content: impl Into<Vec<u8>>) -> Self {
+        self.with_modified_file(path, content, SystemTime::now())
+    }
+
+    /// Add a file with the given modification time to the store
+    pub fn with_modified_file(
+        self,
+        path: &str,
+        content: impl Into<Vec<u8>>,
+        modified: SystemTime,
+    ) -> Self {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), (content.into(), modified));
+        self
+    }
+
+    /// Get the content of a file in the store
+    pub fn get(&self, path: &str) -> Option<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|(content, _)| content.clone())
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        match self.read_only {
+            true => Err(io::ErrorKind::PermissionDenied.into()),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl FileStore for MemoryStore {
+    fn read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<u8>>> {
+        Box::pin(async move { self.get(path).ok_or_else(|| io::ErrorKind::NotFound.into()) })
+    }
+
+    fn write<'a>(&'a self, path: &'a str, content: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_string(), (content.to_vec(), SystemTime::now()));
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            self.check_writable()?;
+            self.files
+                .lock()
+                .unwrap()
+                .remove(path)
+                .map(|_| ())
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<FileMetadata>> {
+        Box::pin(async move {
+            self.files
+                .lock()
+                .unwrap()
+                .get(path)
+                .map(|(content, modified)| FileMetadata {
+                    len: content.len() as u64,
+                    modified: Some(*modified),
+                    is_dir: false,
+                })
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        })
+    }
+
+    /// Directories are implied by the paths of the files in them
+    fn list<'a>(&'a self, path: &'a str) -> BoxFuture<'a, io::Result<Vec<DirEntry>>> {
+        Box::pin(async move {
+            let prefix = format!("{}/", path.trim_end_matches('/'));
+            let mut entries: Vec<DirEntry> = Vec::new();
+
+            for (file_path, (content, modified)) in self.files.lock().unwrap().iter() {
+                let Some(rest) = file_path.strip_prefix(&prefix) else {
+                    continue;
+                };
+
+                let entry = match rest.split_once('/') {
+                    Some((dir_name, _)) => DirEntry {
+                        name: dir_name.to_string(),
+                        metadata: FileMetadata {
+                            len: 0,
+                            modified: None,
+                            is_dir: true,
+                        },
+                    },
+                    None => DirEntry {
+                        name: rest.to_string(),
+                        metadata: FileMetadata {
+                            len: content.len() as u64,
+                            modified: Some(*modified),
+                            is_dir: false,
+                        },
+                    },
+                };
+
+                if !entries.iter().any(|listed| listed.name == entry.name) {
+                    entries.push(entry);
+                }
+            }
+
+            match entries.is_empty() {
+                true => Err(io::ErrorKind::NotFound.into()),
+                false => Ok(entries),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_disk_store() {
+        let root_dir = env!("CARGO_MANIFEST_DIR");
+        let tmp_dir = format!("{}/tmp_disk_store", root_dir);
+        let path = format!("{}/test.txt", tmp_dir);
+
+        // Create temporary directory if it doesn't exist
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+
+        let store = DiskStore;
+
+        store.write(&path, b"Hello World").await.unwrap();
+        assert_eq!(store.read(&path).await.unwrap(), b"Hello World");
+
+        let metadata = store.metadata(&path).await.unwrap();
+        assert_eq!(metadata.len, 11, "File size");
+        assert!(!metadata.is_dir, "Regular file");
+
+        let entries = store.list(&tmp_dir).await.unwrap();
+        assert_eq!(entries.len(), 1, "One entry");
+        assert_eq!(entries[0].name, "test.txt", "Entry name");
+        assert_eq!(entries[0].metadata, metadata, "Entry metadata");
+
+        store.delete(&path).await.unwrap();
+        assert_eq!(
+            store.read(&path).await.unwrap_err().kind(),
+            io::ErrorKind::NotFound,
+            "File is deleted"
+        );
+
+        // Remove temporary directory and its contents
+        std::fs::remove_dir_all(tmp_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new().with_file("/files/a.txt", "abc");
+
+        assert_eq!(store.read("/files/a.txt").await.unwrap(), b"abc");
+        assert_eq!(store.metadata("/files/a.txt").await.unwrap().len, 3);
+
+        store.write("/files/b.txt", b"def").await.unwrap();
+        assert_eq!(store.get("/files/b.txt"), Some(b"def".to_vec()));
+
+        store.write("/files/dir/c.txt", b"ghi").await.unwrap();
+        let mut names = store
+            .list("/files")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.metadata.is_dir))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("a.txt".to_string(), false),
+                ("b.txt".to_string(), false),
+                ("dir".to_string(), true)
+            ],
+            "Listing with an implied directory"
+        );
+
+        store.delete("/files/a.txt").await.unwrap();
+        assert_eq!(store.get("/files/a.txt"), None);
+
+        let store = MemoryStore::read_only();
+        assert_eq!(
+            store
+                .write("/files/a.txt", b"abc")
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::PermissionDenied,
+            "Read-only store rejects writes"
+        );
+    }
+}
@@ -1,315 +1,4013 @@
+use std::{
+    future::Future,
+    ops::{ControlFlow, Range},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use tokio::{
-    fs::OpenOptions,
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::TcpStream,
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    time::Instant,
+};
+
+use crate::{
+    config::ServerConfig,
+    file_store::FileMetadata,
+    http::{
+        format_http_date, mime_from_extension, parse_http_date, percent_decode, sniff_mime,
+        BodyStream, Method, ParseRequestError, ParseRequestErrorKind, Request, RequestReader,
+        Response, ResponseBuilder, StatusCode, Version,
+    },
+    listing::{render_listing, ListingOptions},
+    router::{RouteContext, Router, StreamHandler},
+    stats::{ConnectionStats, CountingStream},
+    upgrade::Upgraded,
 };
 
-use crate::http::{Method, ParseRequestError, Request, ResponseBuilder, StatusCode};
+/// Time a keep-alive connection may stay idle before the server closes it
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Icon served at `/favicon.ico` when enabled in the config
+const FAVICON: &[u8] = include_bytes!("../assets/favicon.ico");
+
+/// Gzip-encoded bodies larger than this are compressed while they're sent, as a chunked response
+const GZIP_STREAMING_THRESHOLD: usize = 64 * 1024;
+
+/// # Serve all requests sent over a connection.
+///
+/// Connections are persistent: requests are read and answered one after another until the
+/// client closes the connection, sends `Connection: close`, or stays idle for longer than
+/// [`KEEP_ALIVE_TIMEOUT`].
+///
+/// At most `max_pipelined_requests` requests pipelined by the client (sent without waiting for
+/// the previous responses) are answered; the response to the last one closes the connection.
+/// The same goes for the `max_requests_per_connection`-th request on the connection. Until
+/// then, the `max` of the `Keep-Alive` header tells clients asking for it how many requests are
+/// left.
+///
+/// The handling of a request is cancelled if the client closes the connection before the
+/// response is ready.
+///
+/// Once the server is shutting down, the connection is closed after the current response, or
+/// right away if it's waiting for the next request.
+///
+/// With a `request_deadline` in the config, a request that isn't answered in time is answered
+/// with `504 Gateway Timeout` instead, and a response that can't be written in time is cut off.
+/// Either way the connection is closed.
+///
+/// A request asking to upgrade to a protocol registered in the config is answered with
+/// `101 Switching Protocols`, after which the connection is handed over to the protocol's
+/// handler.
+///
+/// The traffic of the connection is logged when it closes.
+pub async fn handle_connection<S>(stream: S, config: &ServerConfig) -> Result<(), ParseRequestError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    handle_connection_with_stats(stream, config, Arc::new(ConnectionStats::default())).await
+}
+
+/// # Serve a connection, recording its traffic in `stats`.
+///
+/// See [`handle_connection`]. The summary of the stats is logged when the connection closes,
+/// whether it closed cleanly or not.
+pub async fn handle_connection_with_stats<S>(
+    stream: S,
+    config: &ServerConfig,
+    stats: Arc<ConnectionStats>,
+) -> Result<(), ParseRequestError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let result = serve_connection(CountingStream::new(stream, stats.clone()), config, &stats).await;
+
+    eprintln!("{}", stats);
+
+    result
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    config: &ServerConfig,
+    stats: &ConnectionStats,
+) -> Result<(), ParseRequestError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut reader = RequestReader::new(stream)
+        .with_limits(config.limits)
+        .with_http09(config.allow_http09)
+        .with_raw(config.keep_raw_requests)
+        .with_eof_body(config.eof_body)
+        .with_buffer(config.read_buffer)
+        .with_idle_timeout(KEEP_ALIVE_TIMEOUT);
+
+    // Number of consecutive requests that were already buffered when the server got to them
+    let mut pipeline_depth = 0;
+    // Number of requests read on the connection
+    let mut request_count = 0;
+
+    loop {
+        pipeline_depth = match reader.buffered().is_empty() {
+            true => 1,
+            false => pipeline_depth + 1,
+        };
+
+        let idle = reader.buffered().is_empty();
+        let read = tokio::select! {
+            read = reader.read_head() => read,
+            // Don't keep the server from shutting down by waiting for a next request
+            _ = config.shutdown.triggered(), if idle => break,
+        };
+
+        let request = match read {
+            Ok(request) => request,
+            Err(err) => return reject_request(reader.get_mut(), err).await,
+        };
+
+        // The client closed the connection, or it stayed idle for too long
+        let Some(mut request) = request else {
+            break;
+        };
+
+        stats.add_request();
+        request_count += 1;
+
+        let deadline = config
+            .request_deadline
+            .map(|limit| reader.request_started().unwrap_or_else(Instant::now) + limit);
+
+        // A route reading the body as a stream gets it unbuffered
+        let path = percent_decode(request.path(), config.path_decoding).ok();
+        let stream_route = path
+            .as_deref()
+            .and_then(|path| config.router.find_stream(request.method, path));
+
+        if stream_route.is_none() {
+            let err = match until_deadline(deadline, reader.read_body(&mut request)).await {
+                Some(Ok(())) => None,
+                Some(Err(err)) => Some(err),
+                None => Some(ParseRequestError {
+                    kind: ParseRequestErrorKind::DeadlineExceeded,
+                }),
+            };
+
+            if let Some(err) = err {
+                return reject_request(reader.get_mut(), err).await;
+            }
+        }
+
+        config.interceptor.request(&mut request);
+
+        if let Some((protocol, handler)) = config.upgrades.find(&request) {
+            let response = ResponseBuilder::switching_protocols(protocol).build();
+            let stream = reader.get_mut();
+
+            stream.write_all(&response.to_bytes_vec()).await?;
+            stream.flush().await?;
+
+            let (stream, leftover) = reader.into_parts();
+            let upgraded = Upgraded {
+                stream: Box::new(stream),
+                leftover,
+            };
+
+            handler(request, upgraded).await;
+
+            return Ok(());
+        }
+
+        // HTTP/0.9 has no headers and the connection ends with the response
+        if request.version == Version::Http09 {
+            let mut response = handle_request(&request, config).await;
+            config.interceptor.response(&request, &mut response);
+            let stream = reader.get_mut();
+
+            stream.write_all(&response.to_http09_bytes_vec()).await?;
+            stream.flush().await?;
+
+            return Ok(());
+        }
+
+        // Closing the connection after the last allowed pipelined request bounds the resources
+        // a single connection can use; the client has to resend the rest of the pipeline
+        let close_connection = request
+            .header("Connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+            || pipeline_depth >= config.max_pipelined_requests
+            || request_count >= config.max_requests_per_connection;
+
+        let half_closed =
+            stream_route.is_none() && (reader.reached_eof() || reader.closed_now().await);
+
+        let response = match stream_route {
+            Some((handler, tail)) => {
+                let path = path.as_deref().unwrap_or_default();
+                let body = reader.body_stream();
+                let response =
+                    handle_streaming_request(&request, path, tail, handler, body, config);
+
+                until_deadline(deadline, response).await
+            }
+
+            // Stop working on the response if the client goes away in the meantime. A client
+            // that half-closed the connection right after the request (or to end an
+            // EOF-delimited body) still waits for it.
+            None if half_closed => until_deadline(deadline, handle_request(&request, config)).await,
+            None => tokio::select! {
+                // A response that is ready right away is sent even to a half-closed connection
+                biased;
+                response = until_deadline(deadline, handle_request(&request, config)) => response,
+                _ = reader.closed() => break,
+            },
+        };
+
+        // A request past its deadline isn't worth keeping the connection for
+        let deadline_exceeded = response.is_none();
+        let mut response = response.unwrap_or_else(|| {
+            ResponseBuilder::new()
+                .with_status_code(StatusCode::GatewayTimeout)
+                .build()
+        });
+
+        // The server is shutting down, possibly because of this very request
+        let close_connection =
+            close_connection || deadline_exceeded || config.shutdown.is_triggered();
+
+        if close_connection {
+            response.set_connection("close");
+        } else if wants_keep_alive_header(&request, &response) {
+            let keep_alive = format!(
+                "timeout={}, max={}",
+                KEEP_ALIVE_TIMEOUT.as_secs(),
+                config.max_requests_per_connection - request_count
+            );
+            response.set_header("Keep-Alive", keep_alive);
+        }
+
+        if config.canonical_header_order {
+            response.sort_headers_canonically();
+        }
+
+        config.interceptor.response(&request, &mut response);
+
+        let stream = reader.get_mut();
+
+        // The client gets a truncated response at worst, so just drop the connection
+        match until_deadline(deadline, response.write_to(stream)).await {
+            Some(Ok(_)) => (),
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                break;
+            }
+            None => {
+                eprintln!("Request deadline exceeded while writing the response");
+                break;
+            }
+        }
+
+        if close_connection {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `future` to completion, unless the deadline passes first
+async fn until_deadline<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Whether the response to a request on a connection kept alive tells the client how many more
+/// requests it may send: when the client asked for `Connection: keep-alive`, or the route set a
+/// `Keep-Alive` header of its own
+fn wants_keep_alive_header(request: &Request, response: &Response) -> bool {
+    let asked = request.header("Connection").is_some_and(|value| {
+        value
+            .split(',')
+            .any(|option| option.trim().eq_ignore_ascii_case("keep-alive"))
+    });
+
+    asked
+        || response
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Keep-Alive"))
+}
+
+/// Answer a request that couldn't be read with the status code of the error, and close the
+/// connection by returning the error
+async fn reject_request<S>(stream: &mut S, err: ParseRequestError) -> Result<(), ParseRequestError>
+where
+    S: AsyncWrite + Unpin,
+{
+    if let Some(status_code) = err.status_code() {
+        let response = ResponseBuilder::new()
+            .with_status_code(status_code)
+            .with(("Connection", "close"))
+            .build();
+
+        // The connection is closed either way, so a failed write changes nothing
+        let _ = response.write_to(stream).await;
+    }
+
+    Err(err)
+}
+
+/// Build the response to a single request
+async fn handle_request(request: &Request, config: &ServerConfig) -> Response {
+    let path = match percent_decode(request.path(), config.path_decoding) {
+        Ok(path) => path,
+        // Malformed percent-encoding is ambiguous, so don't guess which resource was meant
+        Err(_) => return ResponseBuilder::bad_request().build(),
+    };
+    let path = path.as_str();
+
+    let response_builder = match config.router.run_middleware(request, config) {
+        // Middleware answering the request itself
+        ControlFlow::Break(response) => response,
+        ControlFlow::Continue(()) => dispatch(request, path, config).await,
+    };
+
+    finish_response(request, response_builder, config)
+}
+
+/// # Build the response to a request whose route reads the body as a stream.
+///
+/// The middleware runs first, as for any other request.
+async fn handle_streaming_request<R>(
+    request: &Request,
+    path: &str,
+    tail: &str,
+    handler: StreamHandler,
+    mut body: BodyStream<'_, R>,
+    config: &ServerConfig,
+) -> Response
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let response_builder = match config.router.run_middleware(request, config) {
+        ControlFlow::Break(response) => response,
+        ControlFlow::Continue(()) => {
+            let context = RouteContext {
+                request,
+                config,
+                path,
+                tail,
+            };
+            let response = handler(context, &mut body).await;
+            config.router.apply_cache_control(path, response)
+        }
+    };
+
+    finish_response(request, response_builder, config)
+}
+
+/// Compress the response if the client accepts it and build it
+fn finish_response(
+    request: &Request,
+    response_builder: ResponseBuilder<StatusCode>,
+    config: &ServerConfig,
+) -> Response {
+    // Only compress the response types configured as compressible, and bodies that aren't
+    // encoded already. The `Content-Range` of a partial body refers to the bytes as they are.
+    let compressible = response_builder
+        .header_value("Content-Type")
+        .is_some_and(|content_type| config.is_compressible(content_type))
+        && response_builder.header_value("Content-Encoding").is_none()
+        && response_builder.header_value("Content-Range").is_none();
+    let gzip = compressible && accepts_encoding(request, "gzip");
+
+    // A body sent as is needs the client to accept the identity coding
+    let encoded = gzip || response_builder.header_value("Content-Encoding").is_some();
+    if !encoded && response_builder.body_len() > 0 && refuses_identity(request) {
+        return ResponseBuilder::new()
+            .with_status_code(StatusCode::NotAcceptable)
+            .with(("Vary", "Accept-Encoding"))
+            .build();
+    }
+
+    match gzip {
+        true => {
+            let response_builder = response_builder.with(("Content-Encoding", "gzip"));
+
+            match response_builder.body_len() > GZIP_STREAMING_THRESHOLD {
+                true => response_builder.chunked().build(),
+                false => response_builder.build(),
+            }
+        }
+        false => response_builder.build(),
+    }
+}
+
+/// Build the response of the route handling the request
+async fn dispatch(
+    request: &Request,
+    path: &str,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let method = request.method;
+
+    match config.router.find(method, path) {
+        _ if method == Method::Trace => get_trace_response(request),
+
+        // Without a route of its own, OPTIONS gets a default answer instead of whatever
+        // a catch-all route would do with it
+        _ if method == Method::Options && !config.router.has_route_for(method, path) => {
+            get_options_response(path, config)
+        }
+
+        Some((handler, tail)) => {
+            let context = RouteContext {
+                request,
+                config,
+                path,
+                tail,
+            };
+            let response = handler(context).await;
+            config.router.apply_cache_control(path, response)
+        }
+
+        None => get_not_found_response(path, config),
+    }
+}
+
+/// # The endpoints of the server.
+///
+/// This is the default router of [`ServerConfig`].
+pub fn router() -> Router {
+    Router::new()
+        .middleware(maintenance_mode)
+        .any("/", |_| Box::pin(async { get_root_response() }))
+        .any("/user-agent", |ctx| {
+            Box::pin(async move { get_user_agent_response(ctx.request) })
+        })
+        .route(Method::Post, "/echo-body", |ctx| {
+            Box::pin(async move { post_echo_body_response(ctx.request, ctx.config) })
+        })
+        .any("/echo/*", |ctx| {
+            Box::pin(async move { get_echo_response(ctx.tail) })
+        })
+        .methods(&[Method::Post, Method::Put], "/files/*", |ctx| {
+            Box::pin(post_file_response(ctx.tail, ctx.request, ctx.config))
+        })
+        .route(Method::Delete, "/files/*", |ctx| {
+            Box::pin(delete_file_response(ctx.tail, ctx.config))
+        })
+        .any("/files/*", |ctx| {
+            Box::pin(async move {
+                if is_denied_path(ctx.tail, ctx.config) {
+                    return ResponseBuilder::not_found().without_content_length_header();
+                }
+
+                match ctx.tail.is_empty() || ctx.tail.ends_with('/') {
+                    true => get_directory_response(ctx.tail, ctx.request, ctx.config).await,
+                    false => get_conditional_file_response(ctx.tail, ctx.request, ctx.config).await,
+                }
+            })
+        })
+        .route(Method::Get, "/favicon.ico", |ctx| {
+            Box::pin(async move { get_favicon_response(ctx.path, ctx.config) })
+        })
+        .route(Method::Get, "/bytes/*", |ctx| {
+            Box::pin(async move { get_bytes_response(ctx.tail, ctx.config) })
+        })
+        .route(Method::Get, "/delay/*", |ctx| {
+            Box::pin(get_delay_response(ctx.tail, ctx.config))
+        })
+        .route(Method::Get, "/status/*", |ctx| {
+            Box::pin(async move { get_status_response(ctx.tail) })
+        })
+        .route(Method::Post, "/admin/shutdown", |ctx| {
+            Box::pin(async move { post_shutdown_response(ctx.request, ctx.config) })
+        })
+}
+
+/// Middleware answering every request with `503 Service Unavailable` while maintenance mode is
+/// enabled in the config
+fn maintenance_mode(
+    _: &Request,
+    config: &ServerConfig,
+) -> ControlFlow<ResponseBuilder<StatusCode>> {
+    if !config.maintenance {
+        return ControlFlow::Continue(());
+    }
+
+    ControlFlow::Break(
+        ResponseBuilder::new()
+            .with_status_code(StatusCode::ServiceUnavailable)
+            .with(("Content-Type", "text/plain"))
+            .body("Down for maintenance"),
+    )
+}
+
+fn get_root_response() -> ResponseBuilder<StatusCode> {
+    ResponseBuilder::ok()
+        .with(vec![
+            ("Connection", "Keep-Alive"),
+            ("Keep-Alive", "timeout=5, max=1000"),
+        ])
+        // Disable Content-Length header generation to pass codecrafters tests
+        .without_content_length_header()
+}
+
+/// `404 Not Found`, listing the closest registered routes in the body when route suggestions
+/// are enabled in the config
+fn get_not_found_response(path: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    if !config.suggest_routes {
+        return ResponseBuilder::not_found();
+    }
+
+    let suggestions = config.router.suggestions(path);
+
+    if suggestions.is_empty() {
+        return ResponseBuilder::not_found();
+    }
+
+    let body = suggestions.iter().fold(
+        "Not Found. Did you mean:\n".to_string(),
+        |mut acc, pattern| {
+            acc.push_str(&format!("  {}\n", pattern));
+            acc
+        },
+    );
+
+    ResponseBuilder::not_found()
+        .with(("Content-Type", "text/plain"))
+        .body(body)
+}
+
+async fn post_file_response(
+    file_name: &str,
+    request: &Request,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    if is_denied_path(file_name, config) {
+        return ResponseBuilder::not_found().without_content_length_header();
+    }
+
+    let path = config.file_path(file_name);
+
+    match config.file_store.write(&path, &request.body).await {
+        Ok(_) if config.upload_no_content => ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .without_content_length_header(),
+        Ok(_) => ResponseBuilder::created(request.path()).without_content_length_header(),
+        Err(_) => ResponseBuilder::internal_server_error().without_content_length_header(),
+    }
+}
+
+/// # Check whether a path under the files directory is one the server must not touch.
+///
+/// `.` and `..` segments are always denied, since nothing normalizes the path and `..` would
+/// escape the files directory. Unless dotfiles are allowed, any other segment starting with `.`
+/// is denied too, so files in hidden directories (`.git/config`) are covered. The path is the
+/// decoded one, so `%2E%2E` and `%2Eenv` are caught as well.
+fn is_denied_path(path: &str, config: &ServerConfig) -> bool {
+    path.split('/').any(|segment| match segment {
+        "." | ".." => true,
+        _ => !config.allow_dotfiles && segment.starts_with('.'),
+    })
+}
+
+async fn get_file_response(file_name: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    if let Some(asset) = config.embedded_assets.get(file_name) {
+        return ResponseBuilder::ok()
+            .with(("Content-Type", file_content_type(file_name, asset, config)))
+            .static_body(asset);
+    }
+
+    let path = config.file_path(file_name);
+    let file = match config.file_store.read(&path).await {
+        Ok(file) => file,
+        Err(_) => return ResponseBuilder::not_found().without_content_length_header(),
+    };
+
+    let content_type = file_content_type(file_name, &file, config);
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", content_type))
+        .body(file)
+}
+
+/// Precompressed companions of served files (`<file>.br`, `<file>.gz`), in order of preference
+const COMPANIONS: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+/// # Serve a file unless the client's cached copy of it is still current.
+///
+/// Files of the file store are sent with `ETag` and `Last-Modified` validators. A `GET` or
+/// `HEAD` request whose `If-None-Match` or `If-Modified-Since` header shows the client's copy is
+/// current is answered with `304 Not Modified` instead.
+///
+/// When the client accepts it, a precompressed companion of the file is sent instead of the
+/// file (see [`COMPANIONS`]), with the `Content-Type` of the file. Its entity tag is the one of
+/// the file with the encoding appended, as it's a different representation.
+///
+/// A `GET` request for the file itself with a `Range` header gets only the requested bytes
+/// (see [`get_range_response`]), unless its `If-Range` validator shows the file changed since
+/// the client got the rest of it, in which case the whole file is sent (see
+/// [`if_range_matches`]).
+async fn get_conditional_file_response(
+    file_name: &str,
+    request: &Request,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    if config.embedded_assets.get(file_name).is_some() {
+        return get_file_response(file_name, config).await;
+    }
+
+    let metadata = match config
+        .file_store
+        .metadata(&config.file_path(file_name))
+        .await
+    {
+        Ok(metadata) if !metadata.is_dir => metadata,
+        _ => return get_file_response(file_name, config).await,
+    };
+
+    let companion = find_companion(file_name, request, config).await;
+
+    let mut etag = entity_tag(&metadata);
+    if let Some((encoding, _)) = companion {
+        etag.insert_str(etag.len() - 1, &format!("-{}", encoding));
+    }
+    let modified = metadata.modified.map(truncate_to_secs);
+
+    let mut validators = vec![("ETag".to_string(), etag.clone())];
+    validators
+        .extend(modified.map(|modified| ("Last-Modified".to_string(), format_http_date(modified))));
+
+    let is_safe = matches!(request.method, Method::Get | Method::Head);
+    let response = match (
+        is_safe && is_not_modified(request, &etag, modified),
+        &companion,
+    ) {
+        (true, _) => ResponseBuilder::new()
+            .with_status_code(StatusCode::NotModified)
+            .without_content_length_header(),
+        (false, Some((encoding, path))) => {
+            get_companion_response(file_name, path, encoding, config).await
+        }
+        (false, None) => match request.header("Range") {
+            Some(range)
+                if request.method == Method::Get && if_range_matches(request, &etag, modified) =>
+            {
+                get_range_response(file_name, range, config).await
+            }
+            _ => get_file_response(file_name, config).await,
+        },
+    };
+
+    match companion {
+        Some(_) => response
+            .with(("Vary", "Accept-Encoding"))
+            .with_headers(validators),
+        None => response.with_headers(validators),
+    }
+}
+
+/// # Serve the part of a file requested by a `Range` header.
+///
+/// A satisfiable range is answered with `206 Partial Content`, a `Content-Range` header
+/// stating which bytes of the file the body holds and a `Content-Length` of the part only.
+/// A range past the end of the file is answered with `416 Range Not Satisfiable`. A `Range`
+/// header the server doesn't support is ignored and the whole file sent.
+async fn get_range_response(
+    file_name: &str,
+    range: &str,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let file = match config.file_store.read(&config.file_path(file_name)).await {
+        Ok(file) => file,
+        Err(_) => return ResponseBuilder::not_found().without_content_length_header(),
+    };
+
+    let range = match parse_byte_range(range, file.len()) {
+        ByteRange::Satisfiable(range) => range,
+        ByteRange::Unsatisfiable => {
+            return ResponseBuilder::new()
+                .with_status_code(StatusCode::RangeNotSatisfiable)
+                .with((
+                    "Content-Range".to_string(),
+                    format!("bytes */{}", file.len()),
+                ));
+        }
+        ByteRange::Unsupported => return get_file_response(file_name, config).await,
+    };
+
+    let content_type = file_content_type(file_name, &file, config);
+    let content_range = format!("bytes {}-{}/{}", range.start, range.end - 1, file.len());
+
+    ResponseBuilder::new()
+        .with_status_code(StatusCode::PartialContent)
+        .with(("Content-Type", content_type))
+        .with(("Content-Range".to_string(), content_range))
+        .body(&file[range])
+}
+
+/// Byte range requested by a `Range` header
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// Bytes of the resource to send
+    Satisfiable(Range<usize>),
+    /// Range entirely past the end of the resource
+    Unsatisfiable,
+    /// Another unit, several ranges or a malformed header, none of which the server supports
+    Unsupported,
+}
+
+/// # Parse a `Range` header with a single byte range against a resource of `len` bytes.
+///
+/// The range is `bytes=<first>-<last>`, `bytes=<first>-` (up to the end of the resource) or
+/// `bytes=-<suffix length>` (the last bytes). A last byte past the end of the resource is
+/// clamped to it.
+fn parse_byte_range(header: &str, len: usize) -> ByteRange {
+    let Some((unit, range)) = header.trim().split_once('=') else {
+        return ByteRange::Unsupported;
+    };
+    let Some((first, last)) = range.trim().split_once('-') else {
+        return ByteRange::Unsupported;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") || range.contains(',') {
+        return ByteRange::Unsupported;
+    }
+
+    let parse = |position: &str| position.trim().parse::<usize>().ok();
+
+    let (first, last) = match (first.trim(), last.trim()) {
+        ("", suffix) => match parse(suffix) {
+            Some(suffix) => (len.saturating_sub(suffix), len.checked_sub(1)),
+            None => return ByteRange::Unsupported,
+        },
+        (first, "") => match parse(first) {
+            Some(first) => (first, len.checked_sub(1)),
+            None => return ByteRange::Unsupported,
+        },
+        (first, last) => match (parse(first), parse(last)) {
+            (Some(first), Some(last)) if first <= last => {
+                (first, len.checked_sub(1).map(|end| last.min(end)))
+            }
+            _ => return ByteRange::Unsupported,
+        },
+    };
+
+    match last {
+        Some(last) if first < len && first <= last => ByteRange::Satisfiable(first..last + 1),
+        _ => ByteRange::Unsatisfiable,
+    }
+}
+
+/// The encoding and path of the preferred precompressed companion of a file the client accepts
+async fn find_companion(
+    file_name: &str,
+    request: &Request,
+    config: &ServerConfig,
+) -> Option<(&'static str, String)> {
+    for (encoding, extension) in COMPANIONS {
+        if !accepts_encoding(request, encoding) {
+            continue;
+        }
+
+        let path = config.file_path(&format!("{}{}", file_name, extension));
+        if matches!(config.file_store.metadata(&path).await, Ok(metadata) if !metadata.is_dir) {
+            return Some((encoding, path));
+        }
+    }
+
+    None
+}
+
+/// Serve the precompressed companion of a file as the file encoded with `encoding`
+async fn get_companion_response(
+    file_name: &str,
+    path: &str,
+    encoding: &str,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let companion = match config.file_store.read(path).await {
+        Ok(companion) => companion,
+        Err(_) => return ResponseBuilder::not_found().without_content_length_header(),
+    };
+
+    // The content can't be sniffed from the compressed bytes
+    let content_type = file_content_type(file_name, &[], config);
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", content_type))
+        .precompressed(encoding)
+        .body(companion)
+}
+
+/// # Check whether the client accepts a content coding.
+///
+/// The coding must be listed in `Accept-Encoding`, or covered by `*`, with a q-value above 0.
+/// An entry for the coding itself takes precedence over `*`.
+fn accepts_encoding(request: &Request, coding: &str) -> bool {
+    let Some(accept_encoding) = request.header("Accept-Encoding") else {
+        return false;
+    };
+
+    // Entries as (coding, accepted)
+    let entries = accept_encoding
+        .split(',')
+        .map(|entry| {
+            let mut params = entry.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let rejected = params.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    == Some(0.0)
+            });
+            (name, !rejected)
+        })
+        .collect::<Vec<_>>();
+
+    let find = |name: &str| {
+        entries
+            .iter()
+            .find(|(entry, _)| entry.eq_ignore_ascii_case(name))
+            .map(|(_, accepted)| *accepted)
+    };
+
+    find(coding).or_else(|| find("*")).unwrap_or(false)
+}
+
+/// # Check whether the client refuses a body without content coding.
+///
+/// `identity` is acceptable unless `Accept-Encoding` rejects it with `q=0`, either by name or,
+/// if it isn't listed, with `*;q=0` (RFC 7231, section 5.3.4).
+fn refuses_identity(request: &Request) -> bool {
+    let Some(accept_encoding) = request.header("Accept-Encoding") else {
+        return false;
+    };
+
+    let listed = |coding: &str| {
+        accept_encoding.split(',').any(|entry| {
+            entry
+                .split(';')
+                .next()
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case(coding))
+        })
+    };
+
+    match listed("identity") {
+        true => !accepts_encoding(request, "identity"),
+        false => listed("*") && !accepts_encoding(request, "*"),
+    }
+}
+
+/// Entity tag of a file, derived from its size and modification time
+fn entity_tag(metadata: &FileMetadata) -> String {
+    let modified = metadata
+        .modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs());
+
+    format!("\"{:x}-{:x}\"", metadata.len, modified)
+}
+
+/// `Last-Modified` dates have a precision of seconds, so modification times are compared at it
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// # Check whether the client's cached copy of a resource is still current.
+///
+/// When the request has `If-None-Match`, only the entity tags decide (weak comparison, `*`
+/// matches any tag) and `If-Modified-Since` is ignored, as RFC 9110 (section 13.2.2) requires.
+/// Otherwise the copy is current if the resource wasn't modified after the
+/// `If-Modified-Since` date. A date that can't be parsed is ignored.
+fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = request.header("If-None-Match") {
+        let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+        return if_none_match
+            .split(',')
+            .any(|tag| tag.trim() == "*" || opaque_tag(tag) == opaque_tag(etag));
+    }
+
+    let since = request
+        .header("If-Modified-Since")
+        .and_then(parse_http_date);
+
+    match (since, modified) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// # Check whether the `If-Range` validator of a request matches the current file.
+///
+/// Without `If-Range`, there's nothing to match and the `Range` applies. An entity tag matches
+/// with the strong comparison only, so a weak tag never does. A date matches if it's exactly
+/// the `Last-Modified` date of the file.
+fn if_range_matches(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    let Some(if_range) = request.header("If-Range").map(str::trim) else {
+        return true;
+    };
+
+    // The entity tags of files are strong, so a weak tag can't be equal
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return if_range == etag;
+    }
+
+    match (parse_http_date(if_range), modified) {
+        (Some(date), Some(modified)) => date == modified,
+        _ => false,
+    }
+}
+
+/// File served in place of the listing of a directory containing it
+const INDEX_FILE: &str = "index.html";
+
+/// # Serve the index file of a directory, or list the directory.
+///
+/// The index file is sent with a `Content-Location` header pointing at its own URL, so clients
+/// know the directory URL is just another name for it.
+async fn get_directory_response(
+    dir_name: &str,
+    request: &Request,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let index_name = format!("{}{}", dir_name, INDEX_FILE);
+
+    match config
+        .file_store
+        .metadata(&config.file_path(&index_name))
+        .await
+    {
+        Ok(metadata) if !metadata.is_dir => {
+            // The request path is still percent-encoded, as the header must be
+            let location = format!("{}{}", request.path(), INDEX_FILE);
+
+            get_conditional_file_response(&index_name, request, config)
+                .await
+                .with(("Content-Location".to_string(), location))
+        }
+        _ => get_listing_response(dir_name, request, config).await,
+    }
+}
+
+/// # List the files of a directory under the files directory.
+///
+/// The listing is sorted and filtered according to the query parameters (see
+/// [`ListingOptions`]); invalid ones are answered with `400 Bad Request`.
+async fn get_listing_response(
+    dir_name: &str,
+    request: &Request,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let mut options = match request
+        .query_params(config.query_decoding)
+        .map_err(|err| err.to_string())
+        .and_then(|params| ListingOptions::from_query(&params).map_err(|err| err.to_string()))
+    {
+        Ok(options) => options,
+        Err(err) => {
+            return ResponseBuilder::bad_request()
+                .with(("Content-Type", "text/plain"))
+                .body(err)
+        }
+    };
+
+    // Dotfiles are only listed if they can be served as well
+    options.show_hidden &= config.allow_dotfiles;
+
+    let path = config.file_path(dir_name);
+    let entries = match config.file_store.list(&path).await {
+        Ok(entries) => options.apply(entries),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return ResponseBuilder::not_found().without_content_length_header()
+        }
+        Err(_) => return ResponseBuilder::internal_server_error().without_content_length_header(),
+    };
+
+    let title = format!("/files/{}", dir_name);
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", "text/html"))
+        .body(render_listing(&title, &entries, &options))
+}
+
+async fn delete_file_response(
+    file_name: &str,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    if is_denied_path(file_name, config) {
+        return ResponseBuilder::not_found().without_content_length_header();
+    }
+
+    let path = config.file_path(file_name);
+
+    match config.file_store.delete(&path).await {
+        Ok(_) => ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .without_content_length_header(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            ResponseBuilder::not_found().without_content_length_header()
+        }
+        Err(_) => ResponseBuilder::internal_server_error().without_content_length_header(),
+    }
+}
+
+/// Detect the `Content-Type` of a file from the overrides in the config and then its extension,
+/// falling back to sniffing its content (when enabled in the config) and then to
+/// `application/octet-stream`
+fn file_content_type<'a>(file_name: &str, content: &[u8], config: &'a ServerConfig) -> &'a str {
+    if let Some(mime) = config.mime_override(file_name) {
+        return mime;
+    }
+
+    std::path::Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(mime_from_extension)
+        .or_else(|| match config.sniff_content_type {
+            true => sniff_mime(content),
+            false => None,
+        })
+        .unwrap_or("application/octet-stream")
+}
+
+fn get_user_agent_response(request: &Request) -> ResponseBuilder<StatusCode> {
+    let user_agent = request
+        .headers
+        .iter()
+        .find(|(k, _)| k == "User-Agent")
+        .map(|(_, v)| v);
+
+    match user_agent {
+        Some(user_agent) => ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .body(user_agent.as_bytes().to_vec()),
+        None => ResponseBuilder::bad_request(),
+    }
+}
+
+/// # Echo the request body with the request's `Content-Type`.
+///
+/// A compressed body (`Content-Encoding: gzip` or `deflate`) is decoded first, so the client
+/// gets back the original content. When enabled in the config, so is a gzip body without
+/// `Content-Encoding`.
+fn post_echo_body_response(
+    request: &Request,
+    config: &ServerConfig,
+) -> ResponseBuilder<StatusCode> {
+    let max_size = config.limits.max_body_size;
+    let body = match config.detect_gzip_bodies {
+        true => request.decoded_body_detecting_gzip(max_size),
+        false => request.decoded_body(max_size),
+    };
+
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => {
+            let status_code = err.status_code().unwrap_or(StatusCode::BadRequest);
+            return ResponseBuilder::new().with_status_code(status_code);
+        }
+    };
+
+    let response_builder = match request.header("Content-Type") {
+        Some(content_type) => ResponseBuilder::ok().with(("Content-Type", content_type)),
+        None => ResponseBuilder::ok(),
+    };
+
+    if body.is_empty() {
+        response_builder
+    } else {
+        response_builder.body(body.into_owned())
+    }
+}
+
+/// The built-in icon, cacheable for a day, or `404 Not Found` unless enabled in the config
+fn get_favicon_response(path: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    if !config.favicon {
+        return get_not_found_response(path, config);
+    }
+
+    ResponseBuilder::ok()
+        .with(vec![
+            ("Content-Type", "image/x-icon"),
+            ("Cache-Control", "public, max-age=86400"),
+        ])
+        .static_body(FAVICON)
+}
+
+/// # Respond with `n` generated bytes.
+///
+/// The bytes are pseudo-random, so compression on the way doesn't distort bandwidth tests, but
+/// deterministic: the same `n` always gives the same body.
+fn get_bytes_response(n: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    let Ok(n) = n.parse::<usize>() else {
+        return ResponseBuilder::bad_request();
+    };
+
+    if n > config.max_generated_bytes {
+        return ResponseBuilder::new().with_status_code(StatusCode::PayloadTooLarge);
+    }
+
+    // xorshift64
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let body = std::iter::repeat_with(|| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state.to_le_bytes()
+    })
+    .flatten()
+    .take(n)
+    .collect::<Vec<u8>>();
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", "application/octet-stream"))
+        .body(body)
+}
+
+/// # Respond after the requested number of seconds.
+///
+/// Fractional seconds are allowed. The delay is capped at the configured maximum, so a request
+/// can't hold a connection for longer than that.
+async fn get_delay_response(seconds: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    let Some(delay) = seconds
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    else {
+        return ResponseBuilder::bad_request();
+    };
+
+    tokio::time::sleep(delay.min(config.max_delay)).await;
+
+    ResponseBuilder::ok()
+}
+
+/// # Respond with the requested status code.
+///
+/// Only final status codes (`200`-`599`) known to the server are accepted. The body is the
+/// status line text, unless the status code doesn't allow a body.
+fn get_status_response(code: &str) -> ResponseBuilder<StatusCode> {
+    let Some(status_code) = code
+        .parse::<u16>()
+        .ok()
+        .filter(|code| (200..=599).contains(code))
+        .and_then(StatusCode::from_u16)
+    else {
+        return ResponseBuilder::bad_request();
+    };
+
+    let response_builder = ResponseBuilder::new().with_status_code(status_code);
+
+    match status_code.allows_body() {
+        true => response_builder
+            .with(("Content-Type", "text/plain"))
+            .body(format!("{} {}", status_code as u16, status_code.message())),
+        false => response_builder.without_content_length_header(),
+    }
+}
+
+/// # Shut the server down gracefully.
+///
+/// The endpoint only exists when a shutdown token is configured; the client must send it as
+/// `Authorization: Bearer <token>`. A request without the token is `401 Unauthorized`, one with
+/// a wrong token `403 Forbidden`. The token is compared in constant time, so the response time
+/// doesn't tell how much of a guess was right.
+fn post_shutdown_response(request: &Request, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    let Some(expected) = config.shutdown_token.as_deref() else {
+        return ResponseBuilder::not_found();
+    };
+
+    let Some(token) = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return ResponseBuilder::new()
+            .with_status_code(StatusCode::Unauthorized)
+            .with(("WWW-Authenticate", "Bearer"));
+    };
+
+    if !constant_time_eq(token.trim().as_bytes(), expected.as_bytes()) {
+        return ResponseBuilder::new().with_status_code(StatusCode::Forbidden);
+    }
+
+    eprintln!("Shutdown requested");
+    config.shutdown.trigger();
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", "text/plain"))
+        .body("Shutting down")
+}
+
+/// Compare two byte strings in a time depending only on their lengths
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// # Default answer to an `OPTIONS` request.
+///
+/// `204 No Content` listing the methods the routes handle for the path in `Allow`, plus
+/// `OPTIONS` and `TRACE`, which the server always answers.
+pub fn get_options_response(path: &str, config: &ServerConfig) -> ResponseBuilder<StatusCode> {
+    let mut methods = config.router.allowed_methods(path);
+
+    for method in [Method::Options, Method::Trace] {
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+
+    let allow = methods
+        .iter()
+        .map(Method::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    ResponseBuilder::new()
+        .with_status_code(StatusCode::NoContent)
+        .with(("Allow".to_string(), allow))
+        .without_content_length_header()
+}
+
+/// Headers that are never echoed back in a `TRACE` response. Reflecting them would let a
+/// malicious page read the user's credentials via cross-site tracing (XST).
+const TRACE_SENSITIVE_HEADERS: [&str; 3] = ["Authorization", "Cookie", "Proxy-Authorization"];
+
+/// Maximum number of bytes of the request echoed back in a `TRACE` response body
+const TRACE_MAX_BODY_SIZE: usize = 8 * 1024;
+
+fn get_trace_response(request: &Request) -> ResponseBuilder<StatusCode> {
+    let mut message = format!("{} {} {}\r\n", request.method, request.uri, request.version);
+
+    request
+        .headers
+        .iter()
+        .filter(|(k, _)| {
+            !TRACE_SENSITIVE_HEADERS
+                .iter()
+                .any(|sensitive| k.eq_ignore_ascii_case(sensitive))
+        })
+        .for_each(|(k, v)| message.push_str(&format!("{}: {}\r\n", k, v)));
+
+    message.push_str("\r\n");
+
+    // Cap the echoed request so a huge header block doesn't produce a huge response
+    let mut body = message.into_bytes();
+    body.truncate(TRACE_MAX_BODY_SIZE);
+
+    ResponseBuilder::ok()
+        .with(("Content-Type", "message/http"))
+        .body(body)
+}
+
+fn get_echo_response(content: &str) -> ResponseBuilder<StatusCode> {
+    let response_builder = ResponseBuilder::ok().with(("Content-Type", "text/plain"));
+
+    if content.is_empty() {
+        response_builder
+    } else {
+        response_builder.body(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cors::CorsPolicy,
+        file_store::{EmbeddedAssets, MemoryStore},
+        http::{DecodePolicy, Request, StatusCode},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_get_user_agent_response() {
+        //======================================================================
+        // Test for user agent
+        let request =
+            Request::try_from("GET /user-agent HTTP/1.1\r\nUser-Agent: curl/7.68.0\r\n\r\n")
+                .unwrap();
+
+        let response_builder = get_user_agent_response(&request);
+        let response = response_builder.build();
+
+        let body_len_str = "curl/7.68.0".len().to_string();
+        let headers: Vec<(String, String)> = vec![
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("Content-Length".to_string(), body_len_str),
+        ];
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.headers, headers);
+        assert_eq!(response.body, Some(b"curl/7.68.0".to_vec().into()));
+
+        //======================================================================
+        // Test for no user agent
+        let request = Request::try_from("GET /user-agent HTTP/1.1\r\n\r\n").unwrap();
+
+        let response_builder = get_user_agent_response(&request);
+        let response = response_builder.build();
+
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+        assert_eq!(
+            response.headers,
+            vec![("Content-Length".to_string(), "0".to_string())]
+        );
+        assert_eq!(response.body, None);
+    }
+
+    #[test]
+    fn test_get_echo_response() {
+        //======================================================================
+        // Test for non-empty content
+        let request = Request::try_from("GET /echo/Hello%20World HTTP/1.1\r\n\r\n").unwrap();
+
+        let path = request.uri.as_str().trim_start_matches("/echo/");
+
+        let response_builder = get_echo_response(path);
+        let response = response_builder.build();
+
+        let body_len_str = b"Hello%20World".len().to_string();
+        let headers: Vec<(String, String)> = vec![
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("Content-Length".to_string(), body_len_str),
+        ];
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.headers, headers);
+        assert_eq!(response.body, Some(b"Hello%20World".to_vec().into()));
+
+        //======================================================================
+        // Test for empty content
+        let request = Request::try_from("GET /echo/ HTTP/1.1\r\n\r\n").unwrap();
+
+        let path = request.uri.as_str().trim_start_matches("/echo/");
+
+        let response_builder = get_echo_response(path);
+        let response = response_builder.build();
+
+        let headers: Vec<(String, String)> = vec![
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("Content-Length".to_string(), "0".to_string()),
+        ];
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.headers, headers);
+        assert_eq!(response.body, None);
+    }
+
+    /// Config serving the `/files/` endpoints from the given in-memory store
+    fn memory_config(store: MemoryStore) -> ServerConfig {
+        ServerConfig {
+            files_dir: "/srv/files".to_string(),
+            file_store: Arc::new(store),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_file_response() {
+        //======================================================================
+        // Test for file found
+        let file_name = "test.txt";
+        let file_content = "Hello\r\nWorld\r\n";
+
+        let config =
+            memory_config(MemoryStore::new().with_file("/srv/files/test.txt", file_content));
+
+        let response_builder = get_file_response(file_name, &config).await;
+        let response = response_builder.build();
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), file_content.len().to_string())
+            ]
+        );
+        assert_eq!(response.body, Some(file_content.as_bytes().to_vec().into()));
+
+        //======================================================================
+        // Test file not found
+        let config = memory_config(MemoryStore::new());
+
+        let response_builder = get_file_response(file_name, &config).await;
+        let response = response_builder.build();
+
+        assert_eq!(response.status_code, StatusCode::NotFound);
+        assert!(response.headers.is_empty());
+        assert_eq!(response.body, None);
+    }
+
+    #[tokio::test]
+    async fn test_post_file_response() {
+        //======================================================================
+        // Test for file created
+        let file_name = "test.txt";
+        let file_content = "Hello World";
+
+        let store = Arc::new(MemoryStore::new());
+        let config = ServerConfig {
+            files_dir: "/srv/files".to_string(),
+            file_store: store.clone(),
+            ..Default::default()
+        };
+
+        let request = Request::from_bytes(
+            format!(
+                "POST /files/{} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                file_name,
+                file_content.len(),
+                file_content
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let response_builder = post_file_response("test.txt", &request, &config).await;
+        let response = response_builder.build();
+
+        assert_eq!(response.status_code, StatusCode::Created);
+        assert_eq!(
+            response.headers,
+            vec![("Location".to_string(), "/files/test.txt".to_string())]
+        );
+        assert_eq!(response.body, None);
+
+        // Check if file was created
+        assert_eq!(
+            store.get("/srv/files/test.txt"),
+            Some(file_content.as_bytes().to_vec())
+        );
+
+        //======================================================================
+        // Test for 204 instead of 201 when configured
+        let config = ServerConfig {
+            upload_no_content: true,
+            ..memory_config(MemoryStore::new())
+        };
+
+        let response = post_file_response("test.txt", &request, &config)
+            .await
+            .build();
+
+        assert_eq!(response.status_code, StatusCode::NoContent);
+        assert!(
+            response.headers.is_empty(),
+            "No Location and no Content-Length"
+        );
+        assert_eq!(response.body, None);
+
+        //======================================================================
+        // Test for file not created
+        let config = memory_config(MemoryStore::read_only());
+
+        let request = Request::try_from("POST /files/test.txt HTTP/1.1\r\n\r\n").unwrap();
+
+        let response_builder = post_file_response("test.txt", &request, &config).await;
+        let response = response_builder.build();
+
+        assert_eq!(response.status_code, StatusCode::InternalServerError);
+        assert!(response.headers.is_empty());
+        assert_eq!(response.body, None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_file_response() {
+        //======================================================================
+        // Test for file deleted
+        let store = Arc::new(MemoryStore::new().with_file("/srv/files/test.txt", "Hello World"));
+        let config = ServerConfig {
+            files_dir: "/srv/files".to_string(),
+            file_store: store.clone(),
+            ..Default::default()
+        };
+
+        let response = delete_file_response("test.txt", &config).await.build();
+
+        assert_eq!(response.status_code, StatusCode::NoContent);
+        assert!(response.headers.is_empty());
+        assert_eq!(store.get("/srv/files/test.txt"), None);
+
+        //======================================================================
+        // Test for file not found
+        let response = delete_file_response("test.txt", &config).await.build();
+
+        assert_eq!(response.status_code, StatusCode::NotFound);
+
+        //======================================================================
+        // Test for file not deleted
+        let config = memory_config(MemoryStore::read_only().with_file("/srv/files/test.txt", ""));
+
+        let response = delete_file_response("test.txt", &config).await.build();
+
+        assert_eq!(response.status_code, StatusCode::InternalServerError);
+    }
+
+    #[test]
+    fn test_get_trace_response() {
+        //======================================================================
+        // Test for sensitive headers being omitted
+        let request = Request::try_from(
+            "TRACE /echo/abc HTTP/1.1\r\nHost: localhost:4221\r\nAuthorization: Bearer secret\r\ncookie: session=secret\r\nAccept: */*\r\n\r\n",
+        )
+        .unwrap();
+
+        let response = get_trace_response(&request).build();
+        let body = String::from_utf8(response.body.unwrap().into_owned()).unwrap();
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers[0],
+            ("Content-Type".to_string(), "message/http".to_string())
+        );
+        assert_eq!(
+            body,
+            "TRACE /echo/abc HTTP/1.1\r\nHost: localhost:4221\r\nAccept: */*\r\n\r\n"
+        );
+        assert!(!body.contains("Authorization"));
+        assert!(!body.contains("secret"));
+
+        //======================================================================
+        // Test for the version of the request being echoed
+        let mut request = Request::try_from("TRACE / HTTP/1.1\r\n\r\n").unwrap();
+        request.version = Version::Http09;
+
+        let response = get_trace_response(&request).build();
+        assert_eq!(
+            response.body.as_deref(),
+            Some(&b"TRACE / HTTP/0.9\r\n\r\n"[..]),
+            "Version as received"
+        );
+
+        //======================================================================
+        // Test for the echoed request size being capped
+        let request = Request::try_from(
+            format!(
+                "TRACE / HTTP/1.1\r\nX-Large: {}\r\n\r\n",
+                "a".repeat(TRACE_MAX_BODY_SIZE)
+            )
+            .as_str(),
+        )
+        .unwrap();
+
+        let response = get_trace_response(&request).build();
+
+        assert_eq!(response.body.unwrap().len(), TRACE_MAX_BODY_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_get_file_response_embedded_assets() {
+        let config = ServerConfig {
+            embedded_assets: EmbeddedAssets::new([
+                ("index.html", b"<!DOCTYPE html>".as_slice()),
+                ("favicon.ico", FAVICON),
+            ]),
+            ..memory_config(
+                MemoryStore::new()
+                    .with_file("/srv/files/index.html", "on disk")
+                    .with_file("/srv/files/other.txt", "on disk"),
+            )
+        };
+
+        let response = get_file_response("favicon.ico", &config).await.build();
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "image/x-icon".to_string()),
+                ("Content-Length".to_string(), FAVICON.len().to_string()),
+            ],
+            "MIME type of the embedded asset"
+        );
+        assert_eq!(response.body, Some(FAVICON.to_vec().into()));
+
+        let response = get_file_response("index.html", &config).await.build();
+        assert_eq!(
+            response.body,
+            Some(b"<!DOCTYPE html>".to_vec().into()),
+            "Embedded asset takes precedence"
+        );
+
+        let response = get_file_response("other.txt", &config).await.build();
+        assert_eq!(
+            response.body,
+            Some(b"on disk".to_vec().into()),
+            "File store for other files"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_file_response_without_files_dir() {
+        let config = ServerConfig {
+            file_store: Arc::new(
+                MemoryStore::new()
+                    .with_file("/secret.txt", "at the root")
+                    .with_file("./public.txt", "in the working directory"),
+            ),
+            ..Default::default()
+        };
+
+        let response = get_file_response("secret.txt", &config).await.build();
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotFound,
+            "Files at the filesystem root are not served"
+        );
+
+        let response = get_file_response("public.txt", &config).await.build();
+        assert_eq!(
+            response.body,
+            Some(b"in the working directory".to_vec().into()),
+            "Files are resolved against the working directory"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_conditional_file_response() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let config = memory_config(MemoryStore::new().with_modified_file(
+            "/srv/files/a.txt",
+            "Hello",
+            modified + Duration::from_millis(500),
+        ));
+        let get = |headers: &str| {
+            Request::try_from(format!("GET /files/a.txt HTTP/1.1\r\n{}\r\n", headers).as_str())
+                .unwrap()
+        };
+
+        //======================================================================
+        // Test for the validators of a full response
+        let response = handle_request(&get(""), &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        let etag = response
+            .headers
+            .iter()
+            .find(|(name, _)| name == "ETag")
+            .map(|(_, value)| value.clone())
+            .unwrap();
+        assert_eq!(
+            etag, "\"5-3b9aca00\"",
+            "ETag from size and modification time"
+        );
+        assert!(
+            response.headers.contains(&(
+                "Last-Modified".to_string(),
+                "Sun, 09 Sep 2001 01:46:40 GMT".to_string()
+            )),
+            "Last-Modified header"
+        );
+
+        //======================================================================
+        // Test for each condition on its own
+        let response =
+            handle_request(&get(&format!("If-None-Match: W/{}\r\n", etag)), &config).await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotModified,
+            "Matching ETag"
+        );
+        assert_eq!(response.body, None, "No body");
+
+        let response = handle_request(
+            &get("If-Modified-Since: Sun, 09 Sep 2001 01:46:40 GMT\r\n"),
+            &config,
+        )
+        .await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotModified,
+            "Not modified since"
+        );
+
+        let response = handle_request(
+            &get("If-Modified-Since: Sun, 09 Sep 2001 01:46:39 GMT\r\n"),
+            &config,
+        )
+        .await;
+        assert_eq!(response.status_code, StatusCode::Ok, "Modified since");
+
+        //======================================================================
+        // Test for conditions that disagree: If-None-Match takes precedence
+        let response = handle_request(
+            &get(
+                "If-None-Match: \"other\"\r\nIf-Modified-Since: Sun, 09 Sep 2001 01:46:40 GMT\r\n",
+            ),
+            &config,
+        )
+        .await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::Ok,
+            "Different ETag wins over an unmodified date"
+        );
+
+        let response = handle_request(
+            &get(&format!(
+                "If-None-Match: \"other\", {}\r\nIf-Modified-Since: Sun, 09 Sep 2001 01:46:39 GMT\r\n",
+                etag
+            )),
+            &config,
+        )
+        .await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotModified,
+            "Matching ETag wins over a modified date"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_companion_file_response() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let brotli = vec![0x0b, 0x02, 0x80, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x03];
+        let store = MemoryStore::new()
+            .with_modified_file("/srv/files/a.txt", "Hello", modified)
+            .with_modified_file("/srv/files/a.txt.br", brotli.clone(), modified)
+            .with_modified_file("/srv/files/a.txt.gz", vec![0x1f, 0x8b], modified)
+            .with_modified_file("/srv/files/b.txt", "Hello", modified);
+        let config = memory_config(store);
+
+        let get = |path: &str, accept_encoding: &str| {
+            Request::try_from(
+                format!(
+                    "GET /files/{} HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+                    path, accept_encoding
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        let header = |response: &Response, name: &str| {
+            response
+                .headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        //======================================================================
+        // Test for the brotli companion, preferred over the gzip one
+        let response = handle_request(&get("a.txt", "gzip, deflate, br"), &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.body,
+            Some(brotli.into()),
+            "Brotli companion sent as is"
+        );
+        assert_eq!(
+            header(&response, "Content-Encoding").as_deref(),
+            Some("br"),
+            "Brotli encoding"
+        );
+        assert_eq!(
+            header(&response, "Content-Type").as_deref(),
+            Some("text/plain"),
+            "MIME type of the original file"
+        );
+        assert_eq!(
+            header(&response, "Vary").as_deref(),
+            Some("Accept-Encoding"),
+            "Vary header"
+        );
+        assert_eq!(
+            header(&response, "ETag").as_deref(),
+            Some("\"5-3b9aca00-br\""),
+            "ETag of the brotli representation"
+        );
+
+        //======================================================================
+        // Test for the gzip companion
+        let response = handle_request(&get("a.txt", "gzip, br;q=0"), &config).await;
+
+        assert_eq!(
+            response.body,
+            Some(vec![0x1f, 0x8b].into()),
+            "Gzip companion"
+        );
+        assert_eq!(
+            header(&response, "Content-Encoding").as_deref(),
+            Some("gzip"),
+            "Gzip encoding"
+        );
+
+        //======================================================================
+        // Test for the original file
+        let response = handle_request(&get("a.txt", "identity"), &config).await;
+        assert_eq!(
+            response.body,
+            Some(b"Hello".to_vec().into()),
+            "No companion accepted"
+        );
+        assert_eq!(header(&response, "Content-Encoding"), None, "Not encoded");
+
+        let response = handle_request(&get("b.txt", "br"), &config).await;
+        assert_eq!(
+            response.body,
+            Some(b"Hello".to_vec().into()),
+            "File without companions"
+        );
+    }
+
+    #[test]
+    fn test_accepts_encoding() {
+        let request = |accept_encoding: &str| {
+            Request::try_from(
+                format!(
+                    "GET / HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+                    accept_encoding
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+
+        assert!(accepts_encoding(&request("gzip, br"), "br"), "Listed");
+        assert!(
+            accepts_encoding(&request("GZIP;q=0.5"), "gzip"),
+            "Case and q-value"
+        );
+        assert!(!accepts_encoding(&request("gzip"), "br"), "Not listed");
+        assert!(!accepts_encoding(&request("br;q=0"), "br"), "Rejected");
+        assert!(accepts_encoding(&request("*"), "br"), "Wildcard");
+        assert!(
+            !accepts_encoding(&request("br;q=0, *"), "br"),
+            "Entry over the wildcard"
+        );
+        assert!(
+            !accepts_encoding(&Request::try_from("GET / HTTP/1.1\r\n\r\n").unwrap(), "br"),
+            "No Accept-Encoding"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refuses_identity() {
+        let request = |accept_encoding: &str| {
+            Request::try_from(
+                format!(
+                    "GET /echo/abc HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+                    accept_encoding
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+
+        //======================================================================
+        // Test for the check itself
+        assert!(refuses_identity(&request("identity;q=0")), "Rejected");
+        assert!(refuses_identity(&request("br, *;q=0")), "Rejected by *");
+        assert!(
+            !refuses_identity(&request("identity, *;q=0")),
+            "Listed identity takes precedence"
+        );
+        assert!(!refuses_identity(&request("br;q=0")), "Not listed");
+        assert!(
+            !refuses_identity(&Request::try_from("GET / HTTP/1.1\r\n\r\n").unwrap()),
+            "No Accept-Encoding"
+        );
+
+        //======================================================================
+        // Test for the responses
+        let config = ServerConfig::default();
+
+        let response = handle_request(&request("identity;q=0, *;q=0"), &config).await;
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 406 Not Acceptable\r\nVary: Accept-Encoding\r\nContent-Length: 0\r\n\r\n",
+            "No acceptable coding"
+        );
+
+        let response = handle_request(&request("gzip;q=0, identity"), &config).await;
+        assert!(
+            !response
+                .headers
+                .iter()
+                .any(|(name, _)| name == "Content-Encoding"),
+            "Gzip refused with q=0"
+        );
+
+        let request_lowercase =
+            Request::try_from("GET /echo/abc HTTP/1.1\r\naccept-encoding: gzip\r\n\r\n").unwrap();
+        let response = handle_request(&request_lowercase, &config).await;
+        assert!(
+            response
+                .headers
+                .contains(&("Content-Encoding".to_string(), "gzip".to_string())),
+            "Header name matched case-insensitively"
+        );
+
+        let response = handle_request(&request("gzip, identity;q=0"), &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok, "Gzip accepted");
+        assert!(
+            response
+                .headers
+                .contains(&("Content-Encoding".to_string(), "gzip".to_string())),
+            "Body gzip-encoded"
+        );
+
+        let request =
+            Request::try_from("GET / HTTP/1.1\r\nAccept-Encoding: identity;q=0, *;q=0\r\n\r\n")
+                .unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::Ok,
+            "Response without body"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_range_response() {
+        let content = (0..100).map(|i| i as u8).collect::<Vec<u8>>();
+        let config = memory_config(MemoryStore::new().with_file("/srv/files/a.bin", content));
+
+        let get = |method: &str, range: &str| {
+            Request::try_from(
+                format!(
+                    "{} /files/a.bin HTTP/1.1\r\nRange: {}\r\n\r\n",
+                    method, range
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+        let header = |response: &Response, name: &str| {
+            response
+                .headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        //======================================================================
+        // Test for a satisfiable range
+        let response = handle_request(&get("GET", "bytes=10-19"), &config).await;
+
+        assert_eq!(response.status_code, StatusCode::PartialContent);
+        assert_eq!(
+            response.body,
+            Some((10..20).collect::<Vec<u8>>().into()),
+            "Requested bytes"
+        );
+        assert_eq!(
+            header(&response, "Content-Length").as_deref(),
+            Some("10"),
+            "Length of the range, not of the file"
+        );
+        assert_eq!(
+            header(&response, "Content-Range").as_deref(),
+            Some("bytes 10-19/100"),
+            "Range and length of the file"
+        );
+
+        //======================================================================
+        // Test for an unsatisfiable range
+        let response = handle_request(&get("GET", "bytes=100-"), &config).await;
+
+        assert_eq!(response.status_code, StatusCode::RangeNotSatisfiable);
+        assert_eq!(
+            header(&response, "Content-Range").as_deref(),
+            Some("bytes */100"),
+            "Length of the file"
+        );
+
+        //======================================================================
+        // Test for ranges that are ignored
+        for (method, range) in [
+            ("GET", "bytes=0-1,5-6"),
+            ("GET", "items=0-1"),
+            ("HEAD", "bytes=0-1"),
+        ] {
+            let response = handle_request(&get(method, range), &config).await;
+
+            assert_eq!(response.status_code, StatusCode::Ok, "{} {}", method, range);
+            assert_eq!(
+                header(&response, "Content-Length").as_deref(),
+                Some("100"),
+                "Whole file for {} {}",
+                method,
+                range
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_range_response_if_range() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let content = (0..100).map(|i| i as u8).collect::<Vec<u8>>();
+        let config = memory_config(MemoryStore::new().with_modified_file(
+            "/srv/files/a.bin",
+            content,
+            modified,
+        ));
+
+        let get = |if_range: Option<&str>| {
+            let if_range = if_range
+                .map(|value| format!("If-Range: {}\r\n", value))
+                .unwrap_or_default();
+            Request::try_from(
+                format!(
+                    "GET /files/a.bin HTTP/1.1\r\nRange: bytes=10-19\r\n{}\r\n",
+                    if_range
+                )
+                .as_str(),
+            )
+            .unwrap()
+        };
+
+        //======================================================================
+        // Test for a range without If-Range
+        let response = handle_request(&get(None), &config).await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::PartialContent,
+            "Range without If-Range"
+        );
+
+        //======================================================================
+        // Test for a range with a matching If-Range
+        let current_date = format_http_date(modified);
+        for if_range in ["\"64-3b9aca00\"", current_date.as_str()] {
+            let response = handle_request(&get(Some(if_range)), &config).await;
+
+            assert_eq!(
+                response.status_code,
+                StatusCode::PartialContent,
+                "Matching If-Range {}",
+                if_range
+            );
+            assert_eq!(
+                response.body.as_deref(),
+                Some(&(10..20).collect::<Vec<u8>>()[..]),
+                "Range for the matching If-Range {}",
+                if_range
+            );
+        }
+
+        //======================================================================
+        // Test for a range with a stale If-Range
+        let stale_date = format_http_date(modified - Duration::from_secs(60));
+        for if_range in [
+            "\"64-3b9ac9c4\"",
+            "W/\"64-3b9aca00\"",
+            stale_date.as_str(),
+            "not a date",
+        ] {
+            let response = handle_request(&get(Some(if_range)), &config).await;
+
+            assert_eq!(
+                response.status_code,
+                StatusCode::Ok,
+                "Stale If-Range {}",
+                if_range
+            );
+            assert_eq!(
+                response.body.as_ref().map(|body| body.len()),
+                Some(100),
+                "Whole file for the stale If-Range {}",
+                if_range
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_byte_range() {
+        let cases = [
+            ("bytes=10-19", ByteRange::Satisfiable(10..20)),
+            ("bytes=0-0", ByteRange::Satisfiable(0..1)),
+            ("bytes=90-", ByteRange::Satisfiable(90..100)),
+            ("bytes=-5", ByteRange::Satisfiable(95..100)),
+            ("bytes=-500", ByteRange::Satisfiable(0..100)),
+            ("bytes=50-500", ByteRange::Satisfiable(50..100)),
+            ("Bytes = 1-2", ByteRange::Satisfiable(1..3)),
+            ("bytes=100-", ByteRange::Unsatisfiable),
+            ("bytes=100-200", ByteRange::Unsatisfiable),
+            ("bytes=-0", ByteRange::Unsatisfiable),
+            ("bytes=19-10", ByteRange::Unsupported),
+            ("bytes=0-1,5-6", ByteRange::Unsupported),
+            ("bytes=a-b", ByteRange::Unsupported),
+            ("bytes=-", ByteRange::Unsupported),
+            ("bytes 0-1", ByteRange::Unsupported),
+            ("items=0-1", ByteRange::Unsupported),
+        ];
+
+        for (header, expected) in cases {
+            assert_eq!(parse_byte_range(header, 100), expected, "{}", header);
+        }
+
+        assert_eq!(
+            parse_byte_range("bytes=0-", 0),
+            ByteRange::Unsatisfiable,
+            "Empty resource"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dotfiles() {
+        let store = || {
+            MemoryStore::new()
+                .with_file("/srv/files/.env", "SECRET=1")
+                .with_file("/srv/files/.git/config", "[core]")
+                .with_file("/srv/files/a.txt", "a")
+        };
+        let request = |line: &str| Request::try_from(format!("{}\r\n\r\n", line).as_str()).unwrap();
+
+        //======================================================================
+        // Test for dotfiles denied by default
+        let config = memory_config(store());
+
+        for line in [
+            "GET /files/.env HTTP/1.1",
+            "GET /files/%2Eenv HTTP/1.1",
+            "HEAD /files/.env HTTP/1.1",
+            "GET /files/.git/config HTTP/1.1",
+            "GET /files/.git/ HTTP/1.1",
+            "DELETE /files/.env HTTP/1.1",
+            "PUT /files/.htaccess HTTP/1.1",
+        ] {
+            let response = handle_request(&request(line), &config).await;
+            assert_eq!(response.status_code, StatusCode::NotFound, "{}", line);
+        }
+
+        assert!(
+            config.file_store.metadata("/srv/files/.env").await.is_ok(),
+            "Dotfile not deleted"
+        );
+        assert!(
+            config
+                .file_store
+                .metadata("/srv/files/.htaccess")
+                .await
+                .is_err(),
+            "Dotfile not written"
+        );
+
+        let response = handle_request(&request("GET /files/a.txt HTTP/1.1"), &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok, "Other files served");
+
+        //======================================================================
+        // Test for dotfiles allowed
+        let config = ServerConfig {
+            allow_dotfiles: true,
+            ..memory_config(store())
+        };
+
+        let response = handle_request(&request("GET /files/.env HTTP/1.1"), &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok, "Dotfile served");
+        assert_eq!(
+            response.body.as_deref(),
+            Some(&b"SECRET=1"[..]),
+            "Dotfile content"
+        );
+
+        let response = handle_request(&request("GET /files/.git/config HTTP/1.1"), &config).await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::Ok,
+            "File in a hidden directory served"
+        );
+
+        //======================================================================
+        // Test for `.` and `..` segments denied even with dotfiles allowed
+        let config = ServerConfig {
+            allow_dotfiles: true,
+            ..memory_config(store().with_file("/srv/secret.txt", "secret"))
+        };
+
+        for line in [
+            "GET /files/../secret.txt HTTP/1.1",
+            "GET /files/%2E%2E/secret.txt HTTP/1.1",
+            "GET /files/../../etc/passwd HTTP/1.1",
+            "GET /files/./a.txt HTTP/1.1",
+            "GET /files/../ HTTP/1.1",
+            "DELETE /files/../secret.txt HTTP/1.1",
+            "PUT /files/../written.txt HTTP/1.1",
+        ] {
+            let response = handle_request(&request(line), &config).await;
+            assert_eq!(response.status_code, StatusCode::NotFound, "{}", line);
+        }
+
+        assert!(
+            config.file_store.metadata("/srv/secret.txt").await.is_ok(),
+            "File outside the directory not deleted"
+        );
+        assert!(
+            config
+                .file_store
+                .metadata("/srv/files/../written.txt")
+                .await
+                .is_err(),
+            "File outside the directory not written"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_directory_index_response() {
+        let config = memory_config(
+            MemoryStore::new()
+                .with_file("/srv/files/dir/index.html", "<h1>Index</h1>")
+                .with_file("/srv/files/dir/a.txt", "a")
+                .with_file("/srv/files/other/a.txt", "a"),
+        );
+        let content_location = |response: &Response| {
+            response
+                .headers
+                .iter()
+                .find(|(name, _)| name == "Content-Location")
+                .map(|(_, value)| value.clone())
+        };
+
+        let request = Request::try_from("GET /files/dir/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.body,
+            Some(b"<h1>Index</h1>".to_vec().into()),
+            "Index file served"
+        );
+        assert_eq!(
+            content_location(&response).as_deref(),
+            Some("/files/dir/index.html"),
+            "Content-Location of the index file"
+        );
+
+        let request = Request::try_from("GET /files/dir/index.html HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(
+            content_location(&response),
+            None,
+            "No Content-Location at the canonical URL"
+        );
+
+        let request = Request::try_from("GET /files/other/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(
+            response.headers[0],
+            ("Content-Type".to_string(), "text/html".to_string()),
+            "Listing without an index file"
+        );
+        assert_eq!(content_location(&response), None, "No Content-Location");
+    }
+
+    #[tokio::test]
+    async fn test_get_listing_response() {
+        let modified = |secs| std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let config = memory_config(
+            MemoryStore::new()
+                .with_modified_file("/srv/files/a.txt", "aaaaa", modified(3))
+                .with_modified_file("/srv/files/b.txt", "b", modified(1))
+                .with_modified_file("/srv/files/c.txt", "ccc", modified(2))
+                .with_modified_file("/srv/files/.env", "SECRET", modified(4))
+                .with_modified_file("/srv/files/sub/d.txt", "dd", modified(5)),
+        );
+
+        // Names of the listed entries in the order they appear in the body
+        let listed = |response: &Response| {
+            let body = String::from_utf8(response.body.clone().unwrap().into_owned()).unwrap();
+            body.split("<tr><td><a href=\"")
+                .skip(1)
+                .map(|row| row.split('"').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        //======================================================================
+        // Test for the default order
+        let request = Request::try_from("GET /files/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers[0],
+            ("Content-Type".to_string(), "text/html".to_string()),
+            "HTML listing"
+        );
+        assert_eq!(
+            listed(&response),
+            ["a.txt", "b.txt", "c.txt", "sub/"],
+            "Sorted by name, hidden files left out"
+        );
+
+        //======================================================================
+        // Test for sorting by size in descending order
+        let request =
+            Request::try_from("GET /files/?sort=size&order=desc HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            listed(&response),
+            ["a.txt", "c.txt", "b.txt", "sub/"],
+            "Sorted by size, largest first"
+        );
+
+        //======================================================================
+        // Test for sorting by modification time with hidden files
+        let request =
+            Request::try_from("GET /files/?sort=mtime&hidden=true HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            listed(&response),
+            ["sub/", "b.txt", "c.txt", "a.txt"],
+            "Hidden files left out while dotfiles are denied"
+        );
+
+        let dotfiles_config = ServerConfig {
+            allow_dotfiles: true,
+            ..config.clone()
+        };
+        let response = handle_request(&request, &dotfiles_config).await;
+
+        assert_eq!(
+            listed(&response),
+            ["sub/", "b.txt", "c.txt", "a.txt", ".env"],
+            "Sorted by modification time, oldest first"
+        );
+
+        //======================================================================
+        // Test for a subdirectory
+        let request = Request::try_from("GET /files/sub/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(listed(&response), ["d.txt"], "Subdirectory listing");
+
+        //======================================================================
+        // Test for invalid options and a missing directory
+        let request = Request::try_from("GET /files/?sort=color HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            response.status_code,
+            StatusCode::BadRequest,
+            "Unknown sort key"
+        );
+
+        let request = Request::try_from("GET /files/missing/ HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotFound,
+            "Missing directory"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_file_response_content_type_sniffing() {
+        let png: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        let html: &[u8] = b"<!DOCTYPE html>\n<html><body>Hello</body></html>";
+
+        let content_type = |response: &crate::http::Response| {
+            response
+                .headers
+                .iter()
+                .find(|(k, _)| k == "Content-Type")
+                .map(|(_, v)| v.clone())
+        };
+
+        //======================================================================
+        // Test for sniffing disabled (default)
+        let mut config = memory_config(
+            MemoryStore::new()
+                .with_file("/srv/files/image", png)
+                .with_file("/srv/files/page", html),
+        );
+
+        let response = get_file_response("image", &config).await.build();
+        assert_eq!(
+            content_type(&response),
+            Some("application/octet-stream".to_string())
+        );
+
+        //======================================================================
+        // Test for sniffing enabled
+        config.sniff_content_type = true;
+
+        let response = get_file_response("image", &config).await.build();
+        assert_eq!(content_type(&response), Some("image/png".to_string()));
+        assert_eq!(response.body, Some(png.to_vec().into()));
+
+        let response = get_file_response("page", &config).await.build();
+        assert_eq!(content_type(&response), Some("text/html".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_get_file_response_mime_overrides() {
+        let mut config = memory_config(
+            MemoryStore::new()
+                .with_file("/srv/files/README.md", "# Hello")
+                .with_file("/srv/files/data.json", "{}"),
+        );
+
+        let content_type = |response: &crate::http::Response| {
+            response
+                .headers
+                .iter()
+                .find(|(k, _)| k == "Content-Type")
+                .map(|(_, v)| v.clone())
+        };
+
+        let response = get_file_response("README.md", &config).await.build();
+        assert_eq!(
+            content_type(&response),
+            Some("application/octet-stream".to_string()),
+            "Unknown extension without an override"
+        );
+
+        config
+            .mime_overrides
+            .insert(".md".to_string(), "text/markdown".to_string());
+        config
+            .mime_overrides
+            .insert(".json".to_string(), "application/vnd.api+json".to_string());
+
+        let response = get_file_response("README.md", &config).await.build();
+        assert_eq!(
+            content_type(&response),
+            Some("text/markdown".to_string()),
+            "Custom type"
+        );
+
+        let response = get_file_response("data.json", &config).await.build();
+        assert_eq!(
+            content_type(&response),
+            Some("application/vnd.api+json".to_string()),
+            "Built-in type overridden"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_pipelined_requests() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let config = ServerConfig::default();
+
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        // The first request has an empty body and the second one follows it immediately
+        client
+            .write_all(
+                b"POST /echo/a HTTP/1.1\r\nContent-Length: 0\r\n\r\nGET /echo/b HTTP/1.1\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut client, &mut response)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\r\na\
+             HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\nConnection: close\r\n\r\nb"
+        );
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_percent_decoding() {
+        //======================================================================
+        // Test for a decoded path
+        let config = ServerConfig::default();
+        let request = Request::try_from("GET /echo/Hello%20World?x=%ZZ HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.body, Some(b"Hello World".to_vec().into()));
+
+        //======================================================================
+        // Test for malformed percent-encoding in the path
+        let request = Request::try_from("GET /echo/%ZZ HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+
+        //======================================================================
+        // Test for lenient path decoding
+        let config = ServerConfig {
+            path_decoding: DecodePolicy::Lenient,
+            ..Default::default()
+        };
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.body, Some(b"%ZZ".to_vec().into()));
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_compressible_types() {
+        let config = memory_config(
+            MemoryStore::new()
+                .with_file("/srv/files/data.json", r#"{"hello":"world"}"#)
+                .with_file("/srv/files/image.png", b"\x89PNG\r\n\x1a\n".to_vec()),
+        );
+
+        let content_encoding = |response: &Response| {
+            response
+                .headers
+                .iter()
+                .find(|(k, _)| k == "Content-Encoding")
+                .map(|(_, v)| v.clone())
+        };
+
+        //======================================================================
+        // Test for a JSON response being compressed
+        let request =
+            Request::try_from("GET /files/data.json HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n")
+                .unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(content_encoding(&response), Some("gzip".to_string()));
+        assert!(response.body.unwrap().starts_with(&[0x1f, 0x8b]));
+
+        //======================================================================
+        // Test for a PNG response not being compressed
+        let request =
+            Request::try_from("GET /files/image.png HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n")
+                .unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(content_encoding(&response), None);
+        assert_eq!(response.body, Some(b"\x89PNG\r\n\x1a\n".to_vec().into()));
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_large_gzip_body_is_streamed() {
+        let body = "a".repeat(GZIP_STREAMING_THRESHOLD + 1);
+        let config = memory_config(
+            MemoryStore::new()
+                .with_file("/srv/files/small.txt", "hello")
+                .with_file("/srv/files/large.txt", body.clone()),
+        );
+
+        let request =
+            Request::try_from("GET /files/large.txt HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n")
+                .unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert!(response.chunked, "Large body is sent chunked");
+        assert!(
+            response
+                .headers
+                .contains(&("Transfer-Encoding".to_string(), "chunked".to_string())),
+            "Transfer-Encoding header"
+        );
+        assert!(
+            !response.headers.iter().any(|(k, _)| k == "Content-Length"),
+            "No Content-Length"
+        );
+        assert_eq!(
+            response.body,
+            Some(body.into_bytes().into()),
+            "Compressed lazily"
+        );
+
+        let request =
+            Request::try_from("GET /files/small.txt HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n")
+                .unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert!(!response.chunked, "Small body is compressed at once");
+        assert!(response.body.unwrap().starts_with(&[0x1f, 0x8b]));
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_chunked_gzip() {
+        use std::io::Read;
+        use tokio::io::AsyncReadExt;
+
+        let body = (0..20_000).map(|i| format!("{} ", i)).collect::<String>();
+        assert!(body.len() > GZIP_STREAMING_THRESHOLD);
+
+        let config =
+            memory_config(MemoryStore::new().with_file("/srv/files/large.txt", body.clone()));
+        let (mut client, server) = tokio::io::duplex(1024 * 1024);
+
+        client
+            .write_all(
+                b"GET /files/large.txt HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        handle_connection(server, &config).await.unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+
+        let head_len = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = std::str::from_utf8(&response[..head_len]).unwrap();
+        assert!(
+            head.contains("Content-Encoding: gzip\r\n"),
+            "gzip: {}",
+            head
+        );
+        assert!(
+            head.contains("Transfer-Encoding: chunked\r\n"),
+            "chunked: {}",
+            head
+        );
+        assert!(
+            !head.contains("Content-Length"),
+            "No Content-Length: {}",
+            head
+        );
+
+        // De-chunk
+        let mut data = &response[head_len..];
+        let mut compressed = Vec::new();
+        loop {
+            let line_end = data.windows(2).position(|w| w == b"\r\n").unwrap();
+            let size =
+                usize::from_str_radix(std::str::from_utf8(&data[..line_end]).unwrap(), 16).unwrap();
+            data = &data[line_end + 2..];
+            if size == 0 {
+                break;
+            }
+            compressed.extend_from_slice(&data[..size]);
+            data = &data[size + 2..];
+        }
+
+        // Then gunzip
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body, "Original body");
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_upgrade() {
+        use tokio::io::AsyncReadExt;
+
+        // Protocol echoing every received byte back in uppercase
+        let mut config = ServerConfig::default();
+        config.upgrades.register(
+            "raw-echo",
+            Arc::new(|_, upgraded| {
+                Box::pin(async move {
+                    let Upgraded {
+                        mut stream,
+                        leftover,
+                    } = upgraded;
+
+                    stream
+                        .write_all(&leftover.to_ascii_uppercase())
+                        .await
+                        .unwrap();
+
+                    let mut buf = [0; 64];
+                    loop {
+                        let read = stream.read(&mut buf).await.unwrap();
+                        if read == 0 {
+                            break;
+                        }
+                        stream
+                            .write_all(&buf[..read].to_ascii_uppercase())
+                            .await
+                            .unwrap();
+                    }
+                })
+            }),
+        );
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        // The first raw bytes are sent together with the upgrade request
+        client
+            .write_all(
+                b"GET /raw HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: raw-echo\r\n\r\nhello",
+            )
+            .await
+            .unwrap();
+
+        let expected = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: raw-echo\r\nConnection: Upgrade\r\n\r\nHELLO";
+        let mut response = vec![0; expected.len()];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, expected);
+
+        client.write_all(b" world").await.unwrap();
+
+        let mut response = vec![0; 6];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, b" WORLD");
+
+        drop(client);
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_not_found_response_suggestions() {
+        let request = Request::try_from("GET /ech/hello HTTP/1.1\r\n\r\n").unwrap();
+
+        //======================================================================
+        // Test for suggestions disabled (default)
+        let config = ServerConfig::default();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::NotFound);
+        assert_eq!(response.body, None);
+
+        //======================================================================
+        // Test for suggestions enabled
+        let config = ServerConfig {
+            suggest_routes: true,
+            ..Default::default()
+        };
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::NotFound);
+        assert_eq!(
+            String::from_utf8(response.body.unwrap().into_owned()).unwrap(),
+            "Not Found. Did you mean:\n  /echo/*\n"
+        );
+
+        //======================================================================
+        // Test for no close route
+        let request = Request::try_from("GET /completely/different HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::NotFound);
+        assert_eq!(response.body, None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_http09() {
+        use tokio::io::AsyncReadExt;
+
+        let config = ServerConfig {
+            allow_http09: true,
+            ..Default::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        client.write_all(b"GET /echo/abc\r\n").await.unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+
+        assert_eq!(
+            response, b"abc",
+            "Bare body without status line and headers"
+        );
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_pipeline_depth_limit() {
+        use tokio::io::AsyncReadExt;
+
+        let config = ServerConfig {
+            max_pipelined_requests: 2,
+            ..Default::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        client
+            .write_all(
+                b"GET /echo/a HTTP/1.1\r\n\r\nGET /echo/b HTTP/1.1\r\n\r\nGET /echo/c HTTP/1.1\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\r\na\
+             HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\nConnection: close\r\n\r\nb",
+            "Only the allowed number of pipelined requests is answered"
+        );
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_interceptor() {
+        use crate::intercept::Interceptor;
+        use std::sync::Mutex;
+        use tokio::io::AsyncReadExt;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_hook = seen.clone();
+        let config = ServerConfig {
+            interceptor: Interceptor::default()
+                .on_request(move |request| {
+                    seen_by_hook.lock().unwrap().push(request.uri.clone());
+                    request
+                        .headers
+                        .push(("User-Agent".to_string(), "intercepted".to_string()));
+                })
+                .on_response(|request, response| {
+                    response.set_header("Content-Type", "text/x-intercepted");
+                    response.set_header("X-Request-Path", request.path());
+                }),
+            ..Default::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        client
+            .write_all(b"GET /user-agent HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        handle_connection(server, &config).await.unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/x-intercepted\r\nContent-Length: 11\r\n\
+             Connection: close\r\nX-Request-Path: /user-agent\r\n\r\nintercepted",
+            "Request and response altered by the hooks"
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["/user-agent"],
+            "Request hook called once"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_keep_alive_max() {
+        use tokio::io::AsyncReadExt;
+
+        let config = ServerConfig {
+            max_requests_per_connection: 3,
+            ..Default::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        let mut responses = Vec::new();
+        for path in ["a", "b", "c"] {
+            let request = format!(
+                "GET /echo/{} HTTP/1.1\r\nConnection: keep-alive\r\n\r\n",
+                path
+            );
+            client.write_all(request.as_bytes()).await.unwrap();
+
+            let mut response = vec![0; 1024];
+            let len = client.read(&mut response).await.unwrap();
+            responses.push(String::from_utf8(response[..len].to_vec()).unwrap());
+        }
+
+        assert_eq!(
+            responses,
+            [
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\
+                 Keep-Alive: timeout=5, max=2\r\n\r\na",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\
+                 Keep-Alive: timeout=5, max=1\r\n\r\nb",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\
+                 Connection: close\r\n\r\nc",
+            ],
+            "Keep-Alive max counts down, the last allowed request closes the connection"
+        );
+        assert!(server.await.unwrap().is_ok());
+
+        //======================================================================
+        // Test for the Keep-Alive header set by a route
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server =
+            tokio::spawn(async move { handle_connection(server, &ServerConfig::default()).await });
+
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = vec![0; 1024];
+        let len = client.read(&mut response).await.unwrap();
+        drop(client);
+
+        assert_eq!(
+            String::from_utf8(response[..len].to_vec()).unwrap(),
+            "HTTP/1.1 200 OK\r\nKeep-Alive: timeout=5, max=999\r\nConnection: Keep-Alive\r\n\r\n",
+            "Route's Keep-Alive max replaced"
+        );
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_echo_body_response() {
+        let config = ServerConfig::default();
+
+        //======================================================================
+        // Test for a body with a content type
+        let body = "{\"message\":\r\n\"Hello\\r\\nWorld\"}";
+        let request = Request::from_bytes(
+            format!(
+                "POST /echo-body HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+                ("Content-Length".to_string(), body.len().to_string()),
+            ]
+        );
+        assert_eq!(response.body, Some(body.as_bytes().to_vec().into()));
+
+        //======================================================================
+        // Test for an empty body without content type
+        let request = Request::try_from("POST /echo-body HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![("Content-Length".to_string(), "0".to_string())]
+        );
+        assert_eq!(response.body, None);
+
+        //======================================================================
+        // Test for a gzip-encoded body
+        let body = "Hello\r\nWorld";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, body.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut data = format!(
+            "POST /echo-body HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&compressed);
+        let request = Request::from_bytes(&data).unwrap();
+
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), body.len().to_string()),
+            ],
+            "Content-Encoding of the request is not echoed"
+        );
+        assert_eq!(
+            response.body,
+            Some(body.as_bytes().to_vec().into()),
+            "Decoded echo"
+        );
+
+        //======================================================================
+        // Test for an unsupported or corrupt encoding
+        let request = Request::try_from(
+            "POST /echo-body HTTP/1.1\r\nContent-Encoding: br\r\nContent-Length: 3\r\n\r\nabc",
+        )
+        .unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(response.status_code, StatusCode::UnsupportedMediaType);
+
+        let request = Request::try_from(
+            "POST /echo-body HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: 3\r\n\r\nabc",
+        )
+        .unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let called = Arc::new(AtomicBool::new(false));
+        let handler_called = called.clone();
+        let mut config = ServerConfig {
+            router: Router::new()
+                .middleware(maintenance_mode)
+                .any("/*", move |_| {
+                    handler_called.store(true, Ordering::SeqCst);
+                    Box::pin(async { ResponseBuilder::ok() })
+                }),
+            maintenance: true,
+            ..Default::default()
+        };
+
+        let request = Request::try_from("GET /echo/abc HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Type: text/plain\r\nContent-Length: 20\r\n\r\nDown for maintenance",
+            "Maintenance response"
+        );
+        assert!(!called.load(Ordering::SeqCst), "Handler not called");
+
+        config.maintenance = false;
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok, "Handler response");
+        assert!(called.load(Ordering::SeqCst), "Handler called");
+    }
+
+    #[tokio::test]
+    async fn test_get_bytes_response() {
+        let config = ServerConfig {
+            max_generated_bytes: 1024,
+            ..Default::default()
+        };
+
+        //======================================================================
+        // Test for a small number of bytes
+        let request = Request::try_from("GET /bytes/100 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                (
+                    "Content-Type".to_string(),
+                    "application/octet-stream".to_string()
+                ),
+                ("Content-Length".to_string(), "100".to_string()),
+            ]
+        );
+        let body = response.body.unwrap();
+        assert_eq!(body.len(), 100, "Exactly n bytes");
+        assert!(body.iter().any(|&b| b != 0), "Generated content");
+
+        let response = handle_request(&request, &config).await;
+        assert_eq!(response.body, Some(body), "Deterministic content");
+
+        let request = Request::try_from("GET /bytes/0 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(response.body, Some(Vec::new().into()), "No bytes");
+
+        //======================================================================
+        // Test for a request over the limit
+        let request = Request::try_from("GET /bytes/1025 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::PayloadTooLarge);
+        assert_eq!(response.body, None);
+
+        let request = Request::try_from("GET /bytes/1024 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok, "At the limit");
+
+        //======================================================================
+        // Test for an invalid number of bytes
+        let request = Request::try_from("GET /bytes/abc HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn test_get_delay_response() {
+        let config = ServerConfig {
+            max_delay: Duration::from_millis(300),
+            ..Default::default()
+        };
+
+        //======================================================================
+        // Test for the response arriving after the delay
+        let request = Request::try_from("GET /delay/0.1 HTTP/1.1\r\n\r\n").unwrap();
+
+        let start = std::time::Instant::now();
+        let response = handle_request(&request, &config).await;
+        let elapsed = start.elapsed();
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert!(
+            elapsed >= Duration::from_millis(100),
+            "Delayed: {:?}",
+            elapsed
+        );
+        assert!(elapsed < Duration::from_millis(300), "Not delayed too long");
+
+        //======================================================================
+        // Test for the delay being capped
+        let request = Request::try_from("GET /delay/60 HTTP/1.1\r\n\r\n").unwrap();
+
+        let start = std::time::Instant::now();
+        let response = handle_request(&request, &config).await;
+        let elapsed = start.elapsed();
+
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert!(
+            elapsed >= Duration::from_millis(300),
+            "Delayed: {:?}",
+            elapsed
+        );
+        assert!(elapsed < Duration::from_secs(2), "Capped delay");
+
+        //======================================================================
+        // Test for an invalid delay
+        for delay in ["abc", "-1", "NaN"] {
+            let request =
+                Request::try_from(format!("GET /delay/{} HTTP/1.1\r\n\r\n", delay).as_str())
+                    .unwrap();
+            let response = handle_request(&request, &config).await;
+            assert_eq!(response.status_code, StatusCode::BadRequest, "{}", delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_chunked_upload() {
+        use tokio::io::AsyncReadExt;
+
+        let config = memory_config(MemoryStore::new());
+        let (mut client, server) = tokio::io::duplex(1024);
+
+        client
+            .write_all(
+                b"PUT /files/a.txt HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                  5\r\nHello\r\n7\r\n, World\r\n0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        handle_connection(server, &config).await.unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 201 Created\r\n"),
+            "Chunked upload saved: {:?}",
+            response
+        );
+        assert_eq!(
+            config.file_store.read("/srv/files/a.txt").await.unwrap(),
+            b"Hello, World",
+            "Decoded body saved"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_slow_head() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig::default();
+        assert!(
+            config.limits.read_timeout > KEEP_ALIVE_TIMEOUT + Duration::from_secs(1),
+            "Read timeout longer than the test"
+        );
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        // The head trickles in for longer than the keep-alive timeout, but within the read timeout
+        let head = b"GET /echo/slow HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let pause = (KEEP_ALIVE_TIMEOUT + Duration::from_secs(1)) / 4;
+        for part in head.chunks(head.len() / 4 + 1) {
+            client.write_all(part).await.unwrap();
+            tokio::time::sleep(pause).await;
+        }
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap().unwrap();
+
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK\r\n") && response.ends_with("slow"),
+            "Slow head answered: {:?}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_large_head() {
+        use tokio::io::AsyncReadExt;
+
+        // 16 KiB of headers, far more than one read of the connection returns, with the header
+        // the response depends on at the very end
+        let mut request = "GET /user-agent HTTP/1.1\r\n".to_string();
+        for i in 0..16 {
+            request.push_str(&format!("X-Padding-{:02}: {}\r\n", i, "a".repeat(1024)));
+        }
+        request.push_str("User-Agent: large-client/1.0\r\nConnection: close\r\n\r\n");
+        assert!(request.len() > 16 * 1024, "Request head over 16 KiB");
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig::default();
+        let server = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap().unwrap();
+
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 16\r\n\
+             Connection: close\r\n\r\nlarge-client/1.0",
+            "Whole head parsed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_request_then_eof() {
+        use tokio::io::AsyncReadExt;
+
+        for (request, body) in [
+            ("GET / HTTP/1.1\r\n\r\n", ""),
+            ("GET /delay/0.1 HTTP/1.1\r\n\r\n", ""),
+            ("GET /echo/abc HTTP/1.1\r\n\r\n", "abc"),
+        ] {
+            let (mut client, server) = tokio::io::duplex(1024);
+            client.write_all(request.as_bytes()).await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let result = handle_connection(server, &ServerConfig::default()).await;
+            assert!(result.is_ok(), "Connection closed cleanly: {:?}", request);
+
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            assert!(
+                response.starts_with("HTTP/1.1 200 OK\r\n") && response.ends_with(body),
+                "Response to {:?} written before the close: {:?}",
+                request,
+                response
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_cancelled_on_disconnect() {
+        let config = ServerConfig {
+            max_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let (mut client, server) = tokio::io::duplex(1024);
+
+        client
+            .write_all(b"GET /delay/60 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        let connection = tokio::spawn(async move { handle_connection(server, &config).await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(client);
+
+        let result = tokio::time::timeout(Duration::from_secs(1), connection).await;
+        assert!(
+            matches!(result, Ok(Ok(Ok(())))),
+            "Connection closed without waiting for the delay"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_request_deadline() {
+        use tokio::io::AsyncReadExt;
+
+        let config = ServerConfig {
+            max_delay: Duration::from_secs(60),
+            request_deadline: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        //======================================================================
+        // Test for a request answered in time
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET /echo/abc HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        handle_connection(server, &config).await.unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK\r\n"),
+            "Fast request: {:?}",
+            response
+        );
+
+        //======================================================================
+        // Test for a slow handler
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET /delay/60 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let result =
+            tokio::time::timeout(Duration::from_secs(1), handle_connection(server, &config)).await;
+        assert!(
+            matches!(result, Ok(Ok(()))),
+            "Handler cut off at the deadline"
+        );
+        assert!(
+            start.elapsed() >= Duration::from_millis(200),
+            "Handler given until the deadline"
+        );
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 504 Gateway Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "Slow handler"
+        );
+
+        //======================================================================
+        // Test for a body that doesn't arrive in time
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"POST /echo-body HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc")
+            .await
+            .unwrap();
+
+        let result =
+            tokio::time::timeout(Duration::from_secs(1), handle_connection(server, &config)).await;
+        assert!(
+            matches!(
+                result,
+                Ok(Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::DeadlineExceeded
+                }))
+            ),
+            "Body cut off at the deadline"
+        );
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 504 Gateway Timeout\r\n"),
+            "Slow body: {:?}",
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_shutdown_response() {
+        let shutdown_request = |authorization: Option<&str>| {
+            let authorization = authorization
+                .map(|value| format!("Authorization: {}\r\n", value))
+                .unwrap_or_default();
+            Request::try_from(
+                format!("POST /admin/shutdown HTTP/1.1\r\n{}\r\n", authorization).as_str(),
+            )
+            .unwrap()
+        };
+
+        //======================================================================
+        // Test for the endpoint without a configured token
+        let config = ServerConfig::default();
+        let response = handle_request(&shutdown_request(Some("Bearer secret")), &config).await;
+        assert_eq!(response.status_code, StatusCode::NotFound, "Disabled");
+        assert!(!config.shutdown.is_triggered(), "No shutdown when disabled");
+
+        //======================================================================
+        // Test for missing and wrong tokens
+        let config = ServerConfig {
+            shutdown_token: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        let response = handle_request(&shutdown_request(None), &config).await;
+        assert_eq!(response.status_code, StatusCode::Unauthorized, "No token");
+        assert!(
+            response
+                .headers
+                .contains(&("WWW-Authenticate".to_string(), "Bearer".to_string())),
+            "Authentication scheme"
+        );
+
+        let response = handle_request(&shutdown_request(Some("Basic secret")), &config).await;
+        assert_eq!(
+            response.status_code,
+            StatusCode::Unauthorized,
+            "No bearer token"
+        );
+
+        for token in ["Bearer secre", "Bearer secret2", "Bearer SECRET"] {
+            let response = handle_request(&shutdown_request(Some(token)), &config).await;
+            assert_eq!(response.status_code, StatusCode::Forbidden, "{}", token);
+        }
+        assert!(!config.shutdown.is_triggered(), "No shutdown on failures");
+
+        //======================================================================
+        // Test for the correct token
+        let response = handle_request(&shutdown_request(Some("Bearer secret")), &config).await;
+        assert_eq!(response.status_code, StatusCode::Ok, "Correct token");
+        assert!(config.shutdown.is_triggered(), "Shutdown initiated");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"), "Same bytes");
+        assert!(!constant_time_eq(b"secret", b"secreT"), "Last byte differs");
+        assert!(
+            !constant_time_eq(b"secret", b"secrets"),
+            "Different lengths"
+        );
+        assert!(constant_time_eq(b"", b""), "Empty");
+    }
+
+    #[tokio::test]
+    async fn test_get_status_response() {
+        let config = ServerConfig::default();
+
+        let request = Request::try_from("GET /status/404 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n\r\n404 Not Found",
+            "404 status line"
+        );
 
-pub async fn handle_connection(
-    mut stream: TcpStream,
-    files_dir: &String,
-) -> Result<(), ParseRequestError> {
-    let mut buf_reader = BufReader::new(&mut stream);
+        let request = Request::try_from("GET /status/503 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-    let request_str = std::str::from_utf8(buf_reader.fill_buf().await?)?;
+        assert!(
+            response
+                .to_bytes_vec()
+                .starts_with(b"HTTP/1.1 503 Service Unavailable\r\n"),
+            "503 status line"
+        );
+        assert_eq!(
+            response.body,
+            Some(b"503 Service Unavailable".to_vec().into())
+        );
 
-    let request = Request::try_from(request_str)?;
+        let request = Request::try_from("GET /status/429 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-    let path = request.uri.as_str();
-    let method = request.method;
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 429 Too Many Requests\r\nContent-Type: text/plain\r\nContent-Length: 21\r\n\r\n429 Too Many Requests",
+            "Any registered status code"
+        );
 
-    let accept_encoding_gzip_header = request
-        .headers
-        .iter()
-        .find(|(k, v)| k == "Accept-Encoding" && (v == "gzip" || v.contains("gzip")));
-
-    let response_builder = match path {
-        "/" => ResponseBuilder::ok()
-            .with(vec![
-                ("Connection", "Keep-Alive"),
-                ("Keep-Alive", "timeout=5, max=1000"),
-            ])
-            // Disable Content-Length header generation to pass codecrafters tests
-            .without_content_length_header(),
+        let request = Request::try_from("GET /status/204 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-        "/user-agent" => get_user_agent_response(&request),
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 204 No Content\r\n\r\n",
+            "204 without body"
+        );
 
-        other => {
-            if other.starts_with("/echo/") {
-                get_echo_response(other.trim_start_matches("/echo/"))
-            } else if path.starts_with("/files/") {
-                match method {
-                    Method::Post => post_file_response(&request, files_dir).await,
-                    _ => get_file_response(other.trim_start_matches("/files/"), files_dir).await,
-                }
-            } else {
-                ResponseBuilder::not_found()
-            }
+        for code in ["101", "600", "299", "abc", "-200"] {
+            let request =
+                Request::try_from(format!("GET /status/{} HTTP/1.1\r\n\r\n", code).as_str())
+                    .unwrap();
+            let response = handle_request(&request, &config).await;
+            assert_eq!(response.status_code, StatusCode::BadRequest, "{}", code);
         }
-    };
-
-    let response = match accept_encoding_gzip_header {
-        Some(_) => response_builder.with(("Content-Encoding", "gzip")).build(),
-        None => response_builder.build(),
-    };
+    }
 
-    stream
-        .write_all(response.to_bytes_vec().as_slice())
-        .await
-        .expect("Failed to write to stream");
+    /// Stream reading a fixed request and failing every write after the first `capacity` bytes
+    struct FailingStream {
+        request: &'static [u8],
+        written: Vec<u8>,
+        capacity: usize,
+    }
 
-    stream.flush().await.expect("Failed to flush stream");
+    impl AsyncRead for FailingStream {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            let n = self.request.len().min(buf.remaining());
+            buf.put_slice(&self.request[..n]);
+            self.request = &self.request[n..];
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
 
-    Ok(())
-}
+    impl AsyncWrite for FailingStream {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let n = buf.len().min(self.capacity - self.written.len());
 
-async fn post_file_response(request: &Request, files_dir: &String) -> ResponseBuilder<StatusCode> {
-    let file_name = request.uri.as_str().trim_start_matches("/files/");
+            if n == 0 {
+                return std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
 
-    let path = format!("{}/{}", files_dir, file_name);
-    let mut file = match OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(path)
-        .await
-    {
-        Ok(file) => file,
-        Err(_) => return ResponseBuilder::internal_server_error().without_content_length_header(),
-    };
+            self.written.extend_from_slice(&buf[..n]);
+            std::task::Poll::Ready(Ok(n))
+        }
 
-    let file_content = request.body.as_ref();
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
 
-    match file.write(file_content).await {
-        Ok(_) => ResponseBuilder::new()
-            .with_status_code(StatusCode::Created)
-            .without_content_length_header(),
-        Err(_) => ResponseBuilder::bad_request(),
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
     }
-}
 
-async fn get_file_response(file_name: &str, files_dir: &String) -> ResponseBuilder<StatusCode> {
-    let path = format!("{}/{}", files_dir, file_name);
-    let file = match tokio::fs::read(path).await {
-        Ok(file) => file,
-        Err(_) => return ResponseBuilder::not_found().without_content_length_header(),
-    };
+    #[tokio::test]
+    async fn test_handle_connection_partial_write() {
+        //======================================================================
+        // Test for the number of bytes written before the failure
+        let response = ResponseBuilder::ok().body("Hello, World!").build();
+        let mut stream = FailingStream {
+            request: b"",
+            written: Vec::new(),
+            capacity: 20,
+        };
 
-    ResponseBuilder::ok()
-        .with(("Content-Type", "application/octet-stream"))
-        .body(file)
-}
+        let err = response.write_to(&mut stream).await.unwrap_err();
+        assert_eq!(err.written, 20, "Bytes written before the failure");
+        assert_eq!(err.source.kind(), std::io::ErrorKind::BrokenPipe);
 
-fn get_user_agent_response(request: &Request) -> ResponseBuilder<StatusCode> {
-    let user_agent = request
-        .headers
-        .iter()
-        .find(|(k, _)| k == "User-Agent")
-        .map(|(_, v)| v);
+        //======================================================================
+        // Test for the connection ending without panicking
+        let stream = FailingStream {
+            request: b"GET /echo/abc HTTP/1.1\r\n\r\nGET /echo/def HTTP/1.1\r\n\r\n",
+            written: Vec::new(),
+            capacity: 10,
+        };
 
-    match user_agent {
-        Some(user_agent) => ResponseBuilder::ok()
-            .with(("Content-Type", "text/plain"))
-            .body(user_agent.as_bytes().to_vec()),
-        None => ResponseBuilder::bad_request(),
+        let result = handle_connection(stream, &ServerConfig::default()).await;
+        assert!(result.is_ok(), "Connection dropped after the failed write");
     }
-}
 
-fn get_echo_response(content: &str) -> ResponseBuilder<StatusCode> {
-    let response_builder = ResponseBuilder::ok().with(("Content-Type", "text/plain"));
+    #[tokio::test]
+    async fn test_get_favicon_response() {
+        let request = Request::try_from("GET /favicon.ico HTTP/1.1\r\n\r\n").unwrap();
 
-    if content.is_empty() {
-        response_builder
-    } else {
-        response_builder.body(content)
-    }
-}
+        let response = handle_request(&request, &ServerConfig::default()).await;
+        assert_eq!(response.status_code, StatusCode::NotFound, "Opt-in");
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::http::{Request, StatusCode};
+        let config = ServerConfig {
+            favicon: true,
+            ..Default::default()
+        };
+        let response = handle_request(&request, &config).await;
 
-    #[test]
-    fn test_get_user_agent_response() {
+        assert_eq!(response.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "image/x-icon".to_string()),
+                (
+                    "Cache-Control".to_string(),
+                    "public, max-age=86400".to_string()
+                ),
+                ("Content-Length".to_string(), FAVICON.len().to_string()),
+            ]
+        );
+        assert!(
+            response.body.unwrap().starts_with(&[0, 0, 1, 0]),
+            "ICO header"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_options_response() {
         //======================================================================
-        // Test for user agent
-        let request =
-            Request::try_from("GET /user-agent HTTP/1.1\r\nUser-Agent: curl/7.68.0\r\n\r\n")
-                .unwrap();
+        // Test for a simple server without an OPTIONS route
+        let config = ServerConfig {
+            router: Router::new().route(Method::Get, "/", |_| {
+                Box::pin(async { ResponseBuilder::ok() })
+            }),
+            ..Default::default()
+        };
 
-        let response_builder = get_user_agent_response(&request);
-        let response = response_builder.build();
+        let request = Request::try_from("OPTIONS / HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-        let body_len_str = "curl/7.68.0".len().to_string();
-        let headers: Vec<(String, String)> = vec![
-            ("Content-Type".to_string(), "text/plain".to_string()),
-            ("Content-Length".to_string(), body_len_str),
-        ];
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 204 No Content\r\nAllow: GET, OPTIONS, TRACE\r\n\r\n",
+            "Default OPTIONS response"
+        );
 
-        assert_eq!(response.status_code, StatusCode::Ok);
-        assert_eq!(response.headers, headers);
-        assert_eq!(response.body, Some(b"curl/7.68.0".to_vec()));
+        let request = Request::try_from("OPTIONS /unknown HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::NoContent);
+        assert_eq!(
+            response.headers,
+            vec![("Allow".to_string(), "OPTIONS, TRACE".to_string())],
+            "Unknown path"
+        );
 
         //======================================================================
-        // Test for no user agent
-        let request = Request::try_from("GET /user-agent HTTP/1.1\r\n\r\n").unwrap();
+        // Test for an OPTIONS route taking precedence
+        let config = ServerConfig {
+            router: Router::new().route(Method::Options, "/", |_| {
+                Box::pin(async { ResponseBuilder::ok().body("custom") })
+            }),
+            ..Default::default()
+        };
 
-        let response_builder = get_user_agent_response(&request);
-        let response = response_builder.build();
+        let request = Request::try_from("OPTIONS / HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-        assert_eq!(response.status_code, StatusCode::BadRequest);
+        assert_eq!(response.status_code, StatusCode::Ok);
         assert_eq!(
-            response.headers,
-            vec![("Content-Length".to_string(), "0".to_string())]
+            response.body,
+            Some(b"custom".to_vec().into()),
+            "Registered route"
         );
-        assert_eq!(response.body, None);
     }
 
-    #[test]
-    fn test_get_echo_response() {
-        //======================================================================
-        // Test for non-empty content
-        let request = Request::try_from("GET /echo/Hello%20World HTTP/1.1\r\n\r\n").unwrap();
-
-        let path = request.uri.as_str().trim_start_matches("/echo/");
+    #[tokio::test]
+    async fn test_route_cache_control() {
+        let config = ServerConfig {
+            router: router()
+                .cache_control("/echo/*", "public, max-age=60")
+                .cache_control("/user-agent", "no-store"),
+            ..Default::default()
+        };
 
-        let response_builder = get_echo_response(path);
-        let response = response_builder.build();
+        for (request, cache_control) in [
+            ("GET /echo/abc HTTP/1.1\r\n\r\n", Some("public, max-age=60")),
+            ("GET /user-agent HTTP/1.1\r\n\r\n", Some("no-store")),
+            ("GET / HTTP/1.1\r\n\r\n", None),
+            ("GET /echo HTTP/1.1\r\n\r\n", None),
+        ] {
+            let response = handle_request(&Request::try_from(request).unwrap(), &config).await;
 
-        let body_len_str = b"Hello%20World".len().to_string();
-        let headers: Vec<(String, String)> = vec![
-            ("Content-Type".to_string(), "text/plain".to_string()),
-            ("Content-Length".to_string(), body_len_str),
-        ];
+            assert_eq!(
+                response
+                    .headers
+                    .iter()
+                    .find(|(name, _)| name == "Cache-Control")
+                    .map(|(_, value)| value.as_str()),
+                cache_control,
+                "Cache-Control of {:?}",
+                request
+            );
+        }
+    }
 
-        assert_eq!(response.status_code, StatusCode::Ok);
-        assert_eq!(response.headers, headers);
-        assert_eq!(response.body, Some(b"Hello%20World".to_vec()));
+    #[tokio::test]
+    async fn test_cors_options() {
+        let config = ServerConfig {
+            router: Router::new()
+                .route(Method::Get, "/items/*", |_| {
+                    Box::pin(async { ResponseBuilder::ok() })
+                })
+                .route(Method::Delete, "/items/*", |_| {
+                    Box::pin(async { ResponseBuilder::ok() })
+                })
+                .route(Method::Post, "/upload", |_| {
+                    Box::pin(async { ResponseBuilder::ok() })
+                })
+                .cors(
+                    CorsPolicy::new()
+                        .allow_origin("https://a.example")
+                        .max_age(Duration::from_secs(60)),
+                ),
+            ..Default::default()
+        };
 
         //======================================================================
-        // Test for empty content
-        let request = Request::try_from("GET /echo/ HTTP/1.1\r\n\r\n").unwrap();
+        // Test for preflight requests on registered paths
+        for (path, allow) in [
+            ("/items/1", "GET, DELETE, OPTIONS, TRACE"),
+            ("/upload", "POST, OPTIONS, TRACE"),
+        ] {
+            let request = Request::try_from(
+                format!(
+                    "OPTIONS {} HTTP/1.1\r\nOrigin: https://a.example\r\n\
+                     Access-Control-Request-Method: GET\r\n\
+                     Access-Control-Request-Headers: Content-Type\r\n\r\n",
+                    path
+                )
+                .as_str(),
+            )
+            .unwrap();
+            let response = handle_request(&request, &config).await;
 
-        let path = request.uri.as_str().trim_start_matches("/echo/");
+            assert_eq!(response.status_code, StatusCode::NoContent, "{}", path);
+            assert_eq!(
+                response.headers,
+                vec![
+                    ("Allow".to_string(), allow.to_string()),
+                    (
+                        "Access-Control-Allow-Origin".to_string(),
+                        "https://a.example".to_string()
+                    ),
+                    ("Vary".to_string(), "Origin".to_string()),
+                    (
+                        "Access-Control-Allow-Methods".to_string(),
+                        allow.to_string()
+                    ),
+                    (
+                        "Access-Control-Allow-Headers".to_string(),
+                        "Content-Type".to_string()
+                    ),
+                    ("Access-Control-Max-Age".to_string(), "60".to_string()),
+                ],
+                "CORS headers for {}",
+                path
+            );
+        }
 
-        let response_builder = get_echo_response(path);
-        let response = response_builder.build();
+        //======================================================================
+        // Test for a plain OPTIONS request and other methods
+        let request = Request::try_from("OPTIONS /upload HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
 
-        let headers: Vec<(String, String)> = vec![
-            ("Content-Type".to_string(), "text/plain".to_string()),
-            ("Content-Length".to_string(), "0".to_string()),
-        ];
+        assert_eq!(
+            response.headers,
+            vec![("Allow".to_string(), "POST, OPTIONS, TRACE".to_string())],
+            "No CORS headers without Origin"
+        );
 
-        assert_eq!(response.status_code, StatusCode::Ok);
-        assert_eq!(response.headers, headers);
-        assert_eq!(response.body, None);
+        let request = Request::try_from("GET /items/1 HTTP/1.1\r\n\r\n").unwrap();
+        let response = handle_request(&request, &config).await;
+
+        assert_eq!(response.status_code, StatusCode::Ok, "Routes still served");
     }
 
     #[tokio::test]
-    async fn test_get_file_response() {
-        //======================================================================
-        // Test for file found
-        let root_dir = env!("CARGO_MANIFEST_DIR");
-        let tmp_dir = format!("{}/temp", root_dir);
-        let files_dir = format!("{}/files", tmp_dir);
-        let file_name = "test.txt";
-        let file_content = "Hello World";
+    async fn test_handle_connection_stats() {
+        use tokio::io::AsyncReadExt;
+
+        let requests: &[u8] =
+            b"GET /echo/abc HTTP/1.1\r\n\r\nGET /echo/de HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(requests).await.unwrap();
 
-        // Create files directory if it doesn't exist
-        std::fs::create_dir_all(&files_dir).unwrap();
+        let stats = Arc::new(ConnectionStats::default());
+        handle_connection_with_stats(server, &ServerConfig::default(), stats.clone())
+            .await
+            .unwrap();
+
+        let mut responses = Vec::new();
+        client.read_to_end(&mut responses).await.unwrap();
+
+        assert_eq!(stats.requests(), 2, "Requests");
+        assert_eq!(
+            stats.bytes_read(),
+            requests.len() as u64,
+            "Bytes read over both requests"
+        );
+        assert_eq!(
+            stats.bytes_written(),
+            responses.len() as u64,
+            "Bytes written over both responses"
+        );
+        assert_eq!(
+            stats.to_string(),
+            format!(
+                "Connection closed: 2 requests, {} bytes read, {} bytes written",
+                requests.len(),
+                responses.len()
+            ),
+            "Summary"
+        );
+    }
 
-        // Create temporary file
-        let file_path = format!("{}/{}", files_dir, file_name);
+    #[tokio::test]
+    async fn test_handle_connection_eof_body() {
+        use tokio::io::AsyncReadExt;
 
-        std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&file_path)
+        let config = ServerConfig {
+            eof_body: true,
+            ..Default::default()
+        };
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"POST /echo-body HTTP/1.1\r\nContent-Type: text/plain\r\n\r\nHello, World!")
+            .await
             .unwrap();
+        // End the body by closing the client's side of the connection
+        client.shutdown().await.unwrap();
 
-        std::fs::write(file_path, file_content).unwrap();
+        handle_connection(server, &config).await.unwrap();
 
-        let response_builder = get_file_response(file_name, &files_dir).await;
-        let response = response_builder.build();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
 
-        assert_eq!(response.status_code, StatusCode::Ok);
         assert_eq!(
-            response.headers,
-            vec![
-                (
-                    "Content-Type".to_string(),
-                    "application/octet-stream".to_string()
-                ),
-                ("Content-Length".to_string(), file_content.len().to_string())
-            ]
+            response,
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n\r\nHello, World!",
+            "Body echoed to the half-closed connection"
         );
-        assert_eq!(response.body, Some(file_content.as_bytes().to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_canonical_header_order() {
+        use tokio::io::AsyncReadExt;
 
-        // Remove temporary directory and its contents
-        std::fs::remove_dir_all(tmp_dir).unwrap();
+        let request: &[u8] =
+            b"GET /echo/abc HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n";
+        let head = |config: ServerConfig| async move {
+            let (mut client, server) = tokio::io::duplex(1024);
+            client.write_all(request).await.unwrap();
+            handle_connection(server, &config).await.unwrap();
 
-        //======================================================================
-        // Test file not found
-        let response_builder = get_file_response(file_name, &files_dir).await;
-        let response = response_builder.build();
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            let head_end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            String::from_utf8(response[..head_end].to_vec()).unwrap()
+        };
 
-        assert_eq!(response.status_code, StatusCode::NotFound);
-        assert!(response.headers.is_empty());
-        assert_eq!(response.body, None);
+        assert_eq!(
+            head(ServerConfig::default()).await,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Encoding: gzip\r\n\
+             Content-Length: 23\r\nConnection: close",
+            "Insertion order by default"
+        );
+        assert_eq!(
+            head(ServerConfig {
+                canonical_header_order: true,
+                ..Default::default()
+            })
+            .await,
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: text/plain\r\n\
+             Content-Encoding: gzip\r\nContent-Length: 23",
+            "Canonical order"
+        );
     }
 
     #[tokio::test]
-    async fn test_post_file_response() {
-        //======================================================================
-        // Test for file created
-        let root_dir = env!("CARGO_MANIFEST_DIR");
-        let tmp_dir = format!("{}/tmp", root_dir);
-        let files_dir = format!("{}/files", tmp_dir);
-        let file_name = "test.txt";
-        let file_content = "Hello World";
+    async fn test_handle_connection_streaming_body() {
+        use tokio::io::AsyncReadExt;
 
-        // Create files directory if it doesn't exist
-        std::fs::create_dir_all(&files_dir).unwrap();
+        const BODY_LEN: usize = 4 * 1024 * 1024;
 
-        let request = Request::try_from(
-            format!(
-                "POST /files/{} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
-                file_name,
-                file_content.len(),
-                file_content
+        // Counts the bytes of the body, holding no more than one small chunk at a time
+        let config = ServerConfig {
+            router: Router::new().stream(Method::Post, "/length", |_, body| {
+                Box::pin(async move {
+                    let mut chunk = [0; 4096];
+                    let mut len = 0;
+
+                    loop {
+                        match body.read(&mut chunk).await {
+                            Ok(0) => break,
+                            Ok(read) => len += read,
+                            Err(_) => return ResponseBuilder::bad_request(),
+                        }
+                    }
+
+                    ResponseBuilder::ok().body(len.to_string())
+                })
+            }),
+            ..Default::default()
+        };
+
+        // The connection buffers far less than the body, so it must be consumed while it arrives
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let writer = tokio::spawn(async move {
+            let head = format!(
+                "POST /length HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                BODY_LEN
+            );
+            client.write_all(head.as_bytes()).await.unwrap();
+            for _ in 0..BODY_LEN / 1024 {
+                client.write_all(&[b'x'; 1024]).await.unwrap();
+            }
+            client
+                .write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+
+            let mut responses = Vec::new();
+            client.read_to_end(&mut responses).await.unwrap();
+            responses
+        });
+
+        handle_connection(server, &config).await.unwrap();
+        let responses = String::from_utf8(writer.await.unwrap()).unwrap();
+
+        assert!(
+            responses.starts_with(&format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\n{}",
+                BODY_LEN
+            )),
+            "Length of the streamed body: {}",
+            responses
+        );
+        assert!(
+            responses.contains("HTTP/1.1 404 Not Found"),
+            "Next request answered"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_rejects_bad_targets() {
+        use tokio::io::AsyncReadExt;
+
+        let response_to = |request: String| async move {
+            let (mut client, server) = tokio::io::duplex(64 * 1024);
+            client.write_all(request.as_bytes()).await.unwrap();
+
+            let result = handle_connection(server, &ServerConfig::default()).await;
+
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            (
+                result.unwrap_err().kind,
+                String::from_utf8(response).unwrap(),
             )
-            .as_str(),
-        )
-        .unwrap();
+        };
 
-        let response_builder = post_file_response(&request, &files_dir).await;
-        let response = response_builder.build();
+        //======================================================================
+        // Test for a malformed target
+        let (kind, response) = response_to("GET echo/abc HTTP/1.1\r\n\r\n".to_string()).await;
 
-        assert_eq!(response.status_code, StatusCode::Created);
-        assert!(response.headers.is_empty());
-        assert_eq!(response.body, None);
+        assert_eq!(kind, crate::http::ParseRequestErrorKind::InvalidTarget);
+        assert_eq!(
+            response, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "Malformed target"
+        );
 
-        // Check if file was created
-        let file_path = format!("{}/{}", files_dir, file_name);
-        let file_content = std::fs::read_to_string(file_path).unwrap();
+        //======================================================================
+        // Test for an unknown and a malformed method
+        let (kind, response) = response_to("FOOBAR / HTTP/1.1\r\n\r\n".to_string()).await;
+
+        assert_eq!(kind, crate::http::ParseRequestErrorKind::UnknownMethod);
+        assert_eq!(
+            response,
+            "HTTP/1.1 501 Not Implemented\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "Unknown method"
+        );
 
-        assert_eq!(file_content, "Hello World");
+        let (kind, response) = response_to("FOO(BAR / HTTP/1.1\r\n\r\n".to_string()).await;
 
-        // Remove temporary directory and its contents
-        std::fs::remove_dir_all(tmp_dir).unwrap();
+        assert_eq!(kind, crate::http::ParseRequestErrorKind::InvalidMethod);
+        assert_eq!(
+            response, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "Malformed method"
+        );
 
         //======================================================================
-        // Test for file not created
-        let request = Request::try_from("POST /files/test.txt HTTP/1.1\r\n\r\n").unwrap();
+        // Test for an over-long target
+        let request = format!("GET /echo/{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+        let (kind, response) = response_to(request).await;
 
-        let response_builder = post_file_response(&request, &files_dir).await;
-        let response = response_builder.build();
+        assert_eq!(kind, crate::http::ParseRequestErrorKind::UriTooLong);
+        assert_eq!(
+            response, "HTTP/1.1 414 URI Too Long\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "Over-long target"
+        );
+    }
 
-        assert_eq!(response.status_code, StatusCode::InternalServerError);
-        assert!(response.headers.is_empty());
-        assert_eq!(response.body, None);
+    #[tokio::test]
+    async fn test_handle_connection_single_connection_header() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        handle_connection(server, &ServerConfig::default())
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nKeep-Alive: timeout=5, max=1000\r\nConnection: close\r\n\r\n",
+            "Connection header of the route replaced"
+        );
     }
 }
@@ -0,0 +1,116 @@
+use std::{num::ParseIntError, str::FromStr};
+
+/// # How the buffer requests are read into is sized.
+///
+/// The buffer starts at `initial_size` bytes and grows according to `growth` whenever it's
+/// full. A small initial size saves memory on connections sending small requests; a faster
+/// growth needs fewer reads (and reallocations) for large ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferStrategy {
+    /// Capacity of the buffer before the first read, in bytes
+    pub initial_size: usize,
+    pub growth: BufferGrowth,
+}
+
+impl Default for BufferStrategy {
+    fn default() -> Self {
+        Self {
+            initial_size: 8 * 1024,
+            growth: BufferGrowth::Double,
+        }
+    }
+}
+
+impl BufferStrategy {
+    /// # Make room for at least one more byte in a full buffer.
+    ///
+    /// Does nothing if the buffer has spare capacity.
+    pub fn reserve(&self, buf: &mut Vec<u8>) {
+        if buf.len() < buf.capacity() {
+            return;
+        }
+
+        let additional = match self.growth {
+            BufferGrowth::Double => buf.capacity().max(self.initial_size),
+            BufferGrowth::Fixed(increment) => increment,
+        };
+
+        buf.reserve_exact(additional.max(1));
+    }
+}
+
+/// How a full read buffer grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferGrowth {
+    /// Double the capacity
+    #[default]
+    Double,
+    /// Add a fixed number of bytes to the capacity
+    Fixed(usize),
+}
+
+impl FromStr for BufferGrowth {
+    type Err = ParseIntError;
+
+    /// `double`, or the fixed increment in bytes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "double" => Ok(Self::Double),
+            increment => increment.parse().map(Self::Fixed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        //======================================================================
+        // Test for doubling
+        let strategy = BufferStrategy {
+            initial_size: 4,
+            growth: BufferGrowth::Double,
+        };
+
+        let mut buf = Vec::with_capacity(strategy.initial_size);
+        buf.extend_from_slice(b"ab");
+        strategy.reserve(&mut buf);
+        assert_eq!(buf.capacity(), 4, "Spare capacity left");
+
+        buf.extend_from_slice(b"cd");
+        strategy.reserve(&mut buf);
+        assert_eq!(buf.capacity(), 8, "Doubled");
+
+        //======================================================================
+        // Test for fixed increments
+        let strategy = BufferStrategy {
+            initial_size: 4,
+            growth: BufferGrowth::Fixed(3),
+        };
+
+        let mut buf = Vec::with_capacity(strategy.initial_size);
+        buf.extend_from_slice(b"abcd");
+        strategy.reserve(&mut buf);
+        assert_eq!(buf.capacity(), 7, "Grown by the increment");
+
+        //======================================================================
+        // Test for an empty buffer without capacity
+        let strategy = BufferStrategy {
+            initial_size: 0,
+            growth: BufferGrowth::Double,
+        };
+
+        let mut buf = Vec::new();
+        strategy.reserve(&mut buf);
+        assert!(buf.capacity() >= 1, "Room for one byte");
+    }
+
+    #[test]
+    fn test_buffer_growth_from_str() {
+        assert_eq!("double".parse(), Ok(BufferGrowth::Double), "Double");
+        assert_eq!("1024".parse(), Ok(BufferGrowth::Fixed(1024)), "Fixed");
+        assert!("twice".parse::<BufferGrowth>().is_err(), "Invalid");
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// # Format a point in time as an HTTP date.
+///
+/// Uses the IMF-fixdate format of RFC 9110 (`Sun, 06 Nov 1994 08:49:37 GMT`), the only one
+/// servers may send in headers like `Date`, `Expires` and `Last-Modified`. Fractions of a
+/// second are dropped and times before 1970 are formatted as the epoch.
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// # Parse an HTTP date.
+///
+/// Only the IMF-fixdate format is accepted, which all current clients send in headers like
+/// `If-Modified-Since`. Returns `None` for other formats and invalid dates.
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let (weekday, rest) = date.split_once(", ")?;
+    let mut parts = rest.split(' ');
+
+    let day: u64 = parts.next().filter(|day| day.len() == 2)?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|&name| name == month)? as u64 + 1;
+    let year: u64 = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+    let time = parts.next()?;
+    if parts.next() != Some("GMT") || parts.next().is_some() {
+        return None;
+    }
+
+    let mut time = time.split(':').map(|part| match part.len() {
+        2 => part.parse::<u64>().ok(),
+        _ => None,
+    });
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day)?;
+    if WEEKDAYS[(days % 7) as usize] != weekday {
+        return None;
+    }
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+}
+
+/// Convert a (year, month, day) date to a number of days since 1970-01-01, the inverse of
+/// [`civil_from_days`]. Returns `None` for days that don't exist and dates before 1970.
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_len = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if year < 1970 || day == 0 || day > month_len {
+        return None;
+    }
+
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let mp = (month + 9) % 12;
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/// Convert a number of days since 1970-01-01 to a (year, month, day) date of the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01, so leap days are at the end of a year
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_http_date() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT",
+            "Epoch"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "RFC 9110 example"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_secs(951_825_600)),
+            "Tue, 29 Feb 2000 12:00:00 GMT",
+            "Leap day"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_millis(4_102_444_799_999)),
+            "Thu, 31 Dec 2099 23:59:59 GMT",
+            "Fractions of a second dropped"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH - Duration::from_secs(1)),
+            "Thu, 01 Jan 1970 00:00:00 GMT",
+            "Before the epoch"
+        );
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "RFC 9110 example"
+        );
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 12:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_825_600)),
+            "Leap day"
+        );
+
+        let time = UNIX_EPOCH + Duration::from_secs(4_102_444_799);
+        assert_eq!(
+            parse_http_date(&format_http_date(time)),
+            Some(time),
+            "Formatted date parsed back"
+        );
+
+        for invalid in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Mon, 06 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Wed, 29 Feb 2001 12:00:00 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "",
+        ] {
+            assert_eq!(parse_http_date(invalid), None, "Invalid date {:?}", invalid);
+        }
+    }
+}
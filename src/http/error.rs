@@ -1,6 +1,6 @@
 use std::{fmt::Display, str::Utf8Error};
 
-use super::method::MethodError;
+use super::{method::MethodError, StatusCode};
 
 #[derive(Debug)]
 pub struct ParseRequestError {
@@ -9,23 +9,73 @@ pub struct ParseRequestError {
 
 #[derive(Debug, PartialEq)]
 pub enum ParseRequestErrorKind {
+    /// The request wasn't received within the deadline of the whole request-response exchange
+    DeadlineExceeded,
     EncodingError,
+    HeadersTooLarge,
     InvalidMethod,
     InvalidProtocol,
     InvalidRequest,
+    /// The request target is not an absolute path of visible ASCII characters
+    InvalidTarget,
     NetworkError,
+    PayloadTooLarge,
+    RequestLineTooLong,
+    Timeout,
+    TooManyHeaders,
+    /// The method is a well-formed token, but not one the server implements
+    UnknownMethod,
+    UnsupportedContentEncoding,
+    /// A transfer coding other than `chunked`, which is the only one the server decodes
+    UnsupportedTransferEncoding,
+    /// The request line is too long because of its target
+    UriTooLong,
 }
 
 impl ParseRequestError {
     pub const fn message(&self) -> &'static str {
         match self.kind {
             ParseRequestErrorKind::InvalidRequest => "Invalid Request",
+            ParseRequestErrorKind::DeadlineExceeded => "Request Deadline Exceeded",
             ParseRequestErrorKind::EncodingError => "Invalid Request Encoding",
+            ParseRequestErrorKind::HeadersTooLarge => "Request Header Fields Too Large",
             ParseRequestErrorKind::InvalidMethod => "Invalid Request Method",
             ParseRequestErrorKind::InvalidProtocol => "Invalid Request Protocol",
+            ParseRequestErrorKind::InvalidTarget => "Invalid Request Target",
             ParseRequestErrorKind::NetworkError => "Network I/O Error",
+            ParseRequestErrorKind::PayloadTooLarge => "Request Body Too Large",
+            ParseRequestErrorKind::RequestLineTooLong => "Request Line Too Long",
+            ParseRequestErrorKind::Timeout => "Request Timeout",
+            ParseRequestErrorKind::TooManyHeaders => "Too Many Request Headers",
+            ParseRequestErrorKind::UnknownMethod => "Unknown Request Method",
+            ParseRequestErrorKind::UnsupportedContentEncoding => "Unsupported Content-Encoding",
+            ParseRequestErrorKind::UnsupportedTransferEncoding => "Unsupported Transfer-Encoding",
+            ParseRequestErrorKind::UriTooLong => "URI Too Long",
         }
     }
+
+    /// # Status code of the response to a request that failed with this error.
+    ///
+    /// Returns `None` if the connection failed or timed out, when no response is sent.
+    ///
+    /// An unknown method is `501 Not Implemented`, as RFC 9110 asks for methods the server
+    /// doesn't recognize, and so is a transfer coding the server can't decode (RFC 9112). A
+    /// method that isn't even a valid token is a malformed request line, so it's
+    /// `400 Bad Request` like the other syntax errors.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        let status_code = match self.kind {
+            ParseRequestErrorKind::NetworkError | ParseRequestErrorKind::Timeout => return None,
+            ParseRequestErrorKind::PayloadTooLarge => StatusCode::PayloadTooLarge,
+            ParseRequestErrorKind::UnsupportedContentEncoding => StatusCode::UnsupportedMediaType,
+            ParseRequestErrorKind::UriTooLong => StatusCode::UriTooLong,
+            ParseRequestErrorKind::UnknownMethod
+            | ParseRequestErrorKind::UnsupportedTransferEncoding => StatusCode::NotImplemented,
+            ParseRequestErrorKind::DeadlineExceeded => StatusCode::GatewayTimeout,
+            _ => StatusCode::BadRequest,
+        };
+
+        Some(status_code)
+    }
 }
 
 impl From<Utf8Error> for ParseRequestError {
@@ -57,3 +107,78 @@ impl Display for ParseRequestError {
         write!(f, "Request parsing error: {}", self.message())
     }
 }
+
+/// # A conflict between the parts set on a response builder.
+///
+/// Returned by [`ResponseBuilder::try_build`](super::ResponseBuilder::try_build).
+/// [`ResponseBuilder::build`](super::ResponseBuilder::build) doesn't check for these conflicts.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BuildError {
+    /// A body is set on a status code that must not have one (`1xx`, `204 No Content`)
+    BodyNotAllowed(StatusCode),
+    /// A chunked body is requested for a status code that must not have a body
+    ChunkedNotAllowed(StatusCode),
+    /// A `Transfer-Encoding` header is set manually while the body length is sent as
+    /// `Content-Length`, so the response would carry two conflicting framings
+    ConflictingFraming,
+    /// `Content-Encoding` is set to an encoding the builder can't apply to the body
+    UnsupportedContentEncoding(String),
+}
+
+impl BuildError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            BuildError::BodyNotAllowed(_) => "Body Not Allowed For Status Code",
+            BuildError::ChunkedNotAllowed(_) => "Chunked Body Not Allowed For Status Code",
+            BuildError::ConflictingFraming => "Both Transfer-Encoding And Content-Length Set",
+            BuildError::UnsupportedContentEncoding(_) => "Unsupported Content-Encoding",
+        }
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::BodyNotAllowed(status_code)
+            | BuildError::ChunkedNotAllowed(status_code) => {
+                write!(
+                    f,
+                    "Response building error: {} ({})",
+                    self.message(),
+                    *status_code as u16
+                )
+            }
+            BuildError::UnsupportedContentEncoding(encoding) => {
+                write!(
+                    f,
+                    "Response building error: {} ({})",
+                    self.message(),
+                    encoding
+                )
+            }
+            BuildError::ConflictingFraming => {
+                write!(f, "Response building error: {}", self.message())
+            }
+        }
+    }
+}
+
+/// # Writing a response failed partway.
+///
+/// `written` bytes of the response reached the stream before the failure, so the client got a
+/// truncated response and the connection can't be used anymore.
+#[derive(Debug)]
+pub struct WriteError {
+    pub written: usize,
+    pub source: std::io::Error,
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Response writing error after {} bytes: {}",
+            self.written, self.source
+        )
+    }
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+/// # Safety limits applied while reading and parsing requests.
+///
+/// All limits are enforced in one place so the server can be hardened against oversized or
+/// slow requests by tuning a single value. The defaults are conservative and fit the requests
+/// of typical clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum length of the request line in bytes, without the trailing CRLF
+    pub max_request_line: usize,
+    /// Maximum number of headers
+    pub max_header_count: usize,
+    /// Maximum size of the request line and headers in bytes, including the terminating empty
+    /// line
+    pub max_header_bytes: usize,
+    /// Maximum size of the request body in bytes
+    pub max_body_size: usize,
+    /// Maximum time to receive a whole request once its first byte has arrived
+    pub read_timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_request_line: 8 * 1024,
+            max_header_count: 100,
+            max_header_bytes: 64 * 1024,
+            max_body_size: 16 * 1024 * 1024,
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
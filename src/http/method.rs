@@ -16,6 +16,21 @@ pub enum Method {
     Trace,
 }
 
+impl Method {
+    /// All methods, in the order they are listed in `Allow` headers
+    pub const ALL: [Method; 9] = [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Patch,
+        Method::Options,
+        Method::Trace,
+        Method::Connect,
+    ];
+}
+
 impl FromStr for Method {
     type Err = MethodError;
 
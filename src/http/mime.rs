@@ -0,0 +1,120 @@
+/// # Look up the MIME type for a file extension.
+///
+/// The lookup is case-insensitive and the extension is passed without the leading dot.
+/// Returns `None` for unknown extensions.
+pub fn mime_from_extension(extension: &str) -> Option<&'static str> {
+    let mime = match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "application/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        _ => return None,
+    };
+
+    Some(mime)
+}
+
+/// # Detect the MIME type of a file from its first bytes.
+///
+/// Recognizes PNG, JPEG and GIF magic numbers, HTML documents starting with `<!DOCTYPE` or
+/// `<html` (case-insensitive, after leading whitespace) and text starting with a UTF-8 BOM.
+/// Returns `None` if the content is not recognized.
+pub fn sniff_mime(content: &[u8]) -> Option<&'static str> {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const JPEG: &[u8] = b"\xff\xd8\xff";
+    const GIF87: &[u8] = b"GIF87a";
+    const GIF89: &[u8] = b"GIF89a";
+    const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+    if content.starts_with(PNG) {
+        return Some("image/png");
+    }
+    if content.starts_with(JPEG) {
+        return Some("image/jpeg");
+    }
+    if content.starts_with(GIF87) || content.starts_with(GIF89) {
+        return Some("image/gif");
+    }
+
+    // Skip leading whitespace before looking for HTML markers
+    let start = content
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(content.len());
+    let text = &content[start..];
+
+    let starts_with_ignore_case = |prefix: &[u8]| {
+        text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+
+    if starts_with_ignore_case(b"<!doctype html") || starts_with_ignore_case(b"<html") {
+        return Some("text/html");
+    }
+    if content.starts_with(UTF8_BOM) {
+        return Some("text/plain");
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_from_extension() {
+        assert_eq!(mime_from_extension("html"), Some("text/html"), "HTML");
+        assert_eq!(
+            mime_from_extension("PNG"),
+            Some("image/png"),
+            "Uppercase PNG"
+        );
+        assert_eq!(
+            mime_from_extension("json"),
+            Some("application/json"),
+            "JSON"
+        );
+        assert_eq!(mime_from_extension("unknown"), None, "Unknown extension");
+    }
+
+    #[test]
+    fn test_sniff_mime() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        assert_eq!(sniff_mime(png), Some("image/png"), "PNG magic number");
+
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x10JFIF";
+        assert_eq!(sniff_mime(jpeg), Some("image/jpeg"), "JPEG magic number");
+
+        assert_eq!(
+            sniff_mime(b"GIF89a\x01\x00"),
+            Some("image/gif"),
+            "GIF magic number"
+        );
+
+        let html = b"\n  <!DOCTYPE html>\n<html><body>Hello</body></html>";
+        assert_eq!(sniff_mime(html), Some("text/html"), "HTML doctype");
+        assert_eq!(sniff_mime(b"<HTML>"), Some("text/html"), "HTML tag");
+
+        assert_eq!(
+            sniff_mime(b"\xef\xbb\xbfHello"),
+            Some("text/plain"),
+            "UTF-8 BOM"
+        );
+
+        assert_eq!(sniff_mime(b"\x00\x01\x02"), None, "Unknown content");
+        assert_eq!(sniff_mime(b""), None, "Empty content");
+    }
+}
@@ -1,12 +1,24 @@
-pub use self::error::{ParseRequestError, ParseRequestErrorKind};
+pub use self::buffer::{BufferGrowth, BufferStrategy};
+pub use self::date::{format_http_date, parse_http_date};
+pub use self::error::{BuildError, ParseRequestError, ParseRequestErrorKind, WriteError};
+pub use self::limits::Limits;
 pub use self::method::Method;
-pub use self::request::Request;
+pub use self::mime::{mime_from_extension, sniff_mime};
+pub use self::percent::{percent_decode, percent_encode_segment, DecodePolicy, PercentDecodeError};
+pub use self::reader::{BodyStream, RequestReader};
+pub use self::request::{Request, Version};
 pub use self::response::Response;
 pub use self::response_builder::ResponseBuilder;
 pub use self::status_code::StatusCode;
 
+mod buffer;
+mod date;
 mod error;
+mod limits;
 mod method;
+mod mime;
+mod percent;
+mod reader;
 mod request;
 mod response;
 mod response_builder;
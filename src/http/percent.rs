@@ -0,0 +1,171 @@
+use std::{fmt::Display, str::FromStr};
+
+/// # How to handle malformed percent-encoding.
+///
+/// A sequence is malformed when `%` is not followed by two hex digits (`%ZZ`, a trailing `%`)
+/// or when the decoded bytes are not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodePolicy {
+    /// Reject the input with an error
+    #[default]
+    Strict,
+    /// Keep malformed sequences as they are and replace invalid UTF-8 with `U+FFFD`
+    Lenient,
+}
+
+impl FromStr for DecodePolicy {
+    type Err = PercentDecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            _ => Err(PercentDecodeError),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PercentDecodeError;
+
+impl Display for PercentDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Malformed percent-encoding")
+    }
+}
+
+/// # Decode a percent-encoded string.
+///
+/// `+` is not treated as a space, since it only has that meaning in form-encoded bodies.
+pub fn percent_decode(input: &str, policy: DecodePolicy) -> Result<String, PercentDecodeError> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (hex, policy) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (None, DecodePolicy::Strict) => return Err(PercentDecodeError),
+            (None, DecodePolicy::Lenient) => {
+                // Keep the `%` as is and continue with the next character
+                decoded.push(b'%');
+                i += 1;
+            }
+        }
+    }
+
+    match policy {
+        DecodePolicy::Strict => String::from_utf8(decoded).map_err(|_| PercentDecodeError),
+        DecodePolicy::Lenient => Ok(String::from_utf8_lossy(&decoded).into_owned()),
+    }
+}
+
+/// # Percent-encode a path segment.
+///
+/// Every byte but the unreserved characters of RFC 3986 (letters, digits, `-`, `.`, `_` and
+/// `~`) is encoded, including `/`, so the result is always a single segment.
+pub fn percent_encode_segment(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        for policy in [DecodePolicy::Strict, DecodePolicy::Lenient] {
+            assert_eq!(
+                percent_decode("Hello%20World", policy),
+                Ok("Hello World".to_string()),
+                "Space"
+            );
+            assert_eq!(
+                percent_decode("%E2%9C%93%2f", policy),
+                Ok("✓/".to_string()),
+                "Multi-byte character and lowercase hex"
+            );
+            assert_eq!(
+                percent_decode("a+b", policy),
+                Ok("a+b".to_string()),
+                "Plus sign"
+            );
+        }
+    }
+
+    #[test]
+    fn test_percent_encode_segment() {
+        assert_eq!(percent_encode_segment("a-b_c.~1"), "a-b_c.~1", "Unreserved");
+        assert_eq!(
+            percent_encode_segment("a b#c?d%e/f"),
+            "a%20b%23c%3Fd%25e%2Ff",
+            "Reserved characters and spaces"
+        );
+        assert_eq!(percent_encode_segment("é"), "%C3%A9", "UTF-8 bytes");
+        assert_eq!(
+            percent_decode(&percent_encode_segment("a#b ü%"), DecodePolicy::Strict),
+            Ok("a#b ü%".to_string()),
+            "Round trip"
+        );
+    }
+
+    #[test]
+    fn test_percent_decode_malformed() {
+        assert_eq!(
+            percent_decode("/files/%ZZ", DecodePolicy::Strict),
+            Err(PercentDecodeError),
+            "Invalid hex digits"
+        );
+        assert_eq!(
+            percent_decode("abc%", DecodePolicy::Strict),
+            Err(PercentDecodeError),
+            "Trailing percent"
+        );
+        assert_eq!(
+            percent_decode("%FF", DecodePolicy::Strict),
+            Err(PercentDecodeError),
+            "Invalid UTF-8"
+        );
+
+        assert_eq!(
+            percent_decode("/files/%ZZ", DecodePolicy::Lenient),
+            Ok("/files/%ZZ".to_string()),
+            "Invalid hex digits are kept"
+        );
+        assert_eq!(
+            percent_decode("abc%2", DecodePolicy::Lenient),
+            Ok("abc%2".to_string()),
+            "Trailing percent is kept"
+        );
+        assert_eq!(
+            percent_decode("%FF", DecodePolicy::Lenient),
+            Ok("\u{FFFD}".to_string()),
+            "Invalid UTF-8 is replaced"
+        );
+    }
+}
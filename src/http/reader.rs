@@ -0,0 +1,1607 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, ReadBuf},
+    time::Instant,
+};
+
+use super::{
+    request::{find_head_end, request_line_too_long},
+    BufferStrategy, Limits, Method, ParseRequestError, ParseRequestErrorKind, Request,
+};
+
+/// # Reads consecutive requests from a connection.
+///
+/// The reader buffers everything it receives. A request ends after the empty line terminating
+/// the headers plus its body: the last chunk of a `Transfer-Encoding: chunked` body, or exactly
+/// `Content-Length` bytes (no body if neither header is set), whatever the method. Any bytes
+/// past that point stay buffered for the next request. This keeps pipelined requests intact on
+/// keep-alive connections.
+///
+/// Every request is checked against the configured [`Limits`] while it's being received, so an
+/// oversized request is rejected before it's buffered as a whole and a slow one doesn't hold
+/// the connection forever.
+#[derive(Debug)]
+pub struct RequestReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    limits: Limits,
+    allow_http09: bool,
+    keep_raw: bool,
+    eof_body: bool,
+    reached_eof: bool,
+    buffer: BufferStrategy,
+    /// Longest wait for the first byte of a request, unlimited if `None`
+    idle_timeout: Option<Duration>,
+    /// Body of the request whose head was read last, until it's read
+    pending: Option<PendingBody>,
+}
+
+/// Body of a request whose head was read but the body not yet
+#[derive(Debug)]
+struct PendingBody {
+    /// Bytes of the body left to read, `None` if it ends with the connection. Unused for a
+    /// chunked body.
+    remaining: Option<usize>,
+    /// Decoder of a `Transfer-Encoding: chunked` body
+    chunked: Option<ChunkedDecoder>,
+    /// Bytes of the body streamed so far
+    read: usize,
+    /// Deadline of the request the body belongs to
+    deadline: Option<Instant>,
+    /// Head of the request, when raw requests are kept
+    raw_head: Option<Vec<u8>>,
+}
+
+/// # Body of a request, read as a stream.
+///
+/// Yields the part of the body that was buffered together with the head first, then reads the
+/// rest from the connection. The stream ends with the body. A connection closed before the end
+/// of a `Content-Length` body is an `UnexpectedEof` error and a body delimited by the end of the
+/// connection longer than `max_body_size` an `InvalidData` error.
+#[derive(Debug)]
+pub struct BodyStream<'r, R> {
+    reader: &'r mut RequestReader<R>,
+}
+
+impl<R: AsyncRead + Unpin> RequestReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(BufferStrategy::default().initial_size),
+            limits: Limits::default(),
+            allow_http09: false,
+            keep_raw: false,
+            eof_body: false,
+            reached_eof: false,
+            buffer: BufferStrategy::default(),
+            idle_timeout: None,
+            pending: None,
+        }
+    }
+
+    /// # Set how the read buffer is sized.
+    ///
+    /// Resets the buffer to the initial size of the strategy, so call it before reading.
+    pub fn with_buffer(mut self, buffer: BufferStrategy) -> Self {
+        self.buf = Vec::with_capacity(buffer.initial_size);
+        self.buffer = buffer;
+        self
+    }
+
+    /// # Accept HTTP/0.9 simple requests.
+    ///
+    /// A simple request is a single `GET /path` line without version and headers. When not
+    /// allowed (the default), such a line is treated as an incomplete HTTP/1.1 request.
+    pub fn with_http09(mut self, allow_http09: bool) -> Self {
+        self.allow_http09 = allow_http09;
+        self
+    }
+
+    /// # Keep the received bytes of every request.
+    ///
+    /// The bytes are available with [`Request::raw`]. Disabled by default, as it copies every
+    /// request a second time.
+    pub fn with_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// # Read the body of unframed requests until the client closes the connection.
+    ///
+    /// Applies to `POST`, `PUT` and `PATCH` requests with neither `Content-Length` nor
+    /// `Transfer-Encoding`, which have no body when disabled (the default). The client must
+    /// close (or half-close) the connection after the body; a client waiting for the response
+    /// instead is stopped by the read timeout. The body is still limited to `max_body_size`.
+    pub fn with_eof_body(mut self, eof_body: bool) -> Self {
+        self.eof_body = eof_body;
+        self
+    }
+
+    /// # Stop waiting for a next request after the connection was idle for this long.
+    ///
+    /// Only the wait for the first byte of a request is limited; from there on, the read timeout
+    /// of the limits applies. An idle connection ends like a closed one, with `Ok(None)`.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set the limits requests are checked against
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// # Read the next request from the connection.
+    ///
+    /// Returns `Ok(None)` if the connection was closed before any byte of a new request was
+    /// received. A connection closed in the middle of a request is an `InvalidRequest` error.
+    ///
+    /// Waiting for the first byte of a request is only limited by the idle timeout, if set (see
+    /// [`with_idle_timeout`](RequestReader::with_idle_timeout)); once it has arrived, the whole
+    /// request must be received within the read timeout.
+    pub async fn read_request(&mut self) -> Result<Option<Request>, ParseRequestError> {
+        let Some(mut request) = self.read_head().await? else {
+            return Ok(None);
+        };
+
+        self.read_body(&mut request).await?;
+
+        Ok(Some(request))
+    }
+
+    /// # Read the request line and headers of the next request, leaving its body unread.
+    ///
+    /// The body is then either buffered into the request with
+    /// [`read_body`](RequestReader::read_body) or read as a stream with
+    /// [`body_stream`](RequestReader::body_stream). Whatever is left of it when the next head is
+    /// read is skipped. See [`read_request`](RequestReader::read_request) for the errors.
+    pub async fn read_head(&mut self) -> Result<Option<Request>, ParseRequestError> {
+        self.skip_body().await?;
+
+        let mut deadline = self.start_deadline();
+        let idle_deadline = self.idle_timeout.map(|timeout| Instant::now() + timeout);
+
+        // Read until the end of the headers
+        let mut searched = 0;
+        let head_len = loop {
+            if let Some(request) = self.take_simple_request()? {
+                return Ok(Some(request));
+            }
+
+            if let Some(pos) = find_head_end(&self.buf[searched..]) {
+                break searched + pos;
+            }
+
+            self.check_head_limits()?;
+
+            // The terminator may start in the last bytes that were already searched
+            searched = self.buf.len().saturating_sub(3);
+
+            let read = match deadline {
+                Some(_) => self.fill_buf(deadline).await?,
+                // Nothing of a request received yet, so the connection is merely idle
+                None => match self.fill_buf(idle_deadline).await {
+                    Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::Timeout,
+                    }) => return Ok(None),
+                    read => read?,
+                },
+            };
+
+            if read == 0 {
+                return match self.buf.is_empty() {
+                    true => Ok(None),
+                    false => Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::InvalidRequest,
+                    }),
+                };
+            }
+
+            deadline = deadline.or_else(|| self.start_deadline());
+        };
+
+        if head_len > self.limits.max_header_bytes {
+            return Err(ParseRequestError {
+                kind: ParseRequestErrorKind::HeadersTooLarge,
+            });
+        }
+
+        let request = Request::parse(std::str::from_utf8(&self.buf[..head_len])?, &self.limits)?;
+
+        let chunked = match request.header("Transfer-Encoding") {
+            None => false,
+            // A request framed by both headers is read differently by different servers, which is
+            // what request smuggling relies on, so it's rejected rather than guessed (RFC 9112)
+            Some(_) if request.header("Content-Length").is_some() => {
+                return Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::InvalidRequest,
+                })
+            }
+            Some(codings) if is_chunked(codings) => true,
+            // Any other coding would reach the handler still encoded
+            Some(_) => {
+                return Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::UnsupportedTransferEncoding,
+                })
+            }
+        };
+
+        let remaining = match chunked || self.is_eof_delimited(&request) {
+            true => None,
+            false => Some(request.content_length(&self.limits)?),
+        };
+
+        self.pending = Some(PendingBody {
+            remaining,
+            chunked: chunked.then(ChunkedDecoder::default),
+            read: 0,
+            deadline,
+            raw_head: self.keep_raw.then(|| self.buf[..head_len].to_vec()),
+        });
+
+        self.buf.drain(..head_len);
+
+        Ok(Some(request))
+    }
+
+    /// # Buffer the body of the request whose head was read last.
+    ///
+    /// The body must be received within the read timeout of the request. Does nothing if the
+    /// body was already read.
+    pub async fn read_body(&mut self, request: &mut Request) -> Result<(), ParseRequestError> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(());
+        };
+
+        if let Some(mut decoder) = pending.chunked {
+            let mut raw = pending.raw_head;
+
+            loop {
+                let consumed =
+                    decoder.decode(&self.buf, &mut request.body, usize::MAX, &self.limits)?;
+
+                match raw.as_mut() {
+                    Some(raw) => raw.extend(self.buf.drain(..consumed)),
+                    None => drop(self.buf.drain(..consumed)),
+                }
+
+                if decoder.is_done() {
+                    break;
+                }
+
+                if self.fill_buf(pending.deadline).await? == 0 {
+                    return Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::InvalidRequest,
+                    });
+                }
+            }
+
+            if let Some(raw) = raw {
+                request.set_raw(raw);
+            }
+
+            return Ok(());
+        }
+
+        let len = match pending.remaining {
+            Some(len) => {
+                // Read until the whole body is buffered
+                while self.buf.len() < len {
+                    if self.fill_buf(pending.deadline).await? == 0 {
+                        return Err(ParseRequestError {
+                            kind: ParseRequestErrorKind::InvalidRequest,
+                        });
+                    }
+                }
+                len
+            }
+            None => self.read_to_eof(pending.deadline).await?,
+        };
+
+        // Keep the bytes of the next request buffered
+        request.body = self.buf.drain(..len).collect();
+
+        if let Some(mut raw) = pending.raw_head {
+            raw.extend_from_slice(&request.body);
+            request.set_raw(raw);
+        }
+
+        Ok(())
+    }
+
+    /// # Read the body of the request whose head was read last as a stream.
+    ///
+    /// The stream ends with the body, so the next request stays intact. Unlike
+    /// [`read_body`](RequestReader::read_body), reading the stream is not limited by the read
+    /// timeout; the reader of the stream decides how long to wait.
+    pub fn body_stream(&mut self) -> BodyStream<'_, R> {
+        BodyStream { reader: self }
+    }
+
+    /// # Wait until the client closes the connection.
+    ///
+    /// Meant to be raced against the handling of a request, to stop working on a response
+    /// nobody is waiting for. Bytes received in the meantime (e.g. pipelined requests) stay
+    /// buffered for the next [`read_request`](RequestReader::read_request). Once more than
+    /// `max_header_bytes` are buffered, nothing more is read and this never returns.
+    pub async fn closed(&mut self) {
+        loop {
+            if self.buf.len() >= self.limits.max_header_bytes {
+                std::future::pending::<()>().await;
+            }
+
+            match self.fill_buf(None).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => (),
+            }
+        }
+    }
+
+    /// # Check whether the client closed the connection, without waiting.
+    ///
+    /// Like [`closed`](RequestReader::closed), but only reads what was already received. An
+    /// end of stream that arrived together with a request means the client half-closed the
+    /// connection after sending it, rather than going away while waiting for the response.
+    pub async fn closed_now(&mut self) -> bool {
+        let closed = tokio::time::timeout(Duration::ZERO, self.closed()).await;
+
+        closed.is_ok()
+    }
+
+    /// # When the request whose head was read last started arriving.
+    ///
+    /// For a request that was already buffered, e.g. pipelined, this is when the reader got to
+    /// it. Returns `None` once its body was read, and for HTTP/0.9 requests.
+    pub fn request_started(&self) -> Option<Instant> {
+        let deadline = self.pending.as_ref()?.deadline?;
+
+        Some(deadline - self.limits.read_timeout)
+    }
+
+    /// Whether the client closed (or half-closed) its side of the connection
+    pub fn reached_eof(&self) -> bool {
+        self.reached_eof
+    }
+
+    /// Bytes received but not consumed by a request yet
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Get the underlying reader back together with the bytes buffered but not consumed yet
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        (self.reader, self.buf)
+    }
+
+    /// Whether the body of the request ends when the client closes the connection
+    fn is_eof_delimited(&self, request: &Request) -> bool {
+        self.eof_body
+            && matches!(request.method, Method::Post | Method::Put | Method::Patch)
+            && request.header("Content-Length").is_none()
+            && request.header("Transfer-Encoding").is_none()
+    }
+
+    /// Buffer everything until the end of the stream, returning the length of the body
+    async fn read_to_eof(&mut self, deadline: Option<Instant>) -> Result<usize, ParseRequestError> {
+        loop {
+            if self.buf.len() > self.limits.max_body_size {
+                return Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::PayloadTooLarge,
+                });
+            }
+
+            if self.fill_buf(deadline).await? == 0 {
+                return Ok(self.buf.len());
+            }
+        }
+    }
+
+    /// Discard whatever is left of the body of the request whose head was read last
+    async fn skip_body(&mut self) -> Result<(), ParseRequestError> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(());
+        };
+
+        // The handler of the request may have taken a while, so give the rest a fresh timeout
+        let deadline = Some(Instant::now() + self.limits.read_timeout);
+
+        if let Some(mut decoder) = pending.chunked {
+            let mut skipped = Vec::new();
+
+            loop {
+                skipped.clear();
+                let consumed = decoder.decode(&self.buf, &mut skipped, usize::MAX, &self.limits)?;
+                self.buf.drain(..consumed);
+
+                if decoder.is_done() {
+                    return Ok(());
+                }
+
+                if self.fill_buf(deadline).await? == 0 {
+                    return Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::InvalidRequest,
+                    });
+                }
+            }
+        }
+
+        let mut remaining = pending.remaining;
+
+        loop {
+            let buffered = remaining.map_or(self.buf.len(), |len| len.min(self.buf.len()));
+            self.buf.drain(..buffered);
+            remaining = remaining.map(|len| len - buffered);
+
+            if remaining == Some(0) {
+                return Ok(());
+            }
+
+            if self.fill_buf(deadline).await? == 0 {
+                return match remaining {
+                    None => Ok(()),
+                    Some(_) => Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::InvalidRequest,
+                    }),
+                };
+            }
+        }
+    }
+
+    /// Consume an HTTP/0.9 simple request if it's allowed and the buffered request line is one
+    fn take_simple_request(&mut self) -> Result<Option<Request>, ParseRequestError> {
+        if !self.allow_http09 {
+            return Ok(None);
+        }
+
+        let Some(line_end) = find_line_end(&self.buf) else {
+            return Ok(None);
+        };
+
+        let mut request =
+            Request::parse_simple_request(std::str::from_utf8(&self.buf[..line_end])?);
+
+        if let Some(request) = request.as_mut() {
+            let raw: Vec<u8> = self.buf.drain(..line_end + 2).collect();
+            if self.keep_raw {
+                request.set_raw(raw);
+            }
+        }
+
+        Ok(request)
+    }
+
+    /// The deadline of the current request, if it has started arriving
+    fn start_deadline(&self) -> Option<Instant> {
+        match self.buf.is_empty() {
+            true => None,
+            false => Some(Instant::now() + self.limits.read_timeout),
+        }
+    }
+
+    /// Check the incomplete request head against the limits
+    fn check_head_limits(&self) -> Result<(), ParseRequestError> {
+        // Without a line break yet, everything buffered belongs to the request line
+        let request_line_len = find_line_end(&self.buf).unwrap_or(self.buf.len());
+
+        if request_line_len > self.limits.max_request_line {
+            return Err(request_line_too_long(&self.buf[..request_line_len]));
+        }
+
+        if self.buf.len() >= self.limits.max_header_bytes {
+            return Err(ParseRequestError {
+                kind: ParseRequestErrorKind::HeadersTooLarge,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn fill_buf(&mut self, deadline: Option<Instant>) -> Result<usize, ParseRequestError> {
+        self.buffer.reserve(&mut self.buf);
+
+        // Reads into the spare capacity of the buffer
+        let read = match deadline {
+            Some(deadline) => {
+                tokio::time::timeout_at(deadline, read_retrying(&mut self.reader, &mut self.buf))
+                    .await
+                    .map_err(|_| ParseRequestError {
+                        kind: ParseRequestErrorKind::Timeout,
+                    })??
+            }
+            None => read_retrying(&mut self.reader, &mut self.buf).await?,
+        };
+
+        // The buffer always has spare capacity, so nothing read means the end of the stream
+        if read == 0 {
+            self.reached_eof = true;
+        }
+
+        Ok(read)
+    }
+}
+
+/// Consecutive transient errors a read is retried after before giving up
+const MAX_READ_RETRIES: usize = 8;
+
+/// # Read into the spare capacity of the buffer, retrying transient errors.
+///
+/// `Interrupted` (`EINTR`) and `WouldBlock` mean nothing was read this time rather than that
+/// the connection broke, so the read is retried after yielding to other tasks. Any other error,
+/// or more than [`MAX_READ_RETRIES`] transient errors in a row, is returned.
+async fn read_retrying<R>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut retries = 0;
+
+    loop {
+        match reader.read_buf(buf).await {
+            Err(err) if is_transient(&err) && retries < MAX_READ_RETRIES => {
+                retries += 1;
+                tokio::task::yield_now().await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for BodyStream<'_, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let reader = &mut *self.get_mut().reader;
+        let max_body_size = reader.limits.max_body_size;
+
+        let Some(pending) = reader.pending.as_mut() else {
+            return Poll::Ready(Ok(()));
+        };
+
+        if let Some(decoder) = pending.chunked.as_mut() {
+            loop {
+                if decoder.is_done() || out.remaining() == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+
+                let mut decoded = Vec::new();
+                let consumed = decoder
+                    .decode(&reader.buf, &mut decoded, out.remaining(), &reader.limits)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.message()))?;
+                reader.buf.drain(..consumed);
+
+                if !decoded.is_empty() || decoder.is_done() {
+                    out.put_slice(&decoded);
+                    return Poll::Ready(Ok(()));
+                }
+
+                // The buffered bytes end in the middle of the chunk framing
+                let mut chunk = [0; 8 * 1024];
+                let mut chunk = ReadBuf::new(&mut chunk);
+                ready!(Pin::new(&mut reader.reader).poll_read(cx, &mut chunk))?;
+
+                if chunk.filled().is_empty() {
+                    reader.reached_eof = true;
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                }
+
+                reader.buf.extend_from_slice(chunk.filled());
+            }
+        }
+
+        let limit = pending
+            .remaining
+            .map_or(out.remaining(), |len| len.min(out.remaining()));
+        if limit == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let read = match reader.buf.is_empty() {
+            // Bytes received together with the head come first
+            false => {
+                let read = limit.min(reader.buf.len());
+                out.put_slice(&reader.buf[..read]);
+                reader.buf.drain(..read);
+                read
+            }
+            true => {
+                let mut chunk = [0; 8 * 1024];
+                let mut chunk = ReadBuf::new(&mut chunk[..limit.min(8 * 1024)]);
+                ready!(Pin::new(&mut reader.reader).poll_read(cx, &mut chunk))?;
+                out.put_slice(chunk.filled());
+                chunk.filled().len()
+            }
+        };
+
+        if read == 0 {
+            reader.reached_eof = true;
+
+            return match pending.remaining {
+                Some(_) => Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                None => {
+                    pending.remaining = Some(0);
+                    Poll::Ready(Ok(()))
+                }
+            };
+        }
+
+        pending.remaining = pending.remaining.map(|len| len - read);
+        pending.read += read;
+
+        if pending.remaining.is_none() && pending.read > max_body_size {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request body too large",
+            )));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Find the position of the first CRLF
+fn find_line_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|window| window == b"\r\n")
+}
+
+/// Whether `chunked` is the only transfer coding, the one framing the body
+fn is_chunked(codings: &str) -> bool {
+    codings.trim().eq_ignore_ascii_case("chunked")
+}
+
+/// Longest chunk size line (size and chunk extensions) accepted
+const MAX_CHUNK_SIZE_LINE: usize = 1024;
+
+/// Part of a chunked body the decoder expects next
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum ChunkState {
+    #[default]
+    Size,
+    /// Data of a chunk, with the number of bytes left
+    Data(usize),
+    /// CRLF after the data of a chunk
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// # Decoder of a `Transfer-Encoding: chunked` body.
+///
+/// Works on whatever part of the body is buffered, so the body can be decoded while it's being
+/// received. Chunk extensions and trailer fields are discarded. The decoded body is limited to
+/// `max_body_size` and the trailer section to `max_header_bytes`.
+#[derive(Debug, Default)]
+struct ChunkedDecoder {
+    state: ChunkState,
+    /// Bytes of the body decoded so far
+    decoded: usize,
+    /// Bytes of the trailer section read so far
+    trailer_len: usize,
+}
+
+impl ChunkedDecoder {
+    /// Whether the last chunk and the trailer section were decoded
+    fn is_done(&self) -> bool {
+        self.state == ChunkState::Done
+    }
+
+    /// # Decode the chunks at the start of `input` into `out`.
+    ///
+    /// At most `max_out` bytes are decoded. Returns the number of bytes of `input` that were
+    /// consumed; the rest, an incomplete part of the framing or data past `max_out`, is for the
+    /// next call.
+    fn decode(
+        &mut self,
+        input: &[u8],
+        out: &mut Vec<u8>,
+        max_out: usize,
+        limits: &Limits,
+    ) -> Result<usize, ParseRequestError> {
+        let error = |kind| ParseRequestError { kind };
+        let mut pos = 0;
+        let mut written = 0;
+
+        loop {
+            let rest = &input[pos..];
+
+            match self.state {
+                ChunkState::Size => {
+                    let Some(line_end) = find_line_end(rest) else {
+                        return match rest.len() > MAX_CHUNK_SIZE_LINE {
+                            true => Err(error(ParseRequestErrorKind::InvalidRequest)),
+                            false => Ok(pos),
+                        };
+                    };
+
+                    let size = parse_chunk_size(&rest[..line_end])
+                        .ok_or(error(ParseRequestErrorKind::InvalidRequest))?;
+
+                    if self.decoded.saturating_add(size) > limits.max_body_size {
+                        return Err(error(ParseRequestErrorKind::PayloadTooLarge));
+                    }
+
+                    pos += line_end + 2;
+                    self.state = match size {
+                        0 => ChunkState::Trailers,
+                        size => ChunkState::Data(size),
+                    };
+                }
+                ChunkState::Data(left) => {
+                    let len = left.min(rest.len()).min(max_out - written);
+                    if len == 0 {
+                        return Ok(pos);
+                    }
+
+                    out.extend_from_slice(&rest[..len]);
+                    pos += len;
+                    written += len;
+                    self.decoded += len;
+                    self.state = match left - len {
+                        0 => ChunkState::DataEnd,
+                        left => ChunkState::Data(left),
+                    };
+                }
+                ChunkState::DataEnd => {
+                    if rest.len() < 2 {
+                        return Ok(pos);
+                    }
+
+                    if &rest[..2] != b"\r\n" {
+                        return Err(error(ParseRequestErrorKind::InvalidRequest));
+                    }
+
+                    pos += 2;
+                    self.state = ChunkState::Size;
+                }
+                ChunkState::Trailers => {
+                    let line_len = find_line_end(rest).map_or(rest.len(), |line_end| line_end + 2);
+
+                    if self.trailer_len + line_len > limits.max_header_bytes {
+                        return Err(error(ParseRequestErrorKind::HeadersTooLarge));
+                    }
+
+                    // The empty line ends the trailer section
+                    match find_line_end(rest) {
+                        None => return Ok(pos),
+                        Some(0) => self.state = ChunkState::Done,
+                        Some(_) => (),
+                    }
+
+                    pos += line_len;
+                    self.trailer_len += line_len;
+                }
+                ChunkState::Done => return Ok(pos),
+            }
+        }
+    }
+}
+
+/// Parse the hexadecimal size of a chunk from its size line, ignoring chunk extensions
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    let size = line.split(|&b| b == b';').next()?;
+    let size = std::str::from_utf8(size)
+        .ok()?
+        .trim_end_matches([' ', '\t']);
+
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    usize::from_str_radix(size, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{BufferGrowth, StatusCode};
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(
+            find_head_end(b"GET / HTTP/1.1\r\n\r\n"),
+            Some(18),
+            "Head only"
+        );
+        assert_eq!(
+            find_head_end(b"GET / HTTP/1.1\r\n\r\nbody"),
+            Some(18),
+            "Head followed by body"
+        );
+        assert_eq!(
+            find_head_end(b"GET / HTTP/1.1\r\n"),
+            None,
+            "Incomplete head"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_with_body() {
+        let data: &[u8] = b"POST /files/a HTTP/1.1\r\nContent-Length: 12\r\n\r\nHello\r\nWorld";
+        let mut reader = RequestReader::new(data);
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.method, Method::Post, "Method");
+        assert_eq!(request.body, b"Hello\r\nWorld", "Body with embedded CRLF");
+        assert!(reader.buffered().is_empty(), "Everything consumed");
+
+        assert!(
+            reader.read_request().await.unwrap().is_none(),
+            "Connection closed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_zero_content_length_pipelined() {
+        let data: &[u8] =
+            b"POST /a HTTP/1.1\r\nContent-Length: 0\r\n\r\nGET /b HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut reader = RequestReader::new(data);
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/a", "First request");
+        assert!(request.body.is_empty(), "Zero-length body");
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.method, Method::Get, "Second request method");
+        assert_eq!(request.uri, "/b", "Second request URI");
+        assert_eq!(request.headers.len(), 1, "Second request headers");
+
+        assert!(
+            reader.read_request().await.unwrap().is_none(),
+            "Connection closed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_leaves_next_request_buffered() {
+        let data: &[u8] =
+            b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = RequestReader::new(data);
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.body, b"abc", "Body stops at Content-Length");
+        assert_eq!(
+            reader.buffered(),
+            b"GET /b HTTP/1.1\r\n\r\n",
+            "Next request stays buffered"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_large_header_value() {
+        let token = "x".repeat(4 * 1024);
+        let data = format!(
+            "GET / HTTP/1.1\r\nAuthorization: Bearer {}\r\nHost: localhost\r\n\r\n",
+            token
+        );
+        let mut reader = RequestReader::new(data.as_bytes());
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.header("Authorization"),
+            Some(format!("Bearer {}", token).as_str()),
+            "4 KiB header value is not truncated"
+        );
+        assert_eq!(
+            request.header("Host"),
+            Some("localhost"),
+            "Following header"
+        );
+
+        // The same request is rejected when it doesn't fit the limit
+        let mut reader = RequestReader::new(data.as_bytes()).with_limits(Limits {
+            max_header_bytes: 1024,
+            ..Default::default()
+        });
+
+        let err = reader.read_request().await.unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseRequestErrorKind::HeadersTooLarge,
+            "Head over the limit"
+        );
+
+        // A head of exactly the limit is accepted
+        let mut reader = RequestReader::new(data.as_bytes()).with_limits(Limits {
+            max_header_bytes: data.len(),
+            ..Default::default()
+        });
+
+        assert!(
+            reader.read_request().await.unwrap().is_some(),
+            "Head at the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_incomplete() {
+        let data: &[u8] = b"POST /a HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc";
+        let mut reader = RequestReader::new(data);
+
+        let err = reader.read_request().await.unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Truncated body"
+        );
+
+        let data: &[u8] = b"GET / HTTP/1.1\r\nHost: local";
+        let mut reader = RequestReader::new(data);
+
+        let err = reader.read_request().await.unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Truncated head"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_simple_request() {
+        let data: &[u8] = b"GET /echo/abc\r\n";
+
+        let mut reader = RequestReader::new(data).with_http09(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.version,
+            crate::http::Version::Http09,
+            "HTTP/0.9 request"
+        );
+        assert_eq!(request.uri, "/echo/abc", "URI");
+
+        let mut reader = RequestReader::new(data);
+        assert_eq!(
+            reader.read_request().await.unwrap_err().kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "HTTP/0.9 is not allowed by default"
+        );
+
+        // HTTP/1.1 requests are still read as usual
+        let data: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut reader = RequestReader::new(data).with_http09(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.version,
+            crate::http::Version::Http11,
+            "HTTP/1.1 request"
+        );
+        assert_eq!(request.headers.len(), 1, "Headers");
+    }
+
+    /// Read a single request with the given limits
+    async fn read_with(data: &[u8], limits: Limits) -> Result<Option<Request>, ParseRequestError> {
+        RequestReader::new(data)
+            .with_limits(limits)
+            .read_request()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_limit_request_line() {
+        let data = b"GET /abcdef HTTP/1.1\r\n\r\n";
+        let request_line_len = "GET /abcdef HTTP/1.1".len();
+
+        let limits = Limits {
+            max_request_line: request_line_len,
+            ..Default::default()
+        };
+        assert!(
+            read_with(data, limits).await.is_ok(),
+            "Request line at the limit"
+        );
+
+        let limits = Limits {
+            max_request_line: request_line_len - 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(data, limits).await.unwrap_err().kind,
+            ParseRequestErrorKind::UriTooLong,
+            "Request line over the limit because of the target"
+        );
+
+        // An endless request line is rejected before the end of the head arrives
+        let data = format!("GET /{}", "a".repeat(64 * 1024));
+        assert_eq!(
+            read_with(data.as_bytes(), Limits::default())
+                .await
+                .unwrap_err()
+                .kind,
+            ParseRequestErrorKind::UriTooLong,
+            "Endless target"
+        );
+
+        let data = "A".repeat(64 * 1024);
+        assert_eq!(
+            read_with(data.as_bytes(), Limits::default())
+                .await
+                .unwrap_err()
+                .kind,
+            ParseRequestErrorKind::RequestLineTooLong,
+            "Endless token without a target"
+        );
+
+        let data = format!("GET / HTTP/{}\r\n\r\n", "1".repeat(64));
+        let limits = Limits {
+            max_request_line: 32,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(data.as_bytes(), limits).await.unwrap_err().kind,
+            ParseRequestErrorKind::RequestLineTooLong,
+            "Long protocol after a short target"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_header_count() {
+        let data = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+
+        let limits = Limits {
+            max_header_count: 3,
+            ..Default::default()
+        };
+        assert!(
+            read_with(data, limits).await.is_ok(),
+            "Header count at the limit"
+        );
+
+        let limits = Limits {
+            max_header_count: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(data, limits).await.unwrap_err().kind,
+            ParseRequestErrorKind::TooManyHeaders,
+            "Header count over the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_header_bytes() {
+        let data = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "x".repeat(2048));
+
+        let limits = Limits {
+            max_header_bytes: data.len(),
+            ..Default::default()
+        };
+        assert!(
+            read_with(data.as_bytes(), limits).await.is_ok(),
+            "Head at the limit"
+        );
+
+        let limits = Limits {
+            max_header_bytes: data.len() - 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(data.as_bytes(), limits).await.unwrap_err().kind,
+            ParseRequestErrorKind::HeadersTooLarge,
+            "Head over the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_body_size() {
+        let data = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+
+        let limits = Limits {
+            max_body_size: 5,
+            ..Default::default()
+        };
+        let request = read_with(data, limits).await.unwrap().unwrap();
+        assert_eq!(request.body, b"hello", "Body at the limit");
+
+        let limits = Limits {
+            max_body_size: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(data, limits).await.unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Body over the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limit_read_timeout() {
+        use tokio::io::AsyncWriteExt;
+
+        let limits = Limits {
+            read_timeout: std::time::Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        //======================================================================
+        // Test for a request received within the timeout
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+        let mut reader = RequestReader::new(server).with_limits(limits);
+        let (request, _) = tokio::join!(reader.read_request(), async {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            client.write_all(b"\r\n").await.unwrap();
+        });
+        assert!(request.unwrap().is_some(), "Request completed in time");
+
+        //======================================================================
+        // Test for a request that stalls after its first bytes
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+        let mut reader = RequestReader::new(server).with_limits(limits);
+        assert_eq!(
+            reader.read_request().await.unwrap_err().kind,
+            ParseRequestErrorKind::Timeout,
+            "Stalled request"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+
+        let limits = Limits {
+            read_timeout: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        //======================================================================
+        // Test for an idle connection
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut reader = RequestReader::new(server).with_idle_timeout(Duration::from_millis(20));
+        assert!(
+            reader.read_request().await.unwrap().is_none(),
+            "Idle connection ends without a request"
+        );
+
+        //======================================================================
+        // Test for a request taking longer than the idle timeout once started
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut reader = RequestReader::new(server)
+            .with_limits(limits)
+            .with_idle_timeout(Duration::from_millis(20));
+        let (request, _) = tokio::join!(reader.read_request(), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            client.write_all(b"\r\n").await.unwrap();
+        });
+        assert_eq!(
+            request.unwrap().unwrap().uri,
+            "/",
+            "Read timeout governs once the request started"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut reader = RequestReader::new(server);
+
+        client
+            .write_all(b"GET /next HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        drop(client);
+
+        reader.closed().await;
+        assert_eq!(
+            reader.buffered(),
+            b"GET /next HTTP/1.1\r\n\r\n",
+            "Bytes received while waiting stay buffered"
+        );
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/next", "Buffered request");
+    }
+
+    #[tokio::test]
+    async fn test_closed_now() {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut reader = RequestReader::new(server);
+
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/", "Request");
+        assert!(!reader.closed_now().await, "Client still connected");
+
+        client.write_all(b"GET /next").await.unwrap();
+        client.shutdown().await.unwrap();
+        assert!(reader.closed_now().await, "Client half-closed");
+        assert_eq!(
+            reader.buffered(),
+            b"GET /next",
+            "Received bytes stay buffered"
+        );
+    }
+
+    /// Reader failing with the given errors before reading the data
+    struct FlakyReader {
+        errors: Vec<io::ErrorKind>,
+        data: &'static [u8],
+    }
+
+    impl AsyncRead for FlakyReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            out: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if !self.errors.is_empty() {
+                return Poll::Ready(Err(self.errors.remove(0).into()));
+            }
+
+            let len = self.data.len().min(out.remaining());
+            out.put_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_request_transient_errors() {
+        //======================================================================
+        // Test for transient errors being retried
+        let mut reader = RequestReader::new(FlakyReader {
+            errors: vec![io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock],
+            data: b"GET /echo/abc HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        });
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/echo/abc", "Request read after the errors");
+        assert_eq!(request.header("Host"), Some("localhost"), "Headers");
+
+        //======================================================================
+        // Test for fatal errors and endless transient errors
+        let mut reader = RequestReader::new(FlakyReader {
+            errors: vec![io::ErrorKind::Interrupted, io::ErrorKind::ConnectionReset],
+            data: b"GET / HTTP/1.1\r\n\r\n",
+        });
+        assert!(
+            matches!(
+                reader.read_request().await,
+                Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::NetworkError
+                })
+            ),
+            "Connection reset"
+        );
+
+        let mut reader = RequestReader::new(FlakyReader {
+            errors: vec![io::ErrorKind::Interrupted; MAX_READ_RETRIES + 1],
+            data: b"GET / HTTP/1.1\r\n\r\n",
+        });
+        assert!(reader.read_request().await.is_err(), "Too many retries");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_small_buffer() {
+        let body = (0..10_000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        let mut data = format!(
+            "POST /files/a HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&body);
+        data.extend_from_slice(b"GET /next HTTP/1.1\r\n\r\n");
+
+        for growth in [BufferGrowth::Double, BufferGrowth::Fixed(7)] {
+            let mut reader = RequestReader::new(data.as_slice()).with_buffer(BufferStrategy {
+                initial_size: 16,
+                growth,
+            });
+
+            let request = reader.read_request().await.unwrap().unwrap();
+            assert_eq!(request.uri, "/files/a", "URI with {:?}", growth);
+            assert_eq!(request.header("Host"), Some("localhost"), "Header");
+            assert_eq!(request.body, body, "Body with {:?}", growth);
+
+            let request = reader.read_request().await.unwrap().unwrap();
+            assert_eq!(request.uri, "/next", "Next request with {:?}", growth);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_request_raw() {
+        let first =
+            "POST /echo-body HTTP/1.1\r\nHost:\tlocalhost\r\nContent-Length: 5\r\n\r\nHello";
+        let second = "GET / HTTP/1.1\r\n\r\n";
+        let data = format!("{}{}", first, second);
+
+        let mut reader = RequestReader::new(data.as_bytes()).with_raw(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.raw(),
+            Some(first.as_bytes()),
+            "Raw bytes of the first request"
+        );
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.raw(),
+            Some(second.as_bytes()),
+            "Raw bytes of the second request"
+        );
+
+        let mut reader = RequestReader::new(&b"GET /simple\r\n"[..])
+            .with_http09(true)
+            .with_raw(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.raw(),
+            Some(&b"GET /simple\r\n"[..]),
+            "Simple request"
+        );
+
+        let mut reader = RequestReader::new(data.as_bytes());
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.raw(), None, "Raw bytes not kept by default");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_eof_body() {
+        let data = "POST /echo-body HTTP/1.1\r\nHost: localhost\r\n\r\nHello\r\nWorld";
+
+        //======================================================================
+        // Test for a body delimited by the end of the stream
+        let mut reader = RequestReader::new(data.as_bytes()).with_eof_body(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.body, b"Hello\r\nWorld", "Body read until EOF");
+        assert!(reader.reached_eof(), "End of the stream reached");
+        assert!(
+            reader.read_request().await.unwrap().is_none(),
+            "Nothing left after the body"
+        );
+
+        //======================================================================
+        // Test for the flag disabled (default)
+        let mut reader = RequestReader::new(data.as_bytes());
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert!(request.body.is_empty(), "No body without framing");
+
+        //======================================================================
+        // Test for framed requests and methods without a body
+        let data = "POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nabGET / HTTP/1.1\r\n\r\n";
+        let mut reader = RequestReader::new(data.as_bytes()).with_eof_body(true);
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.body, b"ab", "Content-Length takes precedence");
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert!(request.body.is_empty(), "GET without a body");
+
+        //======================================================================
+        // Test for the body size limit
+        let data = "PUT /files/a HTTP/1.1\r\n\r\n0123456789";
+        let mut reader = RequestReader::new(data.as_bytes())
+            .with_eof_body(true)
+            .with_limits(Limits {
+                max_body_size: 5,
+                ..Default::default()
+            });
+        assert_eq!(
+            reader.read_request().await.unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Body over the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_stream() {
+        use tokio::io::AsyncReadExt;
+
+        let data =
+            "POST /a HTTP/1.1\r\nContent-Length: 11\r\n\r\nHello WorldGET /b HTTP/1.1\r\n\r\n";
+
+        //======================================================================
+        // Test for a streamed body followed by the next request
+        let mut reader = RequestReader::new(data.as_bytes());
+        let request = reader.read_head().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/a", "Head of the first request");
+        assert!(request.body.is_empty(), "Body not buffered");
+
+        let mut body = Vec::new();
+        reader.body_stream().read_to_end(&mut body).await.unwrap();
+        assert_eq!(body, b"Hello World", "Streamed body");
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/b", "Next request intact");
+
+        //======================================================================
+        // Test for a body left partly unread
+        let mut reader = RequestReader::new(data.as_bytes());
+        reader.read_head().await.unwrap().unwrap();
+
+        let mut start = [0; 5];
+        reader.body_stream().read_exact(&mut start).await.unwrap();
+        assert_eq!(&start, b"Hello", "Start of the body");
+
+        let request = reader.read_head().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/b", "Rest of the body skipped");
+
+        //======================================================================
+        // Test for a connection closed in the middle of the body
+        let data = "POST /a HTTP/1.1\r\nContent-Length: 11\r\n\r\nHello";
+        let mut reader = RequestReader::new(data.as_bytes());
+        reader.read_head().await.unwrap().unwrap();
+
+        let mut body = Vec::new();
+        assert_eq!(
+            reader
+                .body_stream()
+                .read_to_end(&mut body)
+                .await
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::UnexpectedEof,
+            "Truncated body"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_body_any_method() {
+        let data = "GET /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nHelloDELETE /b HTTP/1.1\r\n\
+                    Transfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\nGET /c HTTP/1.1\r\n\r\n";
+        let mut reader = RequestReader::new(data.as_bytes());
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.method, Method::Get, "GET request");
+        assert_eq!(request.body, b"Hello", "Body of a GET request");
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.method, Method::Delete, "DELETE request");
+        assert_eq!(request.body, b"abc", "Chunked body of a DELETE request");
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/c", "Next request intact");
+        assert!(request.body.is_empty(), "No body without framing headers");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_chunked() {
+        //======================================================================
+        // Test for chunk extensions and trailers, received in small reads
+        let data = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5;name=value\r\nHello\r\n1A \r\n, this is a chunked body!!\r\n\
+                     0\r\nX-Checksum: abc\r\n\r\nGET /next HTTP/1.1\r\n\r\n";
+
+        let mut reader = RequestReader::new(&data[..])
+            .with_raw(true)
+            .with_buffer(BufferStrategy {
+                initial_size: 16,
+                growth: BufferGrowth::Fixed(7),
+            });
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(
+            request.body, b"Hello, this is a chunked body!!",
+            "Decoded body"
+        );
+        assert_eq!(
+            request.content_length(&Limits::default()).unwrap(),
+            31,
+            "Length of the decoded body"
+        );
+        assert_eq!(
+            request.raw(),
+            Some(&data[..data.len() - 22]),
+            "Raw request with the chunk framing"
+        );
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/next", "Next request intact");
+
+        //======================================================================
+        // Test for malformed chunks
+        for data in [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nabc\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n+3\r\nabc\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabcd\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n",
+        ] {
+            let mut reader = RequestReader::new(data.as_bytes());
+            assert_eq!(
+                reader.read_request().await.unwrap_err().kind,
+                ParseRequestErrorKind::InvalidRequest,
+                "Malformed chunked body: {:?}",
+                data
+            );
+        }
+
+        //======================================================================
+        // Test for ambiguous framing and unsupported transfer codings
+        let data = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 1\r\n\r\n\
+                     5\r\nHello\r\n0\r\n\r\nGET /next HTTP/1.1\r\n\r\n";
+        let mut reader = RequestReader::new(&data[..]);
+        let err = reader.read_request().await.unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Transfer-Encoding with Content-Length rejected"
+        );
+        assert_eq!(err.status_code(), Some(StatusCode::BadRequest));
+
+        for data in [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\nabc",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\nabc",
+        ] {
+            let mut reader = RequestReader::new(data.as_bytes());
+            let err = reader.read_request().await.unwrap_err();
+            assert_eq!(
+                err.kind,
+                ParseRequestErrorKind::UnsupportedTransferEncoding,
+                "Transfer coding other than chunked: {:?}",
+                data
+            );
+            assert_eq!(err.status_code(), Some(StatusCode::NotImplemented));
+        }
+
+        //======================================================================
+        // Test for the body size limit
+        let data = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n12345678\r\n8\r\n";
+        let mut reader = RequestReader::new(data.as_bytes()).with_limits(Limits {
+            max_body_size: 10,
+            ..Limits::default()
+        });
+        assert_eq!(
+            reader.read_request().await.unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Chunked body over the limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_stream_chunked() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let data = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5\r\nHello\r\n6\r\n World\r\n0\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+
+        // A tiny pipe splits the body into reads of a few bytes
+        let (mut client, server) = tokio::io::duplex(3);
+        tokio::spawn(async move { client.write_all(data).await.unwrap() });
+
+        let mut reader = RequestReader::new(server);
+        reader.read_head().await.unwrap().unwrap();
+
+        let mut body = Vec::new();
+        reader.body_stream().read_to_end(&mut body).await.unwrap();
+        assert_eq!(body, b"Hello World", "Streamed chunked body");
+
+        let request = reader.read_request().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/b", "Next request intact");
+
+        //======================================================================
+        // Test for a chunked body left partly unread
+        let mut reader = RequestReader::new(&data[..]);
+        reader.read_head().await.unwrap().unwrap();
+
+        let mut start = [0; 3];
+        reader.body_stream().read_exact(&mut start).await.unwrap();
+        assert_eq!(&start, b"Hel", "Start of the body");
+
+        let request = reader.read_head().await.unwrap().unwrap();
+        assert_eq!(request.uri, "/b", "Rest of the body skipped");
+    }
+}
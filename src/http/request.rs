@@ -1,29 +1,279 @@
-use std::str::Lines;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+    io::Read,
+    str::Lines,
+};
 
-use super::{Method, ParseRequestError, ParseRequestErrorKind};
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+
+use super::{
+    percent_decode, DecodePolicy, Limits, Method, ParseRequestError, ParseRequestErrorKind,
+    PercentDecodeError,
+};
 
 #[derive(Debug)]
 pub struct Request {
     pub method: Method,
     pub uri: String,
+    pub version: Version,
     pub headers: Vec<(String, String)>,
     pub body: Vec<u8>,
+    /// Bytes of the request exactly as received, kept only when the reader is asked to
+    raw: Option<Vec<u8>>,
+}
+
+/// HTTP version of a request
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Version {
+    /// `GET /path` simple request without version, headers or body. The response is the bare
+    /// body, without status line and headers.
+    Http09,
+    #[default]
+    Http11,
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let version = match self {
+            Self::Http09 => "HTTP/0.9",
+            Self::Http11 => "HTTP/1.1",
+        };
+        write!(f, "{}", version)
+    }
+}
+
+impl Request {
+    /// # Get the bytes of the request exactly as the client sent them.
+    ///
+    /// Only available when the request was read with raw bytes kept (see
+    /// [`RequestReader::with_raw`](super::RequestReader::with_raw)), `None` otherwise.
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Keep the received bytes of the request
+    pub(super) fn set_raw(&mut self, raw: Vec<u8>) {
+        self.raw = Some(raw);
+    }
+
+    /// # Get the value of a header.
+    ///
+    /// Header names are case-insensitive. If the header is repeated, the first value is returned.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// # Get the names of the headers of the request.
+    ///
+    /// Names are in the order the headers were received, with their original casing. A
+    /// repeated header is named once, as it was first received.
+    pub fn header_names(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .iter()
+            .enumerate()
+            .filter(|(index, (name, _))| {
+                !self.headers[..*index]
+                    .iter()
+                    .any(|(earlier, _)| earlier.eq_ignore_ascii_case(name))
+            })
+            .map(|(_, (name, _))| name.as_str())
+    }
+
+    /// # Check whether the request body is JSON.
+    ///
+    /// True for `Content-Type: application/json` and structured `+json` types such as
+    /// `application/problem+json`, ignoring parameters like `charset` and case.
+    pub fn is_json(&self) -> bool {
+        self.header("Content-Type").is_some_and(is_json_media_type)
+    }
+
+    /// # Check whether the client prefers a JSON response.
+    ///
+    /// True if a JSON media type has the highest quality value in the `Accept` header (the
+    /// first one wins ties). Wildcards like `*/*` don't express a preference for JSON.
+    pub fn expects_json(&self) -> bool {
+        let Some(accept) = self.header("Accept") else {
+            return false;
+        };
+
+        accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+
+                (!media_type.is_empty()).then_some((media_type, quality))
+            })
+            // Keep the first of equally preferred ranges
+            .reduce(|best, range| match range.1 > best.1 {
+                true => range,
+                false => best,
+            })
+            .is_some_and(|(media_type, quality)| quality > 0.0 && is_json_media_type(media_type))
+    }
+
+    /// # Get the body with its `Content-Encoding` removed.
+    ///
+    /// `gzip` (or `x-gzip`) and `deflate` bodies are decompressed, gzip bodies up to their last
+    /// member; codings applied one after another (`Content-Encoding: deflate, gzip`) are removed
+    /// in reverse order. A body without encoding (or `identity`) is borrowed as is.
+    ///
+    /// Returns an `UnsupportedContentEncoding` error for other codings, an `EncodingError` for
+    /// corrupt data and a `PayloadTooLarge` error if the decoded body exceeds `max_size` bytes.
+    pub fn decoded_body(&self, max_size: usize) -> Result<Cow<'_, [u8]>, ParseRequestError> {
+        self.decode_body(max_size, false)
+    }
+
+    /// # Get the body with its `Content-Encoding` removed, detecting unlabeled gzip bodies.
+    ///
+    /// The same as [`decoded_body`](Request::decoded_body), except that a body without
+    /// `Content-Encoding` header starting with the gzip magic bytes (`1f 8b`) is decompressed as
+    /// gzip. Meant for clients that compress their uploads without saying so; a binary body
+    /// that happens to start with these bytes is an `EncodingError`.
+    pub fn decoded_body_detecting_gzip(
+        &self,
+        max_size: usize,
+    ) -> Result<Cow<'_, [u8]>, ParseRequestError> {
+        self.decode_body(max_size, true)
+    }
+
+    fn decode_body(
+        &self,
+        max_size: usize,
+        detect_gzip: bool,
+    ) -> Result<Cow<'_, [u8]>, ParseRequestError> {
+        const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+        let encodings = match self.header("Content-Encoding") {
+            Some(encodings) => encodings,
+            None if detect_gzip && self.body.starts_with(GZIP_MAGIC) => "gzip",
+            None => return Ok(Cow::Borrowed(&self.body)),
+        };
+
+        let mut body = Cow::Borrowed(self.body.as_slice());
+
+        for encoding in encodings.rsplit(',').map(str::trim) {
+            let decoder: Box<dyn Read + '_> = match encoding.to_ascii_lowercase().as_str() {
+                "identity" | "" => continue,
+                // A gzip stream may consist of several concatenated members
+                "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(body.as_ref())),
+                "deflate" => Box::new(ZlibDecoder::new(body.as_ref())),
+                _ => {
+                    return Err(ParseRequestError {
+                        kind: ParseRequestErrorKind::UnsupportedContentEncoding,
+                    })
+                }
+            };
+
+            // Read one byte past the limit to tell a body at the limit from a larger one
+            let mut decoded = Vec::new();
+            decoder
+                .take(max_size as u64 + 1)
+                .read_to_end(&mut decoded)
+                .map_err(|_| ParseRequestError {
+                    kind: ParseRequestErrorKind::EncodingError,
+                })?;
+
+            if decoded.len() > max_size {
+                return Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::PayloadTooLarge,
+                });
+            }
+
+            body = Cow::Owned(decoded);
+        }
+
+        Ok(body)
+    }
+
+    /// # Get the `Max-Forwards` value of a `TRACE` or `OPTIONS` request.
+    ///
+    /// It's the number of times the request may still be forwarded by proxies; a recipient
+    /// receiving `0` must answer the request itself. Returns `None` if the header is missing or
+    /// isn't a decimal number. The server never forwards requests, so it always answers.
+    pub fn max_forwards(&self) -> Option<u32> {
+        let value = self.header("Max-Forwards")?.trim();
+
+        match value.bytes().all(|b| b.is_ascii_digit()) {
+            true => value.parse().ok(),
+            false => None,
+        }
+    }
+
+    /// The path part of the URI, without the query string (still percent-encoded)
+    pub fn path(&self) -> &str {
+        self.uri.split_once('?').map_or(&self.uri, |(path, _)| path)
+    }
+
+    /// The query string of the URI without the leading `?` (still percent-encoded)
+    pub fn query(&self) -> Option<&str> {
+        self.uri.split_once('?').map(|(_, query)| query)
+    }
+
+    /// # Decode the query string into name/value pairs.
+    ///
+    /// Pairs are separated by `&` and names from values by `=`. A pair without `=` has an empty
+    /// value. Names and values are percent-decoded according to the policy.
+    pub fn query_params(
+        &self,
+        policy: DecodePolicy,
+    ) -> Result<Vec<(String, String)>, PercentDecodeError> {
+        self.query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((
+                    percent_decode(name, policy)?,
+                    percent_decode(value, policy)?,
+                ))
+            })
+            .collect()
+    }
 }
 
 impl TryFrom<&str> for Request {
     type Error = ParseRequestError;
 
     fn try_from(request_str: &str) -> Result<Self, Self::Error> {
+        Self::parse(request_str, &Limits::default())
+    }
+}
+
+impl Request {
+    /// # Parse a request, enforcing the request line and header count limits.
+    ///
+    /// The body is everything after the empty line terminating the headers.
+    pub fn parse(request_str: &str, limits: &Limits) -> Result<Self, ParseRequestError> {
         // Get the first line of the request
         let (first_line, mut rest) = get_next_request_line(request_str)?;
 
+        if first_line.len() > limits.max_request_line {
+            return Err(request_line_too_long(first_line.as_bytes()));
+        }
+
         // Split the first line into the method and the rest of the line
         let (method, rest_of_line) = get_next_word(first_line).ok_or(ParseRequestError {
             kind: ParseRequestErrorKind::InvalidRequest,
         })?;
 
-        // Parse the method
-        let method = method.parse::<Method>()?;
+        // Parse the method. A well-formed token the server doesn't know is a method it doesn't
+        // implement, anything else is a malformed request.
+        let method = method.parse::<Method>().map_err(|_| ParseRequestError {
+            kind: match is_token(method) {
+                true => ParseRequestErrorKind::UnknownMethod,
+                false => ParseRequestErrorKind::InvalidMethod,
+            },
+        })?;
 
         // Split the rest of the first line into the URI and the protocol
         let (uri, protocol) = get_next_word(rest_of_line).ok_or(ParseRequestError {
@@ -32,20 +282,21 @@ impl TryFrom<&str> for Request {
 
         let uri = uri.to_string();
 
-        if !uri.starts_with('/') {
+        // The protocol is empty if the request line ends after the URI
+        if protocol.is_empty() {
             return Err(ParseRequestError {
                 kind: ParseRequestErrorKind::InvalidRequest,
             });
         }
 
+        if !uri.starts_with('/') || !uri.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(ParseRequestError {
+                kind: ParseRequestErrorKind::InvalidTarget,
+            });
+        }
+
         // Ensure the protocol is HTTP/1.1
         if protocol != "HTTP/1.1" {
-            // We can get an empty protocol if the method or URI are missing
-            if protocol.is_empty() {
-                return Err(ParseRequestError {
-                    kind: ParseRequestErrorKind::InvalidRequest,
-                });
-            }
             return Err(ParseRequestError {
                 kind: ParseRequestErrorKind::InvalidProtocol,
             });
@@ -60,6 +311,12 @@ impl TryFrom<&str> for Request {
                 break;
             }
 
+            if headers.len() == limits.max_header_count {
+                return Err(ParseRequestError {
+                    kind: ParseRequestErrorKind::TooManyHeaders,
+                });
+            }
+
             // Parse the header
             let (header_name, header_value) = parse_header(line).ok_or(ParseRequestError {
                 kind: ParseRequestErrorKind::InvalidRequest,
@@ -75,14 +332,101 @@ impl TryFrom<&str> for Request {
         Ok(Self {
             method,
             uri,
+            version: Version::Http11,
             headers,
             body,
+            raw: None,
         })
         // todo!()
     }
+
+    /// # Parse a request from raw bytes, framing the body by `Content-Length`.
+    ///
+    /// Unlike [`TryFrom<&str>`](Request::try_from), which takes everything after the headers as
+    /// the body and drops its line breaks, the body is exactly `Content-Length` bytes, copied
+    /// as is. Bytes past the body are ignored. A request without `Content-Length` has no body.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseRequestError> {
+        Self::parse_bytes(bytes, &Limits::default())
+    }
+
+    /// # Parse a request from raw bytes, enforcing the limits.
+    ///
+    /// See [`from_bytes`](Request::from_bytes). A request whose head or body is incomplete is an
+    /// `InvalidRequest` error.
+    pub fn parse_bytes(bytes: &[u8], limits: &Limits) -> Result<Self, ParseRequestError> {
+        let head_len = find_head_end(bytes).ok_or(ParseRequestError {
+            kind: ParseRequestErrorKind::InvalidRequest,
+        })?;
+
+        if head_len > limits.max_header_bytes {
+            return Err(ParseRequestError {
+                kind: ParseRequestErrorKind::HeadersTooLarge,
+            });
+        }
+
+        let mut request = Self::parse(std::str::from_utf8(&bytes[..head_len])?, limits)?;
+        let content_length = request.content_length(limits)?;
+
+        let body = bytes
+            .get(head_len..head_len + content_length)
+            .ok_or(ParseRequestError {
+                kind: ParseRequestErrorKind::InvalidRequest,
+            })?;
+        request.body = body.to_vec();
+
+        Ok(request)
+    }
+
+    /// # Get the length of the body announced by the `Content-Length` header.
+    ///
+    /// A missing header means no body. A value that isn't a number is an `InvalidRequest` error
+    /// and one over the body size limit a `PayloadTooLarge` error.
+    ///
+    /// A `Transfer-Encoding: chunked` body announces no length (and takes precedence over
+    /// `Content-Length`), so its length is the one of the decoded body, once it was read.
+    pub fn content_length(&self, limits: &Limits) -> Result<usize, ParseRequestError> {
+        let content_length = match self.header("Content-Length") {
+            _ if self.header("Transfer-Encoding").is_some() => self.body.len(),
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| ParseRequestError {
+                    kind: ParseRequestErrorKind::InvalidRequest,
+                })?,
+            None => 0,
+        };
+
+        if content_length > limits.max_body_size {
+            return Err(ParseRequestError {
+                kind: ParseRequestErrorKind::PayloadTooLarge,
+            });
+        }
+
+        Ok(content_length)
+    }
+
+    /// # Parse an HTTP/0.9 simple request line (`GET /path`, without CRLF).
+    ///
+    /// Returns `None` if the line is not a simple request, e.g. because it has a version.
+    pub fn parse_simple_request(line: &str) -> Option<Self> {
+        let (method, uri) = get_next_word(line)?;
+
+        if method != "GET" || !uri.starts_with('/') || uri.contains(' ') {
+            return None;
+        }
+
+        Some(Self {
+            method: Method::Get,
+            uri: uri.to_string(),
+            version: Version::Http09,
+            headers: Vec::new(),
+            body: Vec::new(),
+            raw: None,
+        })
+    }
 }
 
-fn get_next_request_line(request_str: &str) -> Result<(&str, Lines), ParseRequestError> {
+fn get_next_request_line(request_str: &str) -> Result<(&str, Lines<'_>), ParseRequestError> {
     let mut lines = request_str.lines();
     let first_line = lines.next().ok_or(ParseRequestError {
         kind: ParseRequestErrorKind::InvalidRequest,
@@ -91,22 +435,80 @@ fn get_next_request_line(request_str: &str) -> Result<(&str, Lines), ParseReques
     Ok((first_line?, lines))
 }
 
+/// # The error for a request line over the length limit.
+///
+/// `UriTooLong` if the (possibly incomplete) line has a method followed by a target, which is
+/// what makes a request line long, and `RequestLineTooLong` if it's a single huge token or has a
+/// long protocol after the target.
+pub(super) fn request_line_too_long(line: &[u8]) -> ParseRequestError {
+    const PROTOCOL_LEN: usize = "HTTP/1.1".len();
+
+    let kind = match line.iter().position(|&b| b == b' ') {
+        None => ParseRequestErrorKind::RequestLineTooLong,
+        Some(method_end) => match line[method_end + 1..].iter().position(|&b| b == b' ') {
+            Some(target_len) if line.len() - (method_end + target_len + 2) > PROTOCOL_LEN => {
+                ParseRequestErrorKind::RequestLineTooLong
+            }
+            _ => ParseRequestErrorKind::UriTooLong,
+        },
+    };
+
+    ParseRequestError { kind }
+}
+
+/// # Split the next word off a request line.
+///
+/// Returns the word and the rest of the line after the single space ending it. Returns `None`
+/// if there's no word: for an empty line, or a line starting with a space, which means a
+/// component of the request line is missing or separated by more than one space.
 fn get_next_word(request_line: &str) -> Option<(&str, &str)> {
-    if request_line.is_empty() {
-        return None;
-    }
+    let (word, rest) = request_line.split_once(' ').unwrap_or((request_line, ""));
 
-    for (i, c) in request_line.chars().enumerate() {
-        if c == ' ' {
-            return Some((&request_line[..i], &request_line[i + 1..]));
-        }
+    match word.is_empty() {
+        true => None,
+        false => Some((word, rest)),
     }
+}
 
-    Some((request_line, ""))
+/// Whether the string is a token (RFC 9110, section 5.6.2), the syntax of methods and header
+/// names
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
 }
 
+/// Split a header line into its name and value. The optional whitespace (spaces and tabs)
+/// around the value is trimmed; whitespace inside the value is kept.
 fn parse_header(header: &str) -> Option<(&str, &str)> {
-    header.split_once(": ")
+    let (name, value) = header.split_once(':')?;
+
+    // No whitespace is allowed in the name or between the name and the colon
+    if name.is_empty() || name.contains([' ', '\t']) {
+        return None;
+    }
+
+    Some((name, value.trim_matches([' ', '\t'])))
+}
+
+/// Check whether a media type (possibly with parameters) is JSON
+fn is_json_media_type(media_type: &str) -> bool {
+    let media_type = media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json"
+        || (media_type.starts_with("application/") && media_type.ends_with("+json"))
+}
+
+/// Find the length of the request head including the empty line terminating the headers
+pub(super) fn find_head_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4)
 }
 
 #[cfg(test)]
@@ -135,6 +537,8 @@ mod tests {
             None,
             "String slice incremental parsing termiantion"
         );
+        assert_eq!(get_next_word(" / HTTP/1.1"), None, "Missing word");
+        assert_eq!(get_next_word("  "), None, "Only whitespace");
     }
 
     #[test]
@@ -220,16 +624,449 @@ mod tests {
         );
 
         let request = Request::try_from("GET abc HTTP/1.0\r\n\r\n");
-        let err_kind = ParseRequestErrorKind::InvalidRequest;
-        assert_eq!(request.unwrap_err().kind, err_kind, "Invalid request error");
+        let err_kind = ParseRequestErrorKind::InvalidTarget;
+        assert_eq!(request.unwrap_err().kind, err_kind, "Relative target");
+
+        let request = Request::try_from("GET /caf\u{e9} HTTP/1.1\r\n\r\n");
+        let err_kind = ParseRequestErrorKind::InvalidTarget;
+        assert_eq!(request.unwrap_err().kind, err_kind, "Non-ASCII target");
+
+        let request = Request::try_from("GET /a\x7fb HTTP/1.1\r\n\r\n");
+        let err_kind = ParseRequestErrorKind::InvalidTarget;
+        assert_eq!(
+            request.unwrap_err().kind,
+            err_kind,
+            "Control character in the target"
+        );
 
         let request = Request::try_from("GETT / HTTP/1.1\r\n\r\n");
+        let err_kind = ParseRequestErrorKind::UnknownMethod;
+        assert_eq!(request.unwrap_err().kind, err_kind, "Unknown method error");
+
+        let request = Request::try_from("GE(T / HTTP/1.1\r\n\r\n");
         let err_kind = ParseRequestErrorKind::InvalidMethod;
         assert_eq!(request.unwrap_err().kind, err_kind, "Invalid method error");
 
         let request = Request::try_from("GET HTTP/1.1\r\n\r\n");
         let err_kind = ParseRequestErrorKind::InvalidRequest;
         assert_eq!(request.unwrap_err().kind, err_kind, "Invalid request error");
+
+        // Every missing component of the request line is a malformed request
+        for line in [
+            "  ",
+            "GET",
+            "GET ",
+            "GET /",
+            "GET / ",
+            " / HTTP/1.1",
+            "GET  / HTTP/1.1",
+        ] {
+            let request = Request::try_from(format!("{}\r\n\r\n", line).as_str());
+            assert_eq!(
+                request.unwrap_err().kind,
+                ParseRequestErrorKind::InvalidRequest,
+                "Incomplete request line {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let data = b"POST /files/a HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 12\r\n\r\nHello\r\nWorld";
+
+        let request = Request::from_bytes(data).unwrap();
+        assert_eq!(request.method, Method::Post, "Method");
+        assert_eq!(request.uri, "/files/a", "URI");
+        assert_eq!(request.headers.len(), 2, "Headers");
+        assert_eq!(request.body, b"Hello\r\nWorld", "Body with embedded CRLF");
+
+        // The line-based parsing loses the line breaks of the body
+        let request = Request::try_from(std::str::from_utf8(data).unwrap()).unwrap();
+        assert_eq!(
+            request.body, b"HelloWorld",
+            "Body corrupted by TryFrom<&str>"
+        );
+
+        //======================================================================
+        // Test for the body being framed by Content-Length
+        let request = Request::from_bytes(
+            b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc\r\nGET / HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.body, b"abc", "Bytes past the body are ignored");
+
+        let request = Request::from_bytes(b"GET / HTTP/1.1\r\n\r\nabc").unwrap();
+        assert!(request.body.is_empty(), "No body without Content-Length");
+
+        let request =
+            Request::from_bytes(b"GET / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc").unwrap();
+        assert_eq!(request.body, b"abc", "Body of a GET request");
+
+        //======================================================================
+        // Test for incomplete or malformed requests
+        let err = Request::from_bytes(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc");
+        assert_eq!(
+            err.unwrap_err().kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Truncated body"
+        );
+
+        let err = Request::from_bytes(b"GET / HTTP/1.1\r\nHost: local");
+        assert_eq!(
+            err.unwrap_err().kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Truncated head"
+        );
+
+        let err = Request::from_bytes(b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n");
+        assert_eq!(
+            err.unwrap_err().kind,
+            ParseRequestErrorKind::InvalidRequest,
+            "Invalid Content-Length"
+        );
+
+        let err = Request::parse_bytes(
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+            &Limits {
+                max_body_size: 4,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            err.unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Body over the limit"
+        );
+    }
+
+    #[test]
+    fn test_parse_simple_request() {
+        let request = Request::parse_simple_request("GET /echo/abc").unwrap();
+        assert_eq!(request.method, Method::Get, "Method");
+        assert_eq!(request.uri, "/echo/abc", "URI");
+        assert_eq!(request.version, Version::Http09, "Version");
+
+        assert!(
+            Request::parse_simple_request("GET / HTTP/1.1").is_none(),
+            "Request line with version"
+        );
+        assert!(
+            Request::parse_simple_request("POST /").is_none(),
+            "Only GET is allowed"
+        );
+        assert!(
+            Request::parse_simple_request("GET").is_none(),
+            "Missing URI"
+        );
+
+        let request = Request::try_from("GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.version, Version::Http11, "HTTP/1.1 request");
+    }
+
+    #[test]
+    fn test_header() {
+        let request = Request::try_from(
+            "GET / HTTP/1.1\r\nHost: localhost:4221\r\ncontent-length: 0\r\nAccept: */*\r\nAccept: text/plain\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.header("Host"), Some("localhost:4221"), "Exact name");
+        assert_eq!(
+            request.header("Content-Length"),
+            Some("0"),
+            "Case-insensitive name"
+        );
+        assert_eq!(
+            request.header("Accept"),
+            Some("*/*"),
+            "First value of repeated header"
+        );
+        assert_eq!(request.header("User-Agent"), None, "Missing header");
+    }
+
+    #[test]
+    fn test_header_names() {
+        let request = Request::try_from(
+            "GET / HTTP/1.1\r\nHost: localhost:4221\r\nx-custom-ID: 1\r\nAccept: */*\r\naccept: text/plain\r\nUser-Agent: curl\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            request.header_names().collect::<Vec<_>>(),
+            vec!["Host", "x-custom-ID", "Accept", "User-Agent"],
+            "Names in order, with their casing, repeated header once"
+        );
+
+        let request = Request::try_from("GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.header_names().count(), 0, "No headers");
+    }
+
+    #[test]
+    fn test_is_json() {
+        let is_json = |headers: &str| {
+            Request::try_from(format!("POST / HTTP/1.1\r\n{}\r\n", headers).as_str())
+                .unwrap()
+                .is_json()
+        };
+
+        assert!(is_json("Content-Type: application/json\r\n"), "JSON");
+        assert!(
+            is_json("content-type: Application/JSON; charset=utf-8\r\n"),
+            "JSON with parameters"
+        );
+        assert!(
+            is_json("Content-Type: application/problem+json\r\n"),
+            "Structured JSON type"
+        );
+        assert!(!is_json("Content-Type: text/plain\r\n"), "Plain text");
+        assert!(
+            !is_json("Content-Type: application/jsonp\r\n"),
+            "Similar type"
+        );
+        assert!(!is_json(""), "No Content-Type");
+    }
+
+    #[test]
+    fn test_expects_json() {
+        let expects_json = |headers: &str| {
+            Request::try_from(format!("GET / HTTP/1.1\r\n{}\r\n", headers).as_str())
+                .unwrap()
+                .expects_json()
+        };
+
+        assert!(expects_json("Accept: application/json\r\n"), "JSON only");
+        assert!(
+            expects_json("Accept: application/json, text/plain;q=0.5\r\n"),
+            "JSON preferred"
+        );
+        assert!(
+            expects_json("Accept: text/html;q=0.8, application/vnd.api+json\r\n"),
+            "Structured JSON type preferred"
+        );
+        assert!(
+            !expects_json("Accept: text/html, application/json;q=0.9\r\n"),
+            "HTML preferred"
+        );
+        assert!(
+            !expects_json("Accept: text/html, application/json\r\n"),
+            "First of equally preferred types"
+        );
+        assert!(
+            !expects_json("Accept: application/json;q=0\r\n"),
+            "JSON refused"
+        );
+        assert!(!expects_json("Accept: */*\r\n"), "Wildcard");
+        assert!(!expects_json(""), "No Accept");
+    }
+
+    #[test]
+    fn test_decoded_body() {
+        use flate2::{
+            write::{GzEncoder, ZlibEncoder},
+            Compression,
+        };
+        use std::io::Write;
+
+        let request = |encoding: &str, body: Vec<u8>| Request {
+            method: Method::Post,
+            uri: "/".to_string(),
+            version: Version::Http11,
+            headers: vec![("Content-Encoding".to_string(), encoding.to_string())],
+            body,
+            raw: None,
+        };
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(b"Hello, World!").unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut deflate_gzip = ZlibEncoder::new(Vec::new(), Compression::default());
+        deflate_gzip.write_all(&gzip).unwrap();
+        let deflate_gzip = deflate_gzip.finish().unwrap();
+
+        assert_eq!(
+            request("gzip", gzip.clone()).decoded_body(1024).unwrap(),
+            &b"Hello, World!"[..],
+            "gzip"
+        );
+        assert_eq!(
+            request("gzip, deflate", deflate_gzip)
+                .decoded_body(1024)
+                .unwrap(),
+            &b"Hello, World!"[..],
+            "Codings removed in reverse order"
+        );
+        assert!(
+            matches!(
+                request("identity", b"plain".to_vec()).decoded_body(1024),
+                Ok(Cow::Borrowed(b"plain"))
+            ),
+            "identity is borrowed"
+        );
+
+        assert_eq!(
+            request("br", gzip.clone())
+                .decoded_body(1024)
+                .unwrap_err()
+                .kind,
+            ParseRequestErrorKind::UnsupportedContentEncoding,
+            "Unsupported coding"
+        );
+        assert_eq!(
+            request("gzip", b"not gzip".to_vec())
+                .decoded_body(1024)
+                .unwrap_err()
+                .kind,
+            ParseRequestErrorKind::EncodingError,
+            "Corrupt data"
+        );
+        assert_eq!(
+            request("gzip", gzip.clone()).decoded_body(13).unwrap(),
+            &b"Hello, World!"[..],
+            "Decoded body at the limit"
+        );
+        assert_eq!(
+            request("gzip", gzip).decoded_body(12).unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Decoded body over the limit"
+        );
+    }
+
+    #[test]
+    fn test_decoded_body_multiple_gzip_members() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let member = |data: &[u8]| {
+            let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+            gzip.write_all(data).unwrap();
+            gzip.finish().unwrap()
+        };
+
+        let mut body = member(b"Hello, ");
+        body.extend(member(b"World!"));
+
+        let request = Request {
+            method: Method::Post,
+            uri: "/".to_string(),
+            version: Version::Http11,
+            headers: vec![("Content-Encoding".to_string(), "gzip".to_string())],
+            body,
+            raw: None,
+        };
+
+        assert_eq!(
+            request.decoded_body(1024).unwrap(),
+            &b"Hello, World!"[..],
+            "All members decoded"
+        );
+        assert_eq!(
+            request.decoded_body(10).unwrap_err().kind,
+            ParseRequestErrorKind::PayloadTooLarge,
+            "Limit applies to all members"
+        );
+    }
+
+    #[test]
+    fn test_decoded_body_detecting_gzip() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let request = |body: Vec<u8>| Request {
+            method: Method::Post,
+            uri: "/".to_string(),
+            version: Version::Http11,
+            headers: Vec::new(),
+            body,
+            raw: None,
+        };
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(b"Hello, World!").unwrap();
+        let request_gzip = request(gzip.finish().unwrap());
+
+        assert_eq!(
+            request_gzip.decoded_body_detecting_gzip(1024).unwrap(),
+            &b"Hello, World!"[..],
+            "Header-less gzip body decoded"
+        );
+        assert_eq!(
+            request_gzip.decoded_body(1024).unwrap(),
+            request_gzip.body.as_slice(),
+            "Not detected by default"
+        );
+
+        let request_plain = request(b"Hello, World!".to_vec());
+        assert!(
+            matches!(
+                request_plain.decoded_body_detecting_gzip(1024).unwrap(),
+                Cow::Borrowed(b"Hello, World!")
+            ),
+            "Other bodies borrowed as is"
+        );
+
+        let request_corrupt = request(vec![0x1f, 0x8b, 0x00]);
+        assert_eq!(
+            request_corrupt
+                .decoded_body_detecting_gzip(1024)
+                .unwrap_err()
+                .kind,
+            ParseRequestErrorKind::EncodingError,
+            "Magic bytes without gzip data"
+        );
+    }
+
+    #[test]
+    fn test_max_forwards() {
+        let max_forwards = |headers: &str| {
+            Request::try_from(format!("OPTIONS / HTTP/1.1\r\n{}\r\n", headers).as_str())
+                .unwrap()
+                .max_forwards()
+        };
+
+        assert_eq!(max_forwards("Max-Forwards: 0\r\n"), Some(0), "Zero");
+        assert_eq!(max_forwards("max-forwards: 10\r\n"), Some(10), "Ten");
+        assert_eq!(max_forwards(""), None, "Missing header");
+        assert_eq!(max_forwards("Max-Forwards: -1\r\n"), None, "Negative");
+        assert_eq!(max_forwards("Max-Forwards: +1\r\n"), None, "Sign");
+        assert_eq!(max_forwards("Max-Forwards: many\r\n"), None, "Not a number");
+    }
+
+    #[test]
+    fn test_path_and_query() {
+        let request =
+            Request::try_from("GET /files/a%20b?sort=size&q=%41%ZZ&flag HTTP/1.1\r\n\r\n").unwrap();
+
+        assert_eq!(request.path(), "/files/a%20b", "Path without query");
+        assert_eq!(
+            request.query(),
+            Some("sort=size&q=%41%ZZ&flag"),
+            "Query string"
+        );
+        assert_eq!(
+            request.query_params(DecodePolicy::Lenient),
+            Ok(vec![
+                ("sort".to_string(), "size".to_string()),
+                ("q".to_string(), "A%ZZ".to_string()),
+                ("flag".to_string(), "".to_string()),
+            ]),
+            "Leniently decoded query parameters"
+        );
+        assert_eq!(
+            request.query_params(DecodePolicy::Strict),
+            Err(PercentDecodeError),
+            "Strictly decoded query parameters"
+        );
+
+        let request = Request::try_from("GET /echo/abc HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path(), "/echo/abc", "Path");
+        assert_eq!(request.query(), None, "No query string");
+        assert_eq!(
+            request.query_params(DecodePolicy::Strict),
+            Ok(vec![]),
+            "No parameters"
+        );
     }
 
     #[test]
@@ -253,5 +1090,39 @@ mod tests {
         let arg = "Content-Length: 11";
         let expected = Some(("Content-Length", "11"));
         assert_eq!(parse_header(arg), expected, "Content-Length header");
+
+        let value = "a".repeat(4 * 1024);
+        let arg = format!("Authorization: {}", value);
+        let expected = Some(("Authorization", value.as_str()));
+        assert_eq!(parse_header(&arg), expected, "4 KiB Authorization header");
+
+        let arg = "Accept:\t text/html,\tapplication/json \t";
+        let expected = Some(("Accept", "text/html,\tapplication/json"));
+        assert_eq!(
+            parse_header(arg),
+            expected,
+            "Value padded and separated by tabs"
+        );
+
+        let arg = "Host:localhost";
+        let expected = Some(("Host", "localhost"));
+        assert_eq!(parse_header(arg), expected, "No whitespace after colon");
+
+        let arg = "X-Empty:\t";
+        let expected = Some(("X-Empty", ""));
+        assert_eq!(parse_header(arg), expected, "Empty value");
+
+        assert_eq!(parse_header("Host : localhost"), None, "Space before colon");
+        assert_eq!(parse_header("Host\t: localhost"), None, "Tab before colon");
+        assert_eq!(parse_header(": localhost"), None, "Empty name");
+        assert_eq!(parse_header("Host localhost"), None, "No colon");
+
+        let request =
+            Request::try_from("GET / HTTP/1.1\r\nUser-Agent:\tcurl/8.6.0\t\r\n\r\n").unwrap();
+        assert_eq!(
+            request.header("User-Agent"),
+            Some("curl/8.6.0"),
+            "Tabs trimmed in a parsed request"
+        );
     }
 }
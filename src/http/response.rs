@@ -1,68 +1,355 @@
-use std::fmt::{Display, Write};
+use std::{borrow::Cow, fmt::Display, io::Write};
 
-use super::status_code::StatusCode;
+use flate2::{write::GzEncoder, Compression};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{status_code::StatusCode, WriteError};
+
+/// Size of the body slices sent (and compressed) as one chunk of a chunked response
+const CHUNK_SIZE: usize = 16 * 1024;
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct Response {
     pub status_code: StatusCode,
     pub headers: Vec<(String, String)>,
-    pub body: Option<Vec<u8>>,
+    /// Borrowed for a static body, see
+    /// [`ResponseBuilder::static_body`](super::ResponseBuilder::static_body)
+    pub body: Option<Cow<'static, [u8]>>,
+    /// Send the body with `Transfer-Encoding: chunked`. When the response also has
+    /// `Content-Encoding: gzip`, the body is kept uncompressed and is compressed chunk by chunk
+    /// while it's serialized.
+    pub chunked: bool,
+    /// Flush the stream after the status line and headers when the response is written, so
+    /// they reach the client without waiting for the body
+    pub flush_head: bool,
 }
 
 impl Response {
     pub fn to_bytes_vec(&self) -> Vec<u8> {
-        let mut response: Vec<u8> = Vec::new();
+        let mut response = self.head_bytes();
+
+        for chunk in self.body_chunks() {
+            response.extend_from_slice(&chunk);
+        }
+
+        response
+    }
+
+    /// # Write the response to a stream.
+    ///
+    /// The body of a chunked response is written chunk by chunk, so a gzip-encoded chunked body
+    /// is never held compressed in memory as a whole. The stream is flushed at the end, and
+    /// after the head too if [`flush_head`](Response::flush_head) is set.
+    ///
+    /// Returns the number of bytes written. If writing fails partway, the error tells how many
+    /// bytes made it to the stream before the failure.
+    pub async fn write_to<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, WriteError> {
+        let mut written = 0;
+
+        write_counted(writer, &self.head_bytes(), &mut written).await?;
+
+        if self.flush_head {
+            writer
+                .flush()
+                .await
+                .map_err(|source| WriteError { written, source })?;
+        }
 
+        for chunk in self.body_chunks() {
+            write_counted(writer, &chunk, &mut written).await?;
+        }
+
+        writer
+            .flush()
+            .await
+            .map_err(|source| WriteError { written, source })?;
+
+        Ok(written)
+    }
+
+    /// Status line and headers, including the empty line terminating the headers
+    fn head_bytes(&self) -> Vec<u8> {
         let status_code: String = format!("{}", self.status_code);
 
-        let mut headers: Vec<u8> = self.headers.iter().fold(vec![], |mut acc, (k, v)| {
-            acc.extend_from_slice(k.as_bytes());
-            acc.extend_from_slice(b": ");
-            acc.extend_from_slice(v.as_bytes());
-            acc.extend_from_slice(b"\r\n");
-            acc
-        });
+        let mut head: Vec<u8> =
+            self.headers
+                .iter()
+                .fold(status_code.into_bytes(), |mut acc, (k, v)| {
+                    acc.extend_from_slice(k.as_bytes());
+                    acc.extend_from_slice(b": ");
+                    acc.extend_from_slice(v.as_bytes());
+                    acc.extend_from_slice(b"\r\n");
+                    acc
+                });
 
         // Add additional CLRF after all headers
-        headers.extend_from_slice(b"\r\n");
+        head.extend_from_slice(b"\r\n");
 
-        if let Some(body) = &self.body {
-            response.extend_from_slice(status_code.as_bytes());
-            response.extend(headers);
-            response.extend_from_slice(body);
-        } else {
-            response.extend_from_slice(status_code.as_bytes());
-            response.extend(headers);
+        head
+    }
+
+    /// The serialized body: the body as is, or the chunk frames of a chunked response
+    fn body_chunks(&self) -> BodyChunks<'_> {
+        let body = self.body.as_deref().unwrap_or_default();
+
+        let gzip = self.chunked
+            && self
+                .headers
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case("Content-Encoding") && v == "gzip");
+
+        BodyChunks {
+            body,
+            pos: 0,
+            chunked: self.chunked,
+            encoder: gzip.then(|| GzEncoder::new(Vec::new(), Compression::default())),
+            done: false,
         }
+    }
 
-        response
+    /// # Reorder the headers canonically.
+    ///
+    /// General headers (`Date`, `Connection`, ...) come first, then response headers
+    /// (`Location`, `ETag`, ...), then content headers (`Content-*`, `Last-Modified`, ...) and
+    /// finally any other header. Headers of the same group keep their relative order, so
+    /// repeated headers stay in the order they were set. Without this, headers are sent in the
+    /// order they were set, with the framing headers last.
+    pub fn sort_headers_canonically(&mut self) {
+        self.headers.sort_by_key(|(name, _)| header_group(name));
+    }
+
+    /// # Set the `Connection` header of a built response.
+    ///
+    /// Replaces the value the builder set, if any, so the response never carries two
+    /// `Connection` headers.
+    pub fn set_connection(&mut self, value: impl Into<String>) {
+        self.set_header("Connection", value);
+    }
+
+    /// # Set a header of a built response.
+    ///
+    /// Replaces the value of the first header with that name in place, or adds the header
+    /// after the others.
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+
+        match self
+            .headers
+            .iter_mut()
+            .find(|(current_name, _)| current_name.eq_ignore_ascii_case(name))
+        {
+            Some((_, current)) => *current = value,
+            None => self.headers.push((name.to_string(), value)),
+        }
+    }
+
+    /// # Serialize the response for an HTTP/0.9 client.
+    ///
+    /// HTTP/0.9 responses consist of the body only, without status line and headers.
+    pub fn to_http09_bytes_vec(&self) -> Vec<u8> {
+        self.body.as_deref().unwrap_or_default().to_vec()
     }
 }
 
+/// Rank of the group of a header in the canonical order
+fn header_group(name: &str) -> u8 {
+    const GENERAL: &[&str] = &[
+        "Date",
+        "Connection",
+        "Keep-Alive",
+        "Cache-Control",
+        "Pragma",
+        "Upgrade",
+        "Via",
+        "Warning",
+        "Transfer-Encoding",
+    ];
+    const RESPONSE: &[&str] = &[
+        "Server",
+        "Location",
+        "ETag",
+        "Allow",
+        "Vary",
+        "Accept-Ranges",
+        "Age",
+        "Retry-After",
+        "WWW-Authenticate",
+    ];
+    const CONTENT: &[&str] = &["Last-Modified", "Expires"];
+
+    let is_in = |group: &[&str]| group.iter().any(|header| header.eq_ignore_ascii_case(name));
+
+    if is_in(GENERAL) {
+        0
+    } else if is_in(RESPONSE) {
+        1
+    } else if is_in(CONTENT)
+        || name
+            .get(..8)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("Content-"))
+    {
+        2
+    } else {
+        3
+    }
+}
+
+/// # Iterator over the serialized body of a response.
+///
+/// A plain body is yielded as is, without copying. A chunked body is yielded as chunk frames
+/// (`<size in hex>\r\n<data>\r\n`) followed by the terminating `0\r\n\r\n` frame. If the body
+/// is gzip-encoded, every slice is compressed right before it's framed, so only one compressed
+/// chunk exists at a time.
+struct BodyChunks<'a> {
+    body: &'a [u8],
+    pos: usize,
+    chunked: bool,
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    done: bool,
+}
+
+impl<'a> Iterator for BodyChunks<'a> {
+    type Item = Cow<'a, [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if !self.chunked {
+            self.done = true;
+            return (!self.body.is_empty()).then_some(Cow::Borrowed(self.body));
+        }
+
+        while self.pos < self.body.len() {
+            let end = (self.pos + CHUNK_SIZE).min(self.body.len());
+            let slice = &self.body[self.pos..end];
+            self.pos = end;
+
+            let data = match self.encoder.as_mut() {
+                Some(encoder) => {
+                    encoder
+                        .write_all(slice)
+                        .expect("Writing to a Vec never fails");
+                    Cow::Owned(std::mem::take(encoder.get_mut()))
+                }
+                None => Cow::Borrowed(slice),
+            };
+
+            // The encoder may buffer the input without producing any output yet
+            if !data.is_empty() {
+                return Some(Cow::Owned(chunk_frame(&data)));
+            }
+        }
+
+        self.done = true;
+
+        let mut last = match self.encoder.take() {
+            Some(encoder) => {
+                let data = encoder.finish().expect("Writing to a Vec never fails");
+                chunk_frame(&data)
+            }
+            None => Vec::new(),
+        };
+        last.extend_from_slice(b"0\r\n\r\n");
+
+        Some(Cow::Owned(last))
+    }
+}
+
+/// Write the whole buffer, adding every successfully written byte to `written`
+async fn write_counted<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut buf: &[u8],
+    written: &mut usize,
+) -> Result<(), WriteError> {
+    while !buf.is_empty() {
+        match writer.write(buf).await {
+            Ok(0) => {
+                return Err(WriteError {
+                    written: *written,
+                    source: std::io::ErrorKind::WriteZero.into(),
+                })
+            }
+            Ok(n) => {
+                *written += n;
+                buf = &buf[n..];
+            }
+            Err(source) => {
+                return Err(WriteError {
+                    written: *written,
+                    source,
+                })
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Frame data as one chunk of a chunked body
+fn chunk_frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = format!("{:x}\r\n", data.len()).into_bytes();
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(b"\r\n");
+    frame
+}
+
+/// # Human-readable representation of the response.
+///
+/// The status line and headers are written straight into the formatter, without building an
+/// intermediate `String`. The body is decoded with `String::from_utf8_lossy`, so any bytes that
+/// are not valid UTF-8 are replaced with `U+FFFD`.
+///
+/// This representation is **lossy** for binary bodies (images, gzip-compressed bodies, etc.) and
+/// is meant for logging and debugging only. Use [`Response::to_bytes_vec`] to serialize the
+/// response that is sent over the wire.
 impl Display for Response {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let headers = self.headers.iter().fold(String::new(), |mut acc, (k, v)| {
-            let _ = write!(acc, "{}: {}\r\n", k, v);
-            acc
-        });
+        write!(f, "{}", self.status_code)?;
+
+        for (k, v) in &self.headers {
+            write!(f, "{}: {}\r\n", k, v)?;
+        }
+
+        f.write_str("\r\n")?;
 
         if let Some(body) = &self.body {
-            write!(
-                f,
-                "{}{}\r\n{}",
-                self.status_code,
-                headers,
-                String::from_utf8_lossy(body)
-            )
-        } else {
-            write!(f, "{}{}\r\n", self.status_code, headers)
+            write!(f, "{}", String::from_utf8_lossy(body))?;
         }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::http::ResponseBuilder;
+    use crate::http::{Response, ResponseBuilder};
+    use std::io::Read;
+
+    /// Decode a chunked body, checking the framing
+    fn dechunk(mut data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        loop {
+            let line_end = data.windows(2).position(|w| w == b"\r\n").unwrap();
+            let size =
+                usize::from_str_radix(std::str::from_utf8(&data[..line_end]).unwrap(), 16).unwrap();
+            data = &data[line_end + 2..];
+
+            if size == 0 {
+                assert_eq!(data, b"\r\n", "Terminating chunk");
+                return body;
+            }
+
+            body.extend_from_slice(&data[..size]);
+            assert_eq!(&data[size..size + 2], b"\r\n", "Chunk terminator");
+            data = &data[size + 2..];
+        }
+    }
 
     #[test]
     fn test_response_to_bytes_vec() {
@@ -93,4 +380,273 @@ mod tests {
             "Response should be converted to string"
         );
     }
+
+    #[test]
+    fn test_sort_headers_canonically() {
+        let header = |name: &str, value: &str| (name.to_string(), value.to_string());
+        let mut response = Response {
+            headers: vec![
+                header("Content-Type", "text/plain"),
+                header("X-Custom", "1"),
+                header("ETag", "\"abc\""),
+                header("Vary", "Accept-Encoding"),
+                header("Content-Encoding", "gzip"),
+                header("X-Custom", "2"),
+                header("Date", "Thu, 01 Jan 1970 00:00:00 GMT"),
+                header("Content-Length", "5"),
+                header("connection", "close"),
+            ],
+            ..Default::default()
+        };
+
+        response.sort_headers_canonically();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                header("Date", "Thu, 01 Jan 1970 00:00:00 GMT"),
+                header("connection", "close"),
+                header("ETag", "\"abc\""),
+                header("Vary", "Accept-Encoding"),
+                header("Content-Type", "text/plain"),
+                header("Content-Encoding", "gzip"),
+                header("Content-Length", "5"),
+                header("X-Custom", "1"),
+                header("X-Custom", "2"),
+            ],
+            "General, response, content and other headers, each in insertion order"
+        );
+    }
+
+    #[test]
+    fn test_set_connection() {
+        let mut response = ResponseBuilder::ok()
+            .with(("Connection", "keep-alive"))
+            .build();
+        response.set_connection("close");
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Length".to_string(), "0".to_string()),
+                ("Connection".to_string(), "close".to_string()),
+            ],
+            "Connection replaced in place"
+        );
+
+        let mut response = ResponseBuilder::ok().build();
+        response.set_connection("close");
+        assert_eq!(
+            response.headers.last(),
+            Some(&("Connection".to_string(), "close".to_string())),
+            "Connection added"
+        );
+    }
+
+    #[test]
+    fn test_response_to_http09_bytes_vec() {
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .body("Hello, World!")
+            .build();
+
+        assert_eq!(
+            response.to_http09_bytes_vec(),
+            b"Hello, World!",
+            "HTTP/0.9 response is the bare body"
+        );
+        assert!(
+            ResponseBuilder::not_found()
+                .build()
+                .to_http09_bytes_vec()
+                .is_empty(),
+            "No body"
+        );
+    }
+
+    #[test]
+    fn test_binary_body_is_not_corrupted() {
+        // Gzip magic bytes followed by bytes that are not valid UTF-8
+        let body: Vec<u8> = vec![0x1f, 0x8b, 0x08, 0x00, 0xff, 0xfe, 0x00, 0x80];
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "application/octet-stream"))
+            .body(body.clone())
+            .build();
+
+        let bytes = response.to_bytes_vec();
+        let head = b"HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: 8\r\n\r\n";
+
+        assert_eq!(&bytes[..head.len()], head, "Status line and headers");
+        assert_eq!(
+            &bytes[head.len()..],
+            body.as_slice(),
+            "Binary body should be serialized byte for byte"
+        );
+
+        // The `Display` representation is lossy for the same body
+        assert!(
+            response.to_string().contains('\u{FFFD}'),
+            "Display should replace invalid UTF-8 with U+FFFD"
+        );
+    }
+
+    #[test]
+    fn test_chunked_response() {
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .body("Hello, World!")
+            .chunked()
+            .build();
+
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\nd\r\nHello, World!\r\n0\r\n\r\n",
+            "Chunked body without Content-Length"
+        );
+    }
+
+    #[test]
+    fn test_chunked_gzip_response() {
+        let body = b"Hello, World! Hello, World! Hello, World!";
+
+        let response = ResponseBuilder::ok()
+            .with(vec![
+                ("Content-Type", "text/plain"),
+                ("Content-Encoding", "gzip"),
+            ])
+            .body(body.as_slice())
+            .chunked()
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Encoding".to_string(), "gzip".to_string()),
+                ("Transfer-Encoding".to_string(), "chunked".to_string()),
+            ],
+            "Both encodings and no Content-Length"
+        );
+
+        let bytes = response.to_bytes_vec();
+        let head_len = bytes.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+
+        // The body is gzipped first and the compressed bytes are chunked, so the client
+        // de-chunks first and then gunzips
+        let compressed = dechunk(&bytes[head_len..]);
+        assert_eq!(
+            &compressed[..2],
+            [0x1f, 0x8b],
+            "Chunks carry the gzip stream"
+        );
+
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body, "De-chunked and gunzipped body");
+    }
+
+    #[tokio::test]
+    async fn test_flush_head() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll},
+        };
+
+        /// Writer recording how many bytes were written at every flush
+        #[derive(Default)]
+        struct FlushRecorder {
+            written: Vec<u8>,
+            flushes: Vec<usize>,
+        }
+
+        impl tokio::io::AsyncWrite for FlushRecorder {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                self.written.extend_from_slice(buf);
+                Poll::Ready(Ok(buf.len()))
+            }
+
+            fn poll_flush(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                let written = self.written.len();
+                self.flushes.push(written);
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let head_len = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n".len();
+
+        let mut writer = FlushRecorder::default();
+        let response = ResponseBuilder::ok().body("Hello").flush_head().build();
+        let written = response.write_to(&mut writer).await.unwrap();
+        assert_eq!(
+            writer.flushes,
+            [head_len, written],
+            "Flushed after the head and at the end"
+        );
+
+        let mut writer = FlushRecorder::default();
+        let response = ResponseBuilder::ok().body("Hello").build();
+        let written = response.write_to(&mut writer).await.unwrap();
+        assert_eq!(writer.flushes, [written], "Flushed at the end only");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_gzip_large_body() {
+        // 1 MiB of compressible but not trivially repetitive text
+        let body = (0..100_000)
+            .map(|i| format!("line {} of the body\n", i * 7919 % 100_003))
+            .collect::<String>()
+            .into_bytes();
+        assert!(body.len() > 1024 * 1024);
+
+        let response = ResponseBuilder::ok()
+            .with(vec![
+                ("Content-Type", "text/plain"),
+                ("Content-Encoding", "gzip"),
+            ])
+            .body(body.clone())
+            .chunked()
+            .build();
+
+        assert_eq!(
+            response.body.as_deref(),
+            Some(body.as_slice()),
+            "Body is compressed lazily"
+        );
+
+        let mut written: Vec<u8> = Vec::new();
+        response.write_to(&mut written).await.unwrap();
+
+        let head = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(&written[..head.len()], head, "Status line and headers");
+
+        let compressed = dechunk(&written[head.len()..]);
+        assert!(compressed.len() < body.len(), "Body is compressed");
+
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body, "De-chunked and gunzipped body");
+
+        assert_eq!(
+            response.to_bytes_vec(),
+            written,
+            "Same bytes from to_bytes_vec"
+        );
+    }
 }
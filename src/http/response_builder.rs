@@ -1,14 +1,25 @@
 use flate2::{write::GzEncoder, Compression};
 
-use super::{Response, StatusCode};
-use std::{default::Default, io::Write};
+use super::{format_http_date, BuildError, Response, StatusCode};
+use std::{
+    borrow::Cow,
+    default::Default,
+    io::{self, Write},
+    time::SystemTime,
+};
 
 #[derive(Clone, Debug)]
 pub struct ResponseBuilder<S> {
     status_code: S,
     headers: Option<Vec<(String, String)>>,
-    body: Option<Vec<u8>>,
+    /// Values of the framing headers, kept apart from the other headers
+    framing: FramingHeaders,
+    body: Option<Cow<'static, [u8]>>,
     set_content_length_header: bool,
+    chunked: bool,
+    /// The body is already encoded with the `Content-Encoding`
+    precompressed: bool,
+    flush_head: bool,
 }
 
 impl ResponseBuilder<MissingStatusCode> {
@@ -16,8 +27,12 @@ impl ResponseBuilder<MissingStatusCode> {
         ResponseBuilder {
             status_code: MissingStatusCode,
             headers: None,
+            framing: FramingHeaders::default(),
             body: None,
             set_content_length_header: true,
+            chunked: false,
+            precompressed: false,
+            flush_head: false,
         }
     }
 
@@ -25,8 +40,12 @@ impl ResponseBuilder<MissingStatusCode> {
         ResponseBuilder {
             status_code,
             headers: self.headers,
+            framing: self.framing,
             body: self.body,
             set_content_length_header: self.set_content_length_header,
+            chunked: self.chunked,
+            precompressed: self.precompressed,
+            flush_head: self.flush_head,
         }
     }
 
@@ -37,6 +56,65 @@ impl ResponseBuilder<MissingStatusCode> {
         }
     }
 
+    /// # Create a `201 Created` response pointing at the new resource.
+    ///
+    /// The `Location` header is set to the URI of the created resource.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// let response = ResponseBuilder::created("/files/report.txt").build();
+    ///
+    /// assert_eq!(response.headers[0], ("Location".to_string(), "/files/report.txt".to_string()));
+    /// ```
+    pub fn created(location: impl Into<String>) -> ResponseBuilder<StatusCode> {
+        ResponseBuilder {
+            status_code: StatusCode::Created,
+            ..Default::default()
+        }
+        .with(("Location".to_string(), location.into()))
+    }
+
+    /// # Create a `308 Permanent Redirect` response.
+    ///
+    /// The `Location` header is set to the new URI of the resource. Unlike
+    /// `301 Moved Permanently`, which clients may follow with a `GET`, the client must repeat
+    /// the request with the same method and body, so a redirected `POST` stays a `POST`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::{ResponseBuilder, StatusCode};
+    /// let response = ResponseBuilder::permanent_redirect("/v2/upload").build();
+    ///
+    /// assert_eq!(response.status_code, StatusCode::PermanentRedirect);
+    /// assert_eq!(response.headers[0], ("Location".to_string(), "/v2/upload".to_string()));
+    /// ```
+    pub fn permanent_redirect(location: impl Into<String>) -> ResponseBuilder<StatusCode> {
+        ResponseBuilder {
+            status_code: StatusCode::PermanentRedirect,
+            ..Default::default()
+        }
+        .with(("Location".to_string(), location.into()))
+    }
+
+    /// # Create a `101 Switching Protocols` response.
+    ///
+    /// Sets the `Connection: Upgrade` and `Upgrade` headers. The response has no body and no
+    /// `Content-Length` header, since the connection switches to the new protocol right after it.
+    pub fn switching_protocols(protocol: impl Into<String>) -> ResponseBuilder<StatusCode> {
+        ResponseBuilder {
+            status_code: StatusCode::SwitchingProtocols,
+            ..Default::default()
+        }
+        .with(vec![
+            ("Connection".to_string(), "Upgrade".to_string()),
+            ("Upgrade".to_string(), protocol.into()),
+        ])
+        .without_content_length_header()
+    }
+
     pub fn not_found() -> ResponseBuilder<StatusCode> {
         ResponseBuilder {
             status_code: StatusCode::NotFound,
@@ -60,48 +138,151 @@ impl ResponseBuilder<MissingStatusCode> {
 }
 
 impl ResponseBuilder<StatusCode> {
+    /// # Build the response.
+    ///
+    /// The builder is consumed: the headers and the body are moved into the response without
+    /// being copied. Only a gzip-encoded body is a new buffer.
+    ///
+    /// A chunked response gets a `Transfer-Encoding: chunked` header instead of `Content-Length`,
+    /// and its body is kept uncompressed: it's gzip-encoded chunk by chunk while it's written.
+    ///
+    /// A `Content-Encoding` the builder can't apply (anything but `gzip` and `identity`) is
+    /// removed, so the response never advertises an encoding its body doesn't have. A
+    /// [`precompressed`](ResponseBuilder::precompressed) body is sent as is. If gzip-encoding
+    /// the body fails, the failure is logged and the body sent uncompressed, without
+    /// `Content-Encoding`.
+    ///
+    /// The framing headers come after all other headers, always in the same order:
+    /// `Content-Encoding`, `Transfer-Encoding`, `Content-Length`, `Connection` and `Date`.
     pub fn build(self) -> Response {
+        self.build_with_encoder(gzip_encode)
+    }
+
+    /// Build the response, gzip-encoding the body with `encode`
+    fn build_with_encoder(self, encode: fn(&[u8]) -> io::Result<Vec<u8>>) -> Response {
         let mut headers = self.headers.unwrap_or_default();
+        let FramingHeaders {
+            content_encoding,
+            transfer_encoding,
+            connection,
+            date,
+        } = self.framing;
+
+        let mut content_encoding = content_encoding
+            .filter(|encoding| self.precompressed || is_supported_content_encoding(encoding));
+
+        // A precompressed body is complete, so it's never split into chunks
+        let chunked = self.chunked && !self.precompressed;
 
         // Check if the Content-Encoding header is set to "gzip"
-        let content_encoding_header = headers
-            .iter()
-            .find(|(k, v)| k == "Content-Encoding" && v == "gzip");
+        let gzip = !self.precompressed && content_encoding.as_deref() == Some("gzip");
 
         let encoded_body = match self.body {
-            Some(body) => match content_encoding_header {
+            Some(body) => match gzip && !chunked {
                 // If the Content-Encoding header is set to "gzip", encode the body
-                Some(_) => {
-                    let mut new_body = Vec::new();
-                    let mut encoder = GzEncoder::new(&mut new_body, Compression::default());
-                    encoder.write_all(&body).unwrap();
-                    encoder.finish().unwrap();
-                    Some(new_body)
-                }
-                // If the Content-Encoding header is not set to "gzip", return the body as is
-                None => Some(body),
+                true => match encode(&body) {
+                    Ok(new_body) => Some(Cow::Owned(new_body)),
+                    // An uncompressed response is better than none
+                    Err(err) => {
+                        eprintln!("Failed to gzip-encode the response body: {}", err);
+                        content_encoding = None;
+                        Some(body)
+                    }
+                },
+                // If the Content-Encoding header is not set to "gzip" or the body is streamed,
+                // return the body as is
+                false => Some(body),
             },
             // If there the body is None, return it as is
             None => None,
         };
 
-        // Calculate the Content-Length header value
-        let content_length = encoded_body.as_ref().map(|b| b.len()).unwrap_or(0);
+        let transfer_encoding = match chunked {
+            true => Some("chunked".to_string()),
+            false => transfer_encoding,
+        };
 
-        // Set the Content-Length header if the `without_content_length_header` method was not called
-        match self.set_content_length_header {
-            false => (), // No Content-Length header for empty bodies
-            true => headers.push(("Content-Length".to_string(), content_length.to_string())),
-        }
+        // Set the Content-Length header if the `without_content_length_header` method was not
+        // called and the body is not chunked
+        let content_length = (!chunked && self.set_content_length_header).then(|| {
+            encoded_body
+                .as_ref()
+                .map_or(0, |body| body.len())
+                .to_string()
+        });
+
+        let framing = [
+            ("Content-Encoding", content_encoding),
+            ("Transfer-Encoding", transfer_encoding),
+            ("Content-Length", content_length),
+            ("Connection", connection),
+            ("Date", date),
+        ];
+
+        headers.extend(
+            framing
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value?))),
+        );
 
         Response {
             status_code: self.status_code,
             headers,
             body: encoded_body,
+            chunked,
+            flush_head: self.flush_head,
         }
     }
 }
 
+impl ResponseBuilder<StatusCode> {
+    /// # Build the response, checking that its parts don't conflict.
+    ///
+    /// Unlike [`build`](ResponseBuilder::build), which builds whatever was set, this returns an
+    /// error for a response that would be malformed on the wire: a body or a chunked body on a
+    /// status code without a body, a manual `Transfer-Encoding` header next to the
+    /// `Content-Length` header, or a `Content-Encoding` the builder can't apply.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::{BuildError, ResponseBuilder, StatusCode};
+    /// let result = ResponseBuilder::new()
+    ///     .with_status_code(StatusCode::NoContent)
+    ///     .body("Hello, world!")
+    ///     .try_build();
+    ///
+    /// assert_eq!(result, Err(BuildError::BodyNotAllowed(StatusCode::NoContent)));
+    /// ```
+    pub fn try_build(self) -> Result<Response, BuildError> {
+        if !self.status_code.allows_body() {
+            if self.chunked {
+                return Err(BuildError::ChunkedNotAllowed(self.status_code));
+            }
+
+            if self.body.is_some() {
+                return Err(BuildError::BodyNotAllowed(self.status_code));
+            }
+        }
+
+        if let Some(encoding) = self
+            .header_value("Content-Encoding")
+            .filter(|encoding| !self.precompressed && !is_supported_content_encoding(encoding))
+        {
+            return Err(BuildError::UnsupportedContentEncoding(encoding.to_string()));
+        }
+
+        if !self.chunked
+            && self.set_content_length_header
+            && self.header_value("Transfer-Encoding").is_some()
+        {
+            return Err(BuildError::ConflictingFraming);
+        }
+
+        Ok(self.build())
+    }
+}
+
 impl<S> ResponseBuilder<S> {
     /// # Set header(s) on the response.
     ///
@@ -116,6 +297,10 @@ impl<S> ResponseBuilder<S> {
     /// Do not set the `Content-Length` header manually. It is calculated automatically based on
     /// the body length.
     ///
+    /// The framing headers (`Content-Encoding`, `Transfer-Encoding`, `Connection` and `Date`)
+    /// are kept apart from the other headers: setting one again replaces its value instead of
+    /// adding a second header, and they're sent after the other headers.
+    ///
     /// # Example
     ///
     /// ```
@@ -145,32 +330,70 @@ impl<S> ResponseBuilder<S> {
         }
     }
 
+    /// # Get the value of a header set on the response.
+    ///
+    /// Header names are case-insensitive. If the header is set multiple times, the first value
+    /// is returned.
+    pub fn header_value(&self, key: &str) -> Option<&str> {
+        if let Some(value) = self.framing.get(key) {
+            return value.as_deref();
+        }
+
+        self.headers
+            .as_ref()?
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
     fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        let key: String = key.into();
+        self.set_header(key.into(), value.into());
+        self
+    }
 
-        if key.as_str() != "Content-Length" {
-            if let Some(ref mut headers) = self.headers {
-                headers.push((key, value.into()));
-            } else {
-                self.headers = Some(vec![(key, value.into())]);
-            }
+    /// Store a framing header apart, skip `Content-Length` and append any other header
+    fn set_header(&mut self, key: String, value: String) {
+        if key.eq_ignore_ascii_case("Content-Length") {
+            return;
         }
 
-        self
+        match self.framing.get_mut(&key) {
+            Some(framing_value) => *framing_value = Some(value),
+            None => self.headers.get_or_insert_with(Vec::new).push((key, value)),
+        }
     }
 
-    fn headers(mut self, headers: Vec<(impl Into<String>, impl Into<String>)>) -> Self {
-        // Filter out the Content-Length header and convert headers' keys and values to owned strings
-        let headers = headers
-            .into_iter()
-            .map(|(key, value)| -> (String, String) { (key.into(), value.into()) })
-            .filter(|(key, _)| key.clone().as_str() != "Content-Length")
-            .collect::<Vec<_>>();
+    /// # Set headers from an iterator of owned name/value pairs.
+    ///
+    /// Unlike [`with`](ResponseBuilder::with), this accepts any iterator, so headers can be taken
+    /// straight from a map without collecting them into a `Vec` first. The `Content-Length`
+    /// header is skipped, as with `with`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// # use std::collections::BTreeMap;
+    /// let headers = BTreeMap::from([
+    ///     ("Content-Type".to_string(), "text/plain".to_string()),
+    ///     ("X-Custom-Header".to_string(), "value".to_string()),
+    /// ]);
+    ///
+    /// let response = ResponseBuilder::ok().with_headers(headers).build();
+    ///
+    /// assert_eq!(response.headers[0], ("Content-Type".to_string(), "text/plain".to_string()));
+    /// ```
+    pub fn with_headers(self, headers: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.headers(headers)
+    }
 
-        if let Some(ref mut existing_headers) = self.headers {
-            existing_headers.extend(headers); // Extend existing headers
-        } else {
-            self.headers = Some(headers); // Set the headers
+    fn headers(
+        mut self,
+        headers: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        // Convert headers' keys and values to owned strings, without cloning them on the way
+        for (key, value) in headers {
+            self.set_header(key.into(), value.into());
         }
 
         self
@@ -212,7 +435,136 @@ impl<S> ResponseBuilder<S> {
     /// assert_eq!(response.body, Some("Hello, world!".as_bytes().to_vec()));
     /// ```
     pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
-        self.body = Some(body.into());
+        self.body = Some(Cow::Owned(body.into()));
+        self
+    }
+
+    /// # Set a static body of the response, without copying it.
+    ///
+    /// Unlike [`body`](ResponseBuilder::body), the bytes are borrowed until the response is
+    /// written, which suits embedded assets and constant responses. A body that gets
+    /// gzip-encoded is a new buffer anyway.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// static PAGE: &[u8] = b"<h1>Hello</h1>";
+    ///
+    /// let response = ResponseBuilder::ok().static_body(PAGE).build();
+    ///
+    /// assert_eq!(response.body.as_deref(), Some(PAGE));
+    /// ```
+    pub fn static_body(mut self, body: &'static [u8]) -> Self {
+        self.body = Some(Cow::Borrowed(body));
+        self
+    }
+
+    /// # Set the `Expires` header.
+    ///
+    /// The time is formatted as an HTTP date (IMF-fixdate). Caches prefer `Cache-Control:
+    /// max-age` when both are set; `Expires` is for the ones that only understand HTTP/1.0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let response = ResponseBuilder::ok()
+    ///     .expires(UNIX_EPOCH + Duration::from_secs(784111777))
+    ///     .build();
+    ///
+    /// assert_eq!(response.headers[0], ("Expires".to_string(), "Sun, 06 Nov 1994 08:49:37 GMT".to_string()));
+    /// ```
+    pub fn expires(self, time: SystemTime) -> Self {
+        self.header("Expires", format_http_date(time))
+    }
+
+    /// # Add a `Warning` header.
+    ///
+    /// Formatted as `<code> <agent> "<text>"`, with `code` a three-digit warn-code between
+    /// `100` and `999` (e.g. `110` for a stale response, `214` for a transformed one, `199` for
+    /// anything else) and `agent` the host adding the warning, or `-` if it's unknown. Quotes
+    /// and backslashes in the text are escaped. Every call adds another `Warning` header.
+    ///
+    /// # Panics
+    ///
+    /// If `code` isn't three digits, i.e. not between `100` and `999`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// let response = ResponseBuilder::ok()
+    ///     .warning(199, "-", "Misc warning")
+    ///     .build();
+    ///
+    /// assert_eq!(
+    ///     response.headers[0],
+    ///     ("Warning".to_string(), "199 - \"Misc warning\"".to_string())
+    /// );
+    /// ```
+    pub fn warning(self, code: u16, agent: &str, text: &str) -> Self {
+        assert!(
+            (100..=999).contains(&code),
+            "Warn-code {} is not three digits",
+            code
+        );
+        let text = text.replace('\\', "\\\\").replace('"', "\\\"");
+
+        self.header("Warning", format!("{} {} \"{}\"", code, agent, text))
+    }
+
+    /// Length of the body set on the response, before any encoding
+    pub fn body_len(&self) -> usize {
+        self.body.as_ref().map_or(0, |body| body.len())
+    }
+
+    /// # Send a body that is already encoded.
+    ///
+    /// Sets the `Content-Encoding` header to `encoding`, which may be any content coding, and
+    /// sends the body as is instead of encoding it in [`build`](ResponseBuilder::build). Meant
+    /// for files stored compressed. The body is always sent with `Content-Length`, even if
+    /// [`chunked`](ResponseBuilder::chunked) is called.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use http::ResponseBuilder;
+    /// let response = ResponseBuilder::ok()
+    ///     .precompressed("br")
+    ///     .body(vec![0x0b, 0x02, 0x80, 0x68, 0x69, 0x03])
+    ///     .build();
+    ///
+    /// assert_eq!(response.headers[0], ("Content-Encoding".to_string(), "br".to_string()));
+    /// ```
+    pub fn precompressed(mut self, encoding: impl Into<String>) -> Self {
+        self.framing.content_encoding = Some(encoding.into());
+        self.precompressed = true;
+        self
+    }
+
+    /// # Flush the status line and headers before the body is written.
+    ///
+    /// The head then leaves right away instead of being held back by the stream's buffering
+    /// (or Nagle's algorithm on TCP) until the body follows, so the client can start processing
+    /// the response early. Meant for streamed responses; bulk transfers are better off without
+    /// the extra flush.
+    pub fn flush_head(mut self) -> Self {
+        self.flush_head = true;
+        self
+    }
+
+    /// # Send the body with `Transfer-Encoding: chunked`.
+    ///
+    /// The response gets no `Content-Length` header. A gzip-encoded body is compressed
+    /// incrementally while the response is written, one chunk at a time, instead of being
+    /// compressed as a whole in [`build`](ResponseBuilder::build).
+    ///
+    /// The transfer coding is applied last: the chunks carry the gzip stream, so clients
+    /// de-chunk the body first and then decompress it.
+    pub fn chunked(mut self) -> Self {
+        self.chunked = true;
         self
     }
 }
@@ -222,10 +574,63 @@ impl Default for ResponseBuilder<StatusCode> {
         ResponseBuilder {
             status_code: StatusCode::Ok,
             headers: None,
+            framing: FramingHeaders::default(),
             body: None,
             set_content_length_header: true,
+            chunked: false,
+            precompressed: false,
+            flush_head: false,
+        }
+    }
+}
+
+/// # Headers the server manages itself.
+///
+/// They describe how the message is encoded and framed, and what happens to the connection,
+/// rather than the content. `Content-Length` isn't stored at all: it's always calculated.
+#[derive(Clone, Debug, Default)]
+struct FramingHeaders {
+    content_encoding: Option<String>,
+    transfer_encoding: Option<String>,
+    connection: Option<String>,
+    date: Option<String>,
+}
+
+impl FramingHeaders {
+    /// Get the value of a framing header, `None` if the header isn't a framing header
+    fn get(&self, key: &str) -> Option<&Option<String>> {
+        match key.to_ascii_lowercase().as_str() {
+            "content-encoding" => Some(&self.content_encoding),
+            "transfer-encoding" => Some(&self.transfer_encoding),
+            "connection" => Some(&self.connection),
+            "date" => Some(&self.date),
+            _ => None,
         }
     }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Option<String>> {
+        match key.to_ascii_lowercase().as_str() {
+            "content-encoding" => Some(&mut self.content_encoding),
+            "transfer-encoding" => Some(&mut self.transfer_encoding),
+            "connection" => Some(&mut self.connection),
+            "date" => Some(&mut self.date),
+            _ => None,
+        }
+    }
+}
+
+/// Gzip-encode a response body
+fn gzip_encode(body: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+/// Content codings `build` can apply to the body
+const SUPPORTED_CONTENT_ENCODINGS: [&str; 2] = ["gzip", "identity"];
+
+fn is_supported_content_encoding(encoding: &str) -> bool {
+    SUPPORTED_CONTENT_ENCODINGS.contains(&encoding)
 }
 
 // Auxiliary type to represent a missing status code in the builder
@@ -293,6 +698,118 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_response_builder_created() {
+        let response = ResponseBuilder::created("/files/test.txt").build();
+        assert_eq!(
+            response.status_code,
+            StatusCode::Created,
+            "Status code should be 201 Created"
+        );
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Location".to_string(), "/files/test.txt".to_string()),
+                ("Content-Length".to_string(), "0".to_string())
+            ],
+            "Location header should point at the created resource"
+        );
+    }
+
+    #[test]
+    fn test_response_builder_permanent_redirect() {
+        let response = ResponseBuilder::permanent_redirect("/v2/upload").build();
+        assert_eq!(
+            response.status_code,
+            StatusCode::PermanentRedirect,
+            "Status code should be 308 Permanent Redirect"
+        );
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 308 Permanent Redirect\r\nLocation: /v2/upload\r\nContent-Length: 0\r\n\r\n",
+            "Location header should point at the new URI"
+        );
+    }
+
+    #[test]
+    fn test_response_builder_switching_protocols() {
+        let response = ResponseBuilder::switching_protocols("raw-echo").build();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: raw-echo\r\nConnection: Upgrade\r\n\r\n",
+            "Upgrade headers without Content-Length"
+        );
+    }
+
+    #[test]
+    fn test_framing_headers() {
+        //======================================================================
+        // Test for a manual Content-Length header
+        let response = ResponseBuilder::ok()
+            .with(vec![("Content-Length", "100"), ("content-length", "200")])
+            .body("Hello")
+            .build();
+        assert_eq!(
+            response.headers,
+            vec![("Content-Length".to_string(), "5".to_string())],
+            "Single calculated Content-Length"
+        );
+
+        //======================================================================
+        // Test for a Connection header set multiple times
+        let response = ResponseBuilder::ok()
+            .with(("Connection", "keep-alive"))
+            .with(("Content-Type", "text/plain"))
+            .with(("connection", "close"))
+            .build();
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), "0".to_string()),
+                ("Connection".to_string(), "close".to_string()),
+            ],
+            "Last Connection value wins, after the other headers"
+        );
+        assert_eq!(
+            ResponseBuilder::ok()
+                .with(("Connection", "keep-alive"))
+                .with(("Connection", "close"))
+                .header_value("Connection"),
+            Some("close"),
+            "Framing header value"
+        );
+
+        //======================================================================
+        // Test for the position of the framing headers
+        let response = ResponseBuilder::ok()
+            .with(vec![
+                ("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ("Connection", "close"),
+                ("Content-Encoding", "identity"),
+                ("X-Custom", "value"),
+            ])
+            .body("Hello")
+            .build();
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\nX-Custom: value\r\nContent-Encoding: identity\r\n\
+             Content-Length: 5\r\nConnection: close\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\nHello",
+            "Framing headers last, in a fixed order"
+        );
+
+        let response = ResponseBuilder::ok()
+            .with(("Transfer-Encoding", "gzip"))
+            .body("Hello")
+            .chunked()
+            .build();
+        assert_eq!(
+            response.headers,
+            vec![("Transfer-Encoding".to_string(), "chunked".to_string())],
+            "Single Transfer-Encoding on a chunked response"
+        );
+    }
+
     #[test]
     fn test_response_builder_not_found() {
         let response = ResponseBuilder::not_found().build();
@@ -388,6 +905,77 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_build_moves_body_and_headers() {
+        let body = b"Hello, world!".to_vec();
+        let body_ptr = body.as_ptr();
+
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .with(vec![("X-Custom", "value"), ("Content-Length", "999")])
+            .body(body)
+            .build();
+
+        assert_eq!(
+            response.body.as_ref().map(|body| body.as_ptr()),
+            Some(body_ptr),
+            "Body buffer should be moved into the response, not copied"
+        );
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("X-Custom".to_string(), "value".to_string()),
+                ("Content-Length".to_string(), "13".to_string()),
+            ],
+            "Headers should keep their order and the manual Content-Length is ignored"
+        );
+    }
+
+    #[test]
+    fn test_static_body() {
+        static PAGE: &[u8] = b"<h1>Hello</h1>";
+
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/html"))
+            .static_body(PAGE)
+            .build();
+
+        assert!(
+            matches!(&response.body, Some(Cow::Borrowed(body)) if body.as_ptr() == PAGE.as_ptr()),
+            "Static body should be borrowed, not copied"
+        );
+        assert_eq!(
+            response.to_bytes_vec(),
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 14\r\n\r\n<h1>Hello</h1>",
+            "Static body serialized as is"
+        );
+
+        // Encoding the body makes a new buffer
+        let response = ResponseBuilder::ok()
+            .with(("Content-Encoding", "gzip"))
+            .static_body(PAGE)
+            .build();
+
+        let mut decoder = flate2::read::GzDecoder::new(response.body.as_deref().unwrap());
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, PAGE, "Static body gzip-encoded");
+    }
+    #[test]
+    fn test_header_value() {
+        let builder = ResponseBuilder::ok().with(vec![
+            ("Content-Type", "text/html"),
+            ("X-Custom", "first"),
+            ("X-Custom", "second"),
+        ]);
+
+        assert_eq!(builder.header_value("content-type"), Some("text/html"));
+        assert_eq!(builder.header_value("X-Custom"), Some("first"));
+        assert_eq!(builder.header_value("Location"), None);
+        assert_eq!(ResponseBuilder::ok().header_value("Content-Type"), None);
+    }
+
     #[test]
     fn test_response_builder_body() {
         let body = "Hello, world!";
@@ -398,4 +986,302 @@ mod tests {
             "Body should be set to the given value"
         );
     }
+
+    #[test]
+    fn test_try_build() {
+        //======================================================================
+        // Test for a response without conflicts
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .body("Hello")
+            .try_build();
+        assert_eq!(
+            response,
+            Ok(ResponseBuilder::ok()
+                .with(("Content-Type", "text/plain"))
+                .body("Hello")
+                .build()),
+            "Same response as build()"
+        );
+
+        let response = ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .without_content_length_header()
+            .try_build();
+        assert!(response.is_ok(), "204 without body");
+
+        //======================================================================
+        // Test for a body on a status code without body
+        let response = ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .body("Hello")
+            .try_build();
+        assert_eq!(
+            response,
+            Err(BuildError::BodyNotAllowed(StatusCode::NoContent)),
+            "Body on 204"
+        );
+
+        let response = ResponseBuilder::switching_protocols("websocket")
+            .body("Hello")
+            .try_build();
+        assert_eq!(
+            response,
+            Err(BuildError::BodyNotAllowed(StatusCode::SwitchingProtocols)),
+            "Body on 101"
+        );
+
+        //======================================================================
+        // Test for a chunked body on a status code without body
+        let response = ResponseBuilder::new()
+            .with_status_code(StatusCode::NoContent)
+            .chunked()
+            .try_build();
+        assert_eq!(
+            response,
+            Err(BuildError::ChunkedNotAllowed(StatusCode::NoContent)),
+            "Chunked 204"
+        );
+
+        //======================================================================
+        // Test for both Transfer-Encoding and Content-Length
+        let response = ResponseBuilder::ok()
+            .with(("Transfer-Encoding", "chunked"))
+            .body("Hello")
+            .try_build();
+        assert_eq!(
+            response,
+            Err(BuildError::ConflictingFraming),
+            "Manual Transfer-Encoding with Content-Length"
+        );
+
+        let response = ResponseBuilder::ok()
+            .with(("Transfer-Encoding", "chunked"))
+            .without_content_length_header()
+            .try_build();
+        assert!(
+            response.is_ok(),
+            "Manual Transfer-Encoding without Content-Length"
+        );
+
+        //======================================================================
+        // Test for an encoding the builder can't apply
+        let response = ResponseBuilder::ok()
+            .with(("Content-Encoding", "compress"))
+            .body("Hello")
+            .try_build();
+        assert_eq!(
+            response,
+            Err(BuildError::UnsupportedContentEncoding(
+                "compress".to_string()
+            )),
+            "Unsupported Content-Encoding"
+        );
+    }
+
+    #[test]
+    fn test_with_headers_from_iterator() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let headers = BTreeMap::from([
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("Content-Length".to_string(), "100".to_string()),
+            ("X-Custom-Header".to_string(), "value".to_string()),
+        ]);
+
+        let response = ResponseBuilder::ok()
+            .with(("Cache-Control", "no-cache"))
+            .with_headers(headers)
+            .body("Hello")
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Cache-Control".to_string(), "no-cache".to_string()),
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("X-Custom-Header".to_string(), "value".to_string()),
+                ("Content-Length".to_string(), "5".to_string()),
+            ],
+            "Headers appended in iteration order, Content-Length calculated"
+        );
+
+        let headers = HashMap::from([("X-One".to_string(), "1".to_string())]);
+        let response = ResponseBuilder::ok()
+            .with_headers(headers.into_iter().map(|(k, v)| (k, v + "0")))
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("X-One".to_string(), "10".to_string()),
+                ("Content-Length".to_string(), "0".to_string()),
+            ],
+            "Headers from a mapped iterator"
+        );
+    }
+
+    #[test]
+    fn test_build_strips_unsupported_content_encoding() {
+        let response = ResponseBuilder::ok()
+            .with(vec![
+                ("Content-Type", "text/plain"),
+                ("Content-Encoding", "compress"),
+            ])
+            .body("Hello")
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), "5".to_string()),
+            ],
+            "Unsupported Content-Encoding removed"
+        );
+        assert_eq!(
+            response.body,
+            Some(b"Hello".to_vec().into()),
+            "Body sent as is"
+        );
+
+        let response = ResponseBuilder::ok()
+            .with(("Content-Encoding", "identity"))
+            .body("Hello")
+            .build();
+
+        assert_eq!(
+            response.headers[0],
+            ("Content-Encoding".to_string(), "identity".to_string()),
+            "Supported Content-Encoding kept"
+        );
+    }
+
+    #[test]
+    fn test_build_falls_back_when_encoding_fails() {
+        let failing_encoder = |_: &[u8]| Err(io::Error::other("encoder failure"));
+
+        let response = ResponseBuilder::ok()
+            .with(vec![
+                ("Content-Type", "text/plain"),
+                ("Content-Encoding", "gzip"),
+            ])
+            .body("Hello")
+            .build_with_encoder(failing_encoder);
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), "5".to_string()),
+            ],
+            "Content-Encoding dropped"
+        );
+        assert_eq!(
+            response.body.as_deref(),
+            Some(&b"Hello"[..]),
+            "Body sent uncompressed"
+        );
+    }
+
+    #[test]
+    fn test_precompressed() {
+        let body = vec![0x1f, 0x8b, 0x08, 0x00];
+
+        let response = ResponseBuilder::ok()
+            .with(("Content-Type", "text/plain"))
+            .precompressed("gzip")
+            .body(body.clone())
+            .chunked()
+            .build();
+        assert_eq!(response.body, Some(body.into()), "Gzip body sent as is");
+        assert!(!response.chunked, "Not chunked");
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Encoding".to_string(), "gzip".to_string()),
+                ("Content-Length".to_string(), "4".to_string()),
+            ],
+            "Precompressed gzip body"
+        );
+
+        let response = ResponseBuilder::ok()
+            .precompressed("br")
+            .body("abc")
+            .try_build()
+            .unwrap();
+        assert_eq!(
+            response.headers[0],
+            ("Content-Encoding".to_string(), "br".to_string()),
+            "Encoding the builder can't apply kept"
+        );
+    }
+
+    #[test]
+    fn test_expires() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let response = ResponseBuilder::ok()
+            .with(("Cache-Control", "max-age=3600"))
+            .expires(UNIX_EPOCH + Duration::from_secs(784_111_777))
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Cache-Control".to_string(), "max-age=3600".to_string()),
+                (
+                    "Expires".to_string(),
+                    "Sun, 06 Nov 1994 08:49:37 GMT".to_string()
+                ),
+                ("Content-Length".to_string(), "0".to_string()),
+            ],
+            "Expires formatted as IMF-fixdate"
+        );
+    }
+
+    #[test]
+    fn test_warning() {
+        //======================================================================
+        // Test for a single warning
+        let response = ResponseBuilder::ok()
+            .warning(199, "-", "Misc warning")
+            .build();
+
+        assert_eq!(
+            response.to_string(),
+            "HTTP/1.1 200 OK\r\nWarning: 199 - \"Misc warning\"\r\nContent-Length: 0\r\n\r\n",
+            "One warning"
+        );
+
+        //======================================================================
+        // Test for multiple warnings
+        let response = ResponseBuilder::ok()
+            .warning(110, "cache.example:8080", "Response is Stale")
+            .warning(214, "-", r#"Transformed by "proxy" \ gzip"#)
+            .build();
+
+        assert_eq!(
+            response.headers,
+            vec![
+                (
+                    "Warning".to_string(),
+                    "110 cache.example:8080 \"Response is Stale\"".to_string()
+                ),
+                (
+                    "Warning".to_string(),
+                    r#"214 - "Transformed by \"proxy\" \\ gzip""#.to_string()
+                ),
+                ("Content-Length".to_string(), "0".to_string()),
+            ],
+            "Two warnings, in order, with the text escaped"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not three digits")]
+    fn test_warning_invalid_code() {
+        let _ = ResponseBuilder::ok().warning(1000, "-", "Too long");
+    }
 }
@@ -1,24 +1,153 @@
 use std::fmt::{Display, Result as FmtResult};
 
-#[derive(Debug, PartialEq, Clone, Copy, Eq, Default)]
-pub enum StatusCode {
+/// Define the status codes together with their reason phrases, so the enum, the table of reason
+/// phrases and [`StatusCode::from_u16`] can't get out of sync
+macro_rules! status_codes {
+    ($($(#[$attr:meta])* $name:ident = $code:literal => $phrase:literal,)+) => {
+        #[derive(Debug, PartialEq, Clone, Copy, Eq, Default)]
+        pub enum StatusCode {
+            $($(#[$attr])* $name = $code,)+
+        }
+
+        /// Reason phrases of the status codes registered with IANA
+        const REASON_PHRASES: &[(u16, &str)] = &[$(($code, $phrase),)+];
+
+        impl StatusCode {
+            /// # Get the status code with the given number.
+            ///
+            /// Every code registered with IANA has one; returns `None` for other numbers.
+            pub fn from_u16(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(StatusCode::$name),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100 => "Continue",
+    SwitchingProtocols = 101 => "Switching Protocols",
+    Processing = 102 => "Processing",
+    EarlyHints = 103 => "Early Hints",
     #[default]
-    Ok = 200,
-    Created = 201,
-    BadRequest = 400,
-    NotFound = 404,
-    InternalServerError = 500,
+    Ok = 200 => "OK",
+    Created = 201 => "Created",
+    Accepted = 202 => "Accepted",
+    NonAuthoritativeInformation = 203 => "Non-Authoritative Information",
+    NoContent = 204 => "No Content",
+    ResetContent = 205 => "Reset Content",
+    PartialContent = 206 => "Partial Content",
+    MultiStatus = 207 => "Multi-Status",
+    AlreadyReported = 208 => "Already Reported",
+    ImUsed = 226 => "IM Used",
+    MultipleChoices = 300 => "Multiple Choices",
+    MovedPermanently = 301 => "Moved Permanently",
+    Found = 302 => "Found",
+    SeeOther = 303 => "See Other",
+    NotModified = 304 => "Not Modified",
+    UseProxy = 305 => "Use Proxy",
+    TemporaryRedirect = 307 => "Temporary Redirect",
+    PermanentRedirect = 308 => "Permanent Redirect",
+    BadRequest = 400 => "Bad Request",
+    Unauthorized = 401 => "Unauthorized",
+    PaymentRequired = 402 => "Payment Required",
+    Forbidden = 403 => "Forbidden",
+    NotFound = 404 => "Not Found",
+    MethodNotAllowed = 405 => "Method Not Allowed",
+    NotAcceptable = 406 => "Not Acceptable",
+    ProxyAuthenticationRequired = 407 => "Proxy Authentication Required",
+    RequestTimeout = 408 => "Request Timeout",
+    Conflict = 409 => "Conflict",
+    Gone = 410 => "Gone",
+    LengthRequired = 411 => "Length Required",
+    PreconditionFailed = 412 => "Precondition Failed",
+    PayloadTooLarge = 413 => "Payload Too Large",
+    UriTooLong = 414 => "URI Too Long",
+    UnsupportedMediaType = 415 => "Unsupported Media Type",
+    RangeNotSatisfiable = 416 => "Range Not Satisfiable",
+    ExpectationFailed = 417 => "Expectation Failed",
+    MisdirectedRequest = 421 => "Misdirected Request",
+    UnprocessableContent = 422 => "Unprocessable Content",
+    Locked = 423 => "Locked",
+    FailedDependency = 424 => "Failed Dependency",
+    TooEarly = 425 => "Too Early",
+    UpgradeRequired = 426 => "Upgrade Required",
+    PreconditionRequired = 428 => "Precondition Required",
+    TooManyRequests = 429 => "Too Many Requests",
+    RequestHeaderFieldsTooLarge = 431 => "Request Header Fields Too Large",
+    UnavailableForLegalReasons = 451 => "Unavailable For Legal Reasons",
+    InternalServerError = 500 => "Internal Server Error",
+    NotImplemented = 501 => "Not Implemented",
+    BadGateway = 502 => "Bad Gateway",
+    ServiceUnavailable = 503 => "Service Unavailable",
+    GatewayTimeout = 504 => "Gateway Timeout",
+    HttpVersionNotSupported = 505 => "HTTP Version Not Supported",
+    VariantAlsoNegotiates = 506 => "Variant Also Negotiates",
+    InsufficientStorage = 507 => "Insufficient Storage",
+    LoopDetected = 508 => "Loop Detected",
+    NotExtended = 510 => "Not Extended",
+    NetworkAuthenticationRequired = 511 => "Network Authentication Required",
 }
 
 impl StatusCode {
+    /// Canonical reason phrase of the status code
     pub fn message(&self) -> &'static str {
-        match self {
-            StatusCode::Ok => "OK",
-            StatusCode::Created => "Created",
-            StatusCode::BadRequest => "Bad Request",
-            StatusCode::NotFound => "Not Found",
-            StatusCode::InternalServerError => "Internal Server Error",
-        }
+        reason_phrase(*self as u16)
+    }
+
+    /// # Whether a response with this status code may have a body.
+    ///
+    /// Informational (`1xx`), `204 No Content` and `304 Not Modified` responses never have a
+    /// body.
+    pub fn allows_body(&self) -> bool {
+        !matches!(*self as u16, 100..=199 | 204 | 304)
+    }
+
+    /// Whether the status code is informational (`1xx`)
+    pub fn is_informational(&self) -> bool {
+        (100..=199).contains(&(*self as u16))
+    }
+
+    /// Whether the status code is successful (`2xx`)
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&(*self as u16))
+    }
+
+    /// Whether the status code is a redirection (`3xx`), including `304 Not Modified`
+    pub fn is_redirect(&self) -> bool {
+        (300..=399).contains(&(*self as u16))
+    }
+
+    /// Whether the status code is a client error (`4xx`)
+    pub fn is_client_error(&self) -> bool {
+        (400..=499).contains(&(*self as u16))
+    }
+
+    /// Whether the status code is a server error (`5xx`)
+    pub fn is_server_error(&self) -> bool {
+        (500..=599).contains(&(*self as u16))
+    }
+}
+
+/// # Get the reason phrase of a numeric status code.
+///
+/// Registered codes get their IANA reason phrase. Unregistered ones fall back to the name of
+/// their class (e.g. `"Client Error"` for `499`), and numbers outside `100..=599` to
+/// `"Unknown"`.
+pub fn reason_phrase(code: u16) -> &'static str {
+    if let Ok(index) = REASON_PHRASES.binary_search_by_key(&code, |&(code, _)| code) {
+        return REASON_PHRASES[index].1;
+    }
+
+    match code {
+        100..=199 => "Informational",
+        200..=299 => "Success",
+        300..=399 => "Redirection",
+        400..=499 => "Client Error",
+        500..=599 => "Server Error",
+        _ => "Unknown",
     }
 }
 
@@ -34,6 +163,11 @@ mod tests {
 
     #[test]
     fn status_code_message() {
+        assert_eq!(
+            StatusCode::SwitchingProtocols.message(),
+            "Switching Protocols",
+            "Status code 101 should be Switching Protocols"
+        );
         assert_eq!(
             StatusCode::Ok.message(),
             "OK",
@@ -44,25 +178,100 @@ mod tests {
             "Created",
             "Status code 201 should be Created"
         );
+        assert_eq!(
+            StatusCode::NoContent.message(),
+            "No Content",
+            "Status code 204 should be No Content"
+        );
+        assert_eq!(
+            StatusCode::PartialContent.message(),
+            "Partial Content",
+            "Status code 206 should be Partial Content"
+        );
+        assert_eq!(
+            StatusCode::NotModified.message(),
+            "Not Modified",
+            "Status code 304 should be Not Modified"
+        );
+        assert_eq!(
+            StatusCode::PermanentRedirect.message(),
+            "Permanent Redirect",
+            "Status code 308 should be Permanent Redirect"
+        );
         assert_eq!(
             StatusCode::BadRequest.message(),
             "Bad Request",
             "Status code 400 should be Bad Request"
         );
+        assert_eq!(
+            StatusCode::Unauthorized.message(),
+            "Unauthorized",
+            "Status code 401 should be Unauthorized"
+        );
+        assert_eq!(
+            StatusCode::Forbidden.message(),
+            "Forbidden",
+            "Status code 403 should be Forbidden"
+        );
         assert_eq!(
             StatusCode::NotFound.message(),
             "Not Found",
             "Status code 404 should be Not Found"
         );
+        assert_eq!(
+            StatusCode::NotAcceptable.message(),
+            "Not Acceptable",
+            "Status code 406 should be Not Acceptable"
+        );
+        assert_eq!(
+            StatusCode::PayloadTooLarge.message(),
+            "Payload Too Large",
+            "Status code 413 should be Payload Too Large"
+        );
+        assert_eq!(
+            StatusCode::UriTooLong.message(),
+            "URI Too Long",
+            "Status code 414 should be URI Too Long"
+        );
+        assert_eq!(
+            StatusCode::UnsupportedMediaType.message(),
+            "Unsupported Media Type",
+            "Status code 415 should be Unsupported Media Type"
+        );
+        assert_eq!(
+            StatusCode::RangeNotSatisfiable.message(),
+            "Range Not Satisfiable",
+            "Status code 416 should be Range Not Satisfiable"
+        );
         assert_eq!(
             StatusCode::InternalServerError.message(),
             "Internal Server Error",
             "Status code 500 should be Internal Server Error"
         );
+        assert_eq!(
+            StatusCode::NotImplemented.message(),
+            "Not Implemented",
+            "Status code 501 should be Not Implemented"
+        );
+        assert_eq!(
+            StatusCode::ServiceUnavailable.message(),
+            "Service Unavailable",
+            "Status code 503 should be Service Unavailable"
+        );
+        assert_eq!(
+            StatusCode::GatewayTimeout.message(),
+            "Gateway Timeout",
+            "Status code 504 should be Gateway Timeout"
+        );
     }
 
     #[test]
     fn status_code_display() {
+        assert_eq!(
+            format!("{}", StatusCode::SwitchingProtocols),
+            "HTTP/1.1 101 Switching Protocols\r\n",
+            "Status code string 101 should be Switching Protocols"
+        );
         assert_eq!(
             format!("{}", StatusCode::Ok),
             "HTTP/1.1 200 OK\r\n",
@@ -73,20 +282,190 @@ mod tests {
             "HTTP/1.1 201 Created\r\n",
             "Status code string 201 should be Created"
         );
+        assert_eq!(
+            format!("{}", StatusCode::NoContent),
+            "HTTP/1.1 204 No Content\r\n",
+            "Status code string 204 should be No Content"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::PartialContent),
+            "HTTP/1.1 206 Partial Content\r\n",
+            "Status code string 206 should be Partial Content"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::NotModified),
+            "HTTP/1.1 304 Not Modified\r\n",
+            "Status code string 304 should be Not Modified"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::PermanentRedirect),
+            "HTTP/1.1 308 Permanent Redirect\r\n",
+            "Status code string 308 should be Permanent Redirect"
+        );
         assert_eq!(
             format!("{}", StatusCode::BadRequest),
             "HTTP/1.1 400 Bad Request\r\n",
             "Status code string 400 should be Bad Request"
         );
+        assert_eq!(
+            format!("{}", StatusCode::Unauthorized),
+            "HTTP/1.1 401 Unauthorized\r\n",
+            "Status code string 401 should be Unauthorized"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::Forbidden),
+            "HTTP/1.1 403 Forbidden\r\n",
+            "Status code string 403 should be Forbidden"
+        );
         assert_eq!(
             format!("{}", StatusCode::NotFound),
             "HTTP/1.1 404 Not Found\r\n",
             "Status code string 404 should be Not Found"
         );
+        assert_eq!(
+            format!("{}", StatusCode::NotAcceptable),
+            "HTTP/1.1 406 Not Acceptable\r\n",
+            "Status code string 406 should be Not Acceptable"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::PayloadTooLarge),
+            "HTTP/1.1 413 Payload Too Large\r\n",
+            "Status code string 413 should be Payload Too Large"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::UriTooLong),
+            "HTTP/1.1 414 URI Too Long\r\n",
+            "Status code string 414 should be URI Too Long"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::UnsupportedMediaType),
+            "HTTP/1.1 415 Unsupported Media Type\r\n",
+            "Status code string 415 should be Unsupported Media Type"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::RangeNotSatisfiable),
+            "HTTP/1.1 416 Range Not Satisfiable\r\n",
+            "Status code string 416 should be Range Not Satisfiable"
+        );
         assert_eq!(
             format!("{}", StatusCode::InternalServerError),
             "HTTP/1.1 500 Internal Server Error\r\n",
             "Status code string 500 should be Internal Server Error"
         );
+        assert_eq!(
+            format!("{}", StatusCode::NotImplemented),
+            "HTTP/1.1 501 Not Implemented\r\n",
+            "Status code string 501 should be Not Implemented"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::ServiceUnavailable),
+            "HTTP/1.1 503 Service Unavailable\r\n",
+            "Status code string 503 should be Service Unavailable"
+        );
+        assert_eq!(
+            format!("{}", StatusCode::GatewayTimeout),
+            "HTTP/1.1 504 Gateway Timeout\r\n",
+            "Status code string 504 should be Gateway Timeout"
+        );
+    }
+
+    #[test]
+    fn status_code_from_u16() {
+        assert_eq!(StatusCode::from_u16(200), Some(StatusCode::Ok), "200");
+        assert_eq!(StatusCode::from_u16(404), Some(StatusCode::NotFound), "404");
+        assert_eq!(
+            StatusCode::from_u16(503),
+            Some(StatusCode::ServiceUnavailable),
+            "503"
+        );
+        assert_eq!(StatusCode::from_u16(999), None, "Unknown code");
+        assert_eq!(StatusCode::from_u16(499), None, "Unregistered code");
+
+        for &(code, phrase) in REASON_PHRASES {
+            let status_code = StatusCode::from_u16(code).expect("Registered code");
+            assert_eq!(status_code as u16, code, "Code {}", code);
+            assert_eq!(status_code.message(), phrase, "Reason phrase of {}", code);
+        }
+    }
+
+    #[test]
+    fn status_code_allows_body() {
+        assert!(StatusCode::Ok.allows_body(), "200 may have a body");
+        assert!(StatusCode::NotFound.allows_body(), "404 may have a body");
+        assert!(
+            !StatusCode::SwitchingProtocols.allows_body(),
+            "101 has no body"
+        );
+        assert!(!StatusCode::NoContent.allows_body(), "204 has no body");
+        assert!(!StatusCode::NotModified.allows_body(), "304 has no body");
+    }
+
+    #[test]
+    fn status_code_categories() {
+        let cases = [
+            (StatusCode::SwitchingProtocols, "informational"),
+            (StatusCode::Ok, "success"),
+            (StatusCode::NoContent, "success"),
+            (StatusCode::NotModified, "redirect"),
+            (StatusCode::BadRequest, "client error"),
+            (StatusCode::NotFound, "client error"),
+            (StatusCode::UriTooLong, "client error"),
+            (StatusCode::InternalServerError, "server error"),
+            (StatusCode::ServiceUnavailable, "server error"),
+        ];
+
+        for (status_code, category) in cases {
+            let categories = [
+                ("informational", status_code.is_informational()),
+                ("success", status_code.is_success()),
+                ("redirect", status_code.is_redirect()),
+                ("client error", status_code.is_client_error()),
+                ("server error", status_code.is_server_error()),
+            ];
+
+            for (name, is_in) in categories {
+                assert_eq!(
+                    is_in,
+                    name == category,
+                    "Whether {} is a {} code",
+                    status_code as u16,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_reason_phrase() {
+        let cases = [
+            (100, "Continue"),
+            (200, "OK"),
+            (206, "Partial Content"),
+            (301, "Moved Permanently"),
+            (304, "Not Modified"),
+            (308, "Permanent Redirect"),
+            (401, "Unauthorized"),
+            (405, "Method Not Allowed"),
+            (414, "URI Too Long"),
+            (418, "Client Error"),
+            (429, "Too Many Requests"),
+            (431, "Request Header Fields Too Large"),
+            (501, "Not Implemented"),
+            (504, "Gateway Timeout"),
+            (511, "Network Authentication Required"),
+            (299, "Success"),
+            (599, "Server Error"),
+            (99, "Unknown"),
+            (600, "Unknown"),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(reason_phrase(code), expected, "Reason phrase of {}", code);
+        }
+
+        assert!(
+            REASON_PHRASES.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "Table sorted by code"
+        );
     }
 }
@@ -0,0 +1,69 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::http::{Request, Response};
+
+/// Hook called with every request before it's handled
+pub type RequestHook = Arc<dyn Fn(&mut Request) + Send + Sync>;
+
+/// Hook called with every response right before it's written, together with its request
+pub type ResponseHook = Arc<dyn Fn(&Request, &mut Response) + Send + Sync>;
+
+/// # Hooks intercepting the requests and responses of all connections.
+///
+/// Meant for integration tests checking or altering what goes over the wire, and for users
+/// needing a last word on it. The request hook sees the request once its body is read (before
+/// it for routes streaming the body); the response hook sees the response after the server
+/// added its connection headers. Requests that can't be parsed aren't intercepted.
+///
+/// # Example
+///
+/// ```
+/// let interceptor = Interceptor::default()
+///     .on_request(|request| request.headers.push(("X-Seen".to_string(), "1".to_string())))
+///     .on_response(|_, response| response.set_header("Server", "test"));
+/// ```
+#[derive(Clone, Default)]
+pub struct Interceptor {
+    on_request: Option<RequestHook>,
+    on_response: Option<ResponseHook>,
+}
+
+impl Interceptor {
+    /// Set the hook called with every request
+    pub fn on_request(mut self, hook: impl Fn(&mut Request) + Send + Sync + 'static) -> Self {
+        self.on_request = Some(Arc::new(hook));
+        self
+    }
+
+    /// Set the hook called with every response
+    pub fn on_response(
+        mut self,
+        hook: impl Fn(&Request, &mut Response) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_response = Some(Arc::new(hook));
+        self
+    }
+
+    /// Pass a request to the request hook, if any
+    pub fn request(&self, request: &mut Request) {
+        if let Some(hook) = &self.on_request {
+            hook(request);
+        }
+    }
+
+    /// Pass a response to the response hook, if any
+    pub fn response(&self, request: &Request, response: &mut Response) {
+        if let Some(hook) = &self.on_response {
+            hook(request, response);
+        }
+    }
+}
+
+impl Debug for Interceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interceptor")
+            .field("on_request", &self.on_request.is_some())
+            .field("on_response", &self.on_response.is_some())
+            .finish()
+    }
+}
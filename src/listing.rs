@@ -0,0 +1,366 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use crate::{file_store::DirEntry, http::percent_encode_segment};
+
+/// # How a directory listing is sorted and filtered.
+///
+/// Parsed from the query string of the listing request, e.g. `?sort=size&order=desc&hidden=true`.
+/// Entries are sorted by name in ascending order by default, and hidden files (names starting
+/// with `.`) are left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ListingOptions {
+    pub sort: SortKey,
+    pub order: SortOrder,
+    pub show_hidden: bool,
+}
+
+/// Property of the entries a listing is sorted by (`sort=name|size|mtime`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    /// Last modification time. Entries without one come first.
+    Modified,
+}
+
+/// Direction of the sort (`order=asc|desc`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Query parameter of a listing request with an unknown value
+#[derive(Debug, PartialEq, Eq)]
+pub struct ListingOptionError {
+    pub name: String,
+    pub value: String,
+}
+
+impl Display for ListingOptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid value of `{}`: {}", self.name, self.value)
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Self::Name),
+            "size" => Ok(Self::Size),
+            "mtime" => Ok(Self::Modified),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(Self::Ascending),
+            "desc" => Ok(Self::Descending),
+            _ => Err(()),
+        }
+    }
+}
+
+impl SortKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "mtime",
+        }
+    }
+}
+
+impl SortOrder {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "asc",
+            SortOrder::Descending => "desc",
+        }
+    }
+
+    fn reversed(&self) -> Self {
+        match self {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        }
+    }
+}
+
+impl ListingOptions {
+    /// # Get the options from the decoded query parameters.
+    ///
+    /// Unknown parameters are ignored. A known parameter with an unknown value is an error.
+    pub fn from_query(params: &[(String, String)]) -> Result<Self, ListingOptionError> {
+        let mut options = Self::default();
+
+        for (name, value) in params {
+            let error = || ListingOptionError {
+                name: name.clone(),
+                value: value.clone(),
+            };
+
+            match name.as_str() {
+                "sort" => options.sort = value.parse().map_err(|_| error())?,
+                "order" => options.order = value.parse().map_err(|_| error())?,
+                "hidden" => {
+                    options.show_hidden = match value.as_str() {
+                        "true" | "1" => true,
+                        "false" | "0" => false,
+                        _ => return Err(error()),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// # Filter and sort the entries of a directory.
+    ///
+    /// Entries that compare equal are ordered by name.
+    pub fn apply(&self, mut entries: Vec<DirEntry>) -> Vec<DirEntry> {
+        if !self.show_hidden {
+            entries.retain(|entry| !entry.name.starts_with('.'));
+        }
+
+        entries.sort_by(|a, b| {
+            let ordering = match self.sort {
+                SortKey::Name => Ordering::Equal,
+                SortKey::Size => a.metadata.len.cmp(&b.metadata.len),
+                SortKey::Modified => a.metadata.modified.cmp(&b.metadata.modified),
+            }
+            .then_with(|| a.name.cmp(&b.name));
+
+            match self.order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            }
+        });
+
+        entries
+    }
+
+    /// Query string of the link sorting the listing by `sort`. Sorting again by the current key
+    /// reverses the order.
+    fn sort_link(&self, sort: SortKey) -> String {
+        let order = match sort == self.sort {
+            true => self.order.reversed(),
+            false => SortOrder::Ascending,
+        };
+
+        let mut query = format!("?sort={}&amp;order={}", sort.as_str(), order.as_str());
+        if self.show_hidden {
+            query.push_str("&amp;hidden=true");
+        }
+
+        query
+    }
+}
+
+/// # Render the listing of a directory as an HTML page.
+///
+/// `title` is the path of the directory as requested. The entries are listed in the given order,
+/// directories with a trailing `/`. The column headers link to the listing sorted by them.
+pub fn render_listing(title: &str, entries: &[DirEntry], options: &ListingOptions) -> String {
+    let title = escape_html(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><title>Index of {title}</title></head>\n<body>\n\
+         <h1>Index of {title}</h1>\n<table>\n<tr>\
+         <th><a href=\"{}\">Name</a></th>\
+         <th><a href=\"{}\">Size</a></th>\
+         <th><a href=\"{}\">Modified</a></th></tr>\n",
+        options.sort_link(SortKey::Name),
+        options.sort_link(SortKey::Size),
+        options.sort_link(SortKey::Modified),
+    );
+
+    for entry in entries {
+        // The link is percent-encoded, so `#`, `?` and `%` in names don't break it; the
+        // encoding leaves nothing to escape for HTML
+        let (href, name) = match entry.metadata.is_dir {
+            true => (
+                format!("{}/", percent_encode_segment(&entry.name)),
+                format!("{}/", escape_html(&entry.name)),
+            ),
+            false => (
+                percent_encode_segment(&entry.name),
+                escape_html(&entry.name),
+            ),
+        };
+        let size = match entry.metadata.is_dir {
+            true => "-".to_string(),
+            false => entry.metadata.len.to_string(),
+        };
+        let modified = entry
+            .metadata
+            .modified
+            .map(crate::http::format_http_date)
+            .unwrap_or_else(|| "-".to_string());
+
+        html.push_str(&format!(
+            "<tr><td><a href=\"{href}\">{name}</a></td><td>{size}</td><td>{modified}</td></tr>\n"
+        ));
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::file_store::FileMetadata;
+
+    fn entry(name: &str, len: u64, modified: u64) -> DirEntry {
+        DirEntry {
+            name: name.to_string(),
+            metadata: FileMetadata {
+                len,
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+                is_dir: false,
+            },
+        }
+    }
+
+    fn params(query: &[(&str, &str)]) -> Vec<(String, String)> {
+        query
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_query() {
+        assert_eq!(
+            ListingOptions::from_query(&[]),
+            Ok(ListingOptions::default()),
+            "Defaults"
+        );
+        assert_eq!(
+            ListingOptions::from_query(&params(&[
+                ("sort", "mtime"),
+                ("order", "desc"),
+                ("hidden", "1"),
+                ("other", "x"),
+            ])),
+            Ok(ListingOptions {
+                sort: SortKey::Modified,
+                order: SortOrder::Descending,
+                show_hidden: true,
+            }),
+            "All options, unknown parameters ignored"
+        );
+        assert_eq!(
+            ListingOptions::from_query(&params(&[("sort", "color")])),
+            Err(ListingOptionError {
+                name: "sort".to_string(),
+                value: "color".to_string(),
+            }),
+            "Unknown sort key"
+        );
+        assert!(
+            ListingOptions::from_query(&params(&[("order", "up")])).is_err(),
+            "Unknown order"
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let entries = vec![
+            entry("b.txt", 30, 1),
+            entry(".hidden", 5, 4),
+            entry("a.txt", 20, 3),
+            entry("c.txt", 10, 2),
+        ];
+        let names = |options: ListingOptions| {
+            options
+                .apply(entries.clone())
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(ListingOptions::default()),
+            ["a.txt", "b.txt", "c.txt"],
+            "By name, hidden files left out"
+        );
+        assert_eq!(
+            names(ListingOptions {
+                sort: SortKey::Size,
+                order: SortOrder::Descending,
+                show_hidden: true,
+            }),
+            ["b.txt", "a.txt", "c.txt", ".hidden"],
+            "By size, descending, with hidden files"
+        );
+        assert_eq!(
+            names(ListingOptions {
+                sort: SortKey::Modified,
+                ..Default::default()
+            }),
+            ["b.txt", "c.txt", "a.txt"],
+            "By modification time"
+        );
+    }
+
+    #[test]
+    fn test_render_listing() {
+        let mut dir = entry("my dir", 0, 0);
+        dir.metadata.is_dir = true;
+        let entries = [entry("<a>.txt", 3, 0), entry("a#b?c%d.txt", 4, 0), dir];
+        let html = render_listing("/files/", &entries, &ListingOptions::default());
+
+        assert!(html.contains("<h1>Index of /files/</h1>"), "Title");
+        assert!(
+            html.contains("<a href=\"%3Ca%3E.txt\">&lt;a&gt;.txt</a></td><td>3</td>"),
+            "Escaped entry with its size"
+        );
+        assert!(
+            html.contains("<a href=\"a%23b%3Fc%25d.txt\">a#b?c%d.txt</a></td><td>4</td>"),
+            "Link percent-encoded, text as is"
+        );
+        assert!(
+            html.contains("<a href=\"my%20dir/\">my dir/</a></td><td>-</td>"),
+            "Directory link keeps the trailing slash"
+        );
+        assert!(
+            html.contains("<a href=\"?sort=name&amp;order=desc\">Name</a>"),
+            "Link reversing the current order"
+        );
+        assert!(
+            html.contains("<a href=\"?sort=size&amp;order=asc\">Size</a>"),
+            "Link sorting by another key"
+        );
+    }
+}
@@ -1,29 +1,29 @@
 #![allow(dead_code)]
-use std::env;
-use tokio::net::TcpListener;
+use std::{env, process, sync::Arc};
 
-use self::handlers::handle_connection;
+use self::config::ServerConfig;
 
+mod config;
+mod cors;
+mod file_store;
 mod handlers;
 mod http;
+mod intercept;
+mod listing;
+mod router;
+mod server;
+mod stats;
+mod upgrade;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() {
     let args: Vec<String> = env::args().collect();
-    let directory = args
-        .iter()
-        .position(|arg| arg == "--directory")
-        .and_then(|pos| args.get(pos + 1).cloned());
+    let config = ServerConfig::from_args(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
 
-    let files_dir = directory.unwrap_or_else(|| "".to_string());
+    let listeners = server::bind(&config).await.unwrap();
 
-    let listener = TcpListener::bind("127.0.0.1:4221").await.unwrap();
-
-    loop {
-        let (stream, _) = listener.accept().await.unwrap();
-        let files_dir = files_dir.clone();
-        tokio::task::spawn(async move {
-            handle_connection(stream, &files_dir).await.unwrap();
-        });
-    }
+    server::serve(listeners, Arc::new(config)).await;
 }
@@ -0,0 +1,699 @@
+use std::{fmt::Debug, ops::ControlFlow, sync::Arc};
+
+use tokio::io::AsyncRead;
+
+use crate::{
+    config::ServerConfig,
+    cors::CorsPolicy,
+    file_store::BoxFuture,
+    handlers::get_options_response,
+    http::{Method, Request, ResponseBuilder, StatusCode},
+};
+
+/// # Everything a route handler gets to build its response.
+#[derive(Clone, Copy)]
+pub struct RouteContext<'a> {
+    pub request: &'a Request,
+    pub config: &'a ServerConfig,
+    /// The percent-decoded request path, without the query string
+    pub path: &'a str,
+    /// The part of the path matched by the trailing `*` of the route pattern. Empty for exact
+    /// patterns.
+    pub tail: &'a str,
+}
+
+/// Route handler building the response to a request
+pub type Handler = Arc<
+    dyn for<'a> Fn(RouteContext<'a>) -> BoxFuture<'a, ResponseBuilder<StatusCode>> + Send + Sync,
+>;
+
+/// # Middleware run before the handler of every request.
+///
+/// Returns `ControlFlow::Break` with a response to answer the request right away, without
+/// running the remaining middleware and the handler (e.g. to reject it), or
+/// `ControlFlow::Continue` to pass it on.
+pub type Middleware =
+    Arc<dyn Fn(&Request, &ServerConfig) -> ControlFlow<ResponseBuilder<StatusCode>> + Send + Sync>;
+
+/// Body of a request handed to a [`StreamHandler`]. It ends with the body of the request.
+pub type RequestBody<'a> = dyn AsyncRead + Unpin + Send + 'a;
+
+/// # Route handler reading the request body as a stream.
+///
+/// The `body` of the request in the context is empty; the handler reads it from the stream
+/// instead, so a large upload can be processed without buffering it. Whatever the handler
+/// leaves unread is skipped.
+pub type StreamHandler = Arc<
+    dyn for<'a> Fn(
+            RouteContext<'a>,
+            &'a mut RequestBody<'a>,
+        ) -> BoxFuture<'a, ResponseBuilder<StatusCode>>
+        + Send
+        + Sync,
+>;
+
+#[derive(Clone)]
+enum RouteHandler {
+    Buffered(Handler),
+    Streaming(StreamHandler),
+}
+
+struct Route {
+    /// `None` if the route matches any method
+    method: Option<Method>,
+    pattern: String,
+    handler: RouteHandler,
+}
+
+/// # Maps request methods and paths to handlers.
+///
+/// A pattern is either an exact path (`/user-agent`) or a prefix followed by `*` (`/echo/*`),
+/// which matches every path starting with the prefix. Routes are tried in registration order
+/// and the first one matching both the method and the path wins, so register method-specific
+/// routes before a catch-all route for the same pattern.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Arc<Route>>,
+    middleware: Vec<Middleware>,
+    /// `Cache-Control` values as (pattern, value), in registration order
+    cache_policies: Vec<(String, String)>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for a method and a path pattern
+    pub fn route<F>(self, method: Method, pattern: &str, handler: F) -> Self
+    where
+        F: for<'a> Fn(RouteContext<'a>) -> BoxFuture<'a, ResponseBuilder<StatusCode>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.add(
+            Some(method),
+            pattern,
+            RouteHandler::Buffered(Arc::new(handler)),
+        )
+    }
+
+    /// # Register a handler reading the request body as a stream.
+    ///
+    /// The body is not buffered before the handler is called (see [`StreamHandler`]). Otherwise
+    /// the same as [`route`](Router::route).
+    pub fn stream<F>(self, method: Method, pattern: &str, handler: F) -> Self
+    where
+        F: for<'a> Fn(
+                RouteContext<'a>,
+                &'a mut RequestBody<'a>,
+            ) -> BoxFuture<'a, ResponseBuilder<StatusCode>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.add(
+            Some(method),
+            pattern,
+            RouteHandler::Streaming(Arc::new(handler)),
+        )
+    }
+
+    /// # Register one handler for several methods and a path pattern.
+    ///
+    /// The same as calling [`route`](Router::route) for every method, in order, with a shared
+    /// handler.
+    pub fn methods<F>(mut self, methods: &[Method], pattern: &str, handler: F) -> Self
+    where
+        F: for<'a> Fn(RouteContext<'a>) -> BoxFuture<'a, ResponseBuilder<StatusCode>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let handler = RouteHandler::Buffered(Arc::new(handler));
+
+        for method in methods {
+            self = self.add(Some(*method), pattern, handler.clone());
+        }
+
+        self
+    }
+
+    /// Register a handler for a path pattern, matching any method
+    pub fn any<F>(self, pattern: &str, handler: F) -> Self
+    where
+        F: for<'a> Fn(RouteContext<'a>) -> BoxFuture<'a, ResponseBuilder<StatusCode>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.add(None, pattern, RouteHandler::Buffered(Arc::new(handler)))
+    }
+
+    /// # Answer `OPTIONS` on every path with the CORS headers of a policy.
+    ///
+    /// The allowed methods are computed for each path from the registered routes, like the
+    /// default `OPTIONS` answer, and [`CorsPolicy::preflight`] adds the CORS headers. Routes
+    /// registered earlier with their own `OPTIONS` handler keep it.
+    pub fn cors(self, policy: CorsPolicy) -> Self {
+        let policy = Arc::new(policy);
+
+        self.route(Method::Options, "/*", move |ctx| {
+            let response =
+                policy.preflight(ctx.request, get_options_response(ctx.path, ctx.config));
+            Box::pin(async move { response })
+        })
+    }
+
+    /// # Attach a `Cache-Control` policy to the routes of a path pattern.
+    ///
+    /// Responses of the routes matching the pattern get a `Cache-Control` header with the value,
+    /// unless the handler sets one itself. Patterns work like route patterns, and the first
+    /// matching policy wins.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let router = Router::new()
+    ///     .cache_control("/assets/*", "public, max-age=31536000, immutable")
+    ///     .cache_control("/api/*", "no-store");
+    /// ```
+    pub fn cache_control(mut self, pattern: &str, value: impl Into<String>) -> Self {
+        self.cache_policies
+            .push((pattern.to_string(), value.into()));
+        self
+    }
+
+    /// The `Cache-Control` policy of the first pattern matching the path, if any
+    pub fn cache_control_for(&self, path: &str) -> Option<&str> {
+        self.cache_policies
+            .iter()
+            .find(|(pattern, _)| match_pattern(pattern, path).is_some())
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// # Apply the `Cache-Control` policy of a path to the response of its route.
+    ///
+    /// A `Cache-Control` header set by the handler is kept.
+    pub fn apply_cache_control(
+        &self,
+        path: &str,
+        response: ResponseBuilder<StatusCode>,
+    ) -> ResponseBuilder<StatusCode> {
+        match self.cache_control_for(path) {
+            Some(value) if response.header_value("Cache-Control").is_none() => {
+                response.with(("Cache-Control".to_string(), value.to_string()))
+            }
+            _ => response,
+        }
+    }
+
+    /// # Register a middleware.
+    ///
+    /// Middleware runs in registration order before any route is looked up, for every request
+    /// including the ones no route matches.
+    pub fn middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(&Request, &ServerConfig) -> ControlFlow<ResponseBuilder<StatusCode>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// # Run the middleware on a request.
+    ///
+    /// Returns the response of the first middleware that short-circuits, if any.
+    pub fn run_middleware(
+        &self,
+        request: &Request,
+        config: &ServerConfig,
+    ) -> ControlFlow<ResponseBuilder<StatusCode>> {
+        self.middleware
+            .iter()
+            .try_for_each(|middleware| middleware(request, config))
+    }
+
+    fn add(mut self, method: Option<Method>, pattern: &str, handler: RouteHandler) -> Self {
+        self.routes.push(Arc::new(Route {
+            method,
+            pattern: pattern.to_string(),
+            handler,
+        }));
+        self
+    }
+
+    /// # Find the handler of a request.
+    ///
+    /// Returns the handler and the part of the path matched by the trailing `*` of the pattern.
+    /// Returns `None` if the first matching route reads the body as a stream (see
+    /// [`find_stream`](Router::find_stream)).
+    pub fn find<'p>(&self, method: Method, path: &'p str) -> Option<(Handler, &'p str)> {
+        match self.find_route(method, path)? {
+            (RouteHandler::Buffered(handler), tail) => Some((handler, tail)),
+            (RouteHandler::Streaming(_), _) => None,
+        }
+    }
+
+    /// # Find the handler of a request reading the body as a stream.
+    ///
+    /// Returns `None` unless the first matching route was registered with
+    /// [`stream`](Router::stream).
+    pub fn find_stream<'p>(
+        &self,
+        method: Method,
+        path: &'p str,
+    ) -> Option<(StreamHandler, &'p str)> {
+        match self.find_route(method, path)? {
+            (RouteHandler::Streaming(handler), tail) => Some((handler, tail)),
+            (RouteHandler::Buffered(_), _) => None,
+        }
+    }
+
+    fn find_route<'p>(&self, method: Method, path: &'p str) -> Option<(RouteHandler, &'p str)> {
+        self.routes
+            .iter()
+            .filter(|route| route.method.map_or(true, |m| m == method))
+            .find_map(|route| {
+                match_pattern(&route.pattern, path).map(|tail| (route.handler.clone(), tail))
+            })
+    }
+
+    /// # Check whether a route is registered specifically for a method and a path.
+    ///
+    /// Unlike [`find`](Router::find), routes matching any method are ignored.
+    pub fn has_route_for(&self, method: Method, path: &str) -> bool {
+        self.routes.iter().any(|route| {
+            route.method == Some(method) && match_pattern(&route.pattern, path).is_some()
+        })
+    }
+
+    /// # Methods some route handles for a path.
+    ///
+    /// In the order of [`Method::ALL`]. `CONNECT` is never listed, since it's meant for proxies.
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        Method::ALL
+            .into_iter()
+            .filter(|method| *method != Method::Connect)
+            .filter(|method| self.find_route(*method, path).is_some())
+            .collect()
+    }
+
+    /// All registered patterns in registration order, without duplicates
+    pub fn patterns(&self) -> Vec<&str> {
+        let mut patterns: Vec<&str> = Vec::new();
+
+        for route in &self.routes {
+            if !patterns.contains(&route.pattern.as_str()) {
+                patterns.push(&route.pattern);
+            }
+        }
+
+        patterns
+    }
+
+    /// # Find the registered patterns closest to a path.
+    ///
+    /// Patterns are ranked by the Levenshtein distance to the path (prefix patterns are compared
+    /// with the same number of leading path segments). Only patterns within a few edits are
+    /// returned, closest first.
+    pub fn suggestions(&self, path: &str) -> Vec<&str> {
+        const MAX_DISTANCE: usize = 3;
+        const MAX_SUGGESTIONS: usize = 3;
+
+        let mut suggestions = self
+            .patterns()
+            .into_iter()
+            .map(|pattern| {
+                (
+                    levenshtein(
+                        comparable_path(pattern, path),
+                        pattern.trim_end_matches('*'),
+                    ),
+                    pattern,
+                )
+            })
+            .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+            .collect::<Vec<_>>();
+
+        // The sort is stable, so patterns at the same distance keep the registration order
+        suggestions.sort_by_key(|(distance, _)| *distance);
+
+        suggestions
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, pattern)| pattern)
+            .collect()
+    }
+}
+
+impl Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.routes.iter().map(|route| {
+                format!(
+                    "{} {}",
+                    route.method.map_or("*".to_string(), |m| m.to_string()),
+                    route.pattern
+                )
+            }))
+            .finish()
+    }
+}
+
+/// Match a path against a pattern, returning the part matched by the trailing `*`
+fn match_pattern<'p>(pattern: &str, path: &'p str) -> Option<&'p str> {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => path.strip_prefix(prefix),
+        None => (pattern == path).then_some(""),
+    }
+}
+
+/// The part of the path to compare with a pattern: the whole path for exact patterns, or as
+/// many leading segments as the prefix of a `*` pattern has
+fn comparable_path<'p>(pattern: &str, path: &'p str) -> &'p str {
+    let Some(prefix) = pattern.strip_suffix('*') else {
+        return path;
+    };
+
+    let segments = prefix.matches('/').count();
+
+    path.match_indices('/')
+        .nth(segments.saturating_sub(1))
+        .map_or(path, |(i, _)| &path[..=i])
+}
+
+/// Number of single-character edits needed to turn one string into the other
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> Router {
+        Router::new()
+            .any("/", |_| Box::pin(async { ResponseBuilder::ok() }))
+            .any("/user-agent", |_| Box::pin(async { ResponseBuilder::ok() }))
+            .route(Method::Post, "/files/*", |_| {
+                Box::pin(async { ResponseBuilder::new().with_status_code(StatusCode::Created) })
+            })
+            .any("/files/*", |_| Box::pin(async { ResponseBuilder::ok() }))
+            .any("/echo/*", |ctx| {
+                Box::pin(async move { ResponseBuilder::ok().body(ctx.tail) })
+            })
+    }
+
+    #[test]
+    fn test_match_pattern() {
+        assert_eq!(match_pattern("/", "/"), Some(""), "Exact root");
+        assert_eq!(
+            match_pattern("/user-agent", "/user-agent/"),
+            None,
+            "Exact mismatch"
+        );
+        assert_eq!(
+            match_pattern("/echo/*", "/echo/abc/def"),
+            Some("abc/def"),
+            "Prefix"
+        );
+        assert_eq!(match_pattern("/echo/*", "/echo/"), Some(""), "Empty tail");
+        assert_eq!(match_pattern("/echo/*", "/echo"), None, "Prefix mismatch");
+    }
+
+    #[tokio::test]
+    async fn test_find() {
+        let router = router();
+        let config = ServerConfig::default();
+        let request = Request::try_from("GET /echo/abc HTTP/1.1\r\n\r\n").unwrap();
+
+        let (handler, tail) = router.find(Method::Get, "/echo/abc").unwrap();
+        assert_eq!(tail, "abc", "Wildcard tail");
+
+        let context = RouteContext {
+            request: &request,
+            config: &config,
+            path: "/echo/abc",
+            tail,
+        };
+        let response = handler(context).await.build();
+        assert_eq!(
+            response.body,
+            Some(b"abc".to_vec().into()),
+            "Handler is called"
+        );
+
+        let (handler, _) = router.find(Method::Post, "/files/a").unwrap();
+        let context = RouteContext {
+            tail: "a",
+            ..context
+        };
+        assert_eq!(
+            handler(context).await.build().status_code,
+            StatusCode::Created,
+            "Method-specific route wins"
+        );
+
+        let (handler, _) = router.find(Method::Get, "/files/a").unwrap();
+        assert_eq!(
+            handler(context).await.build().status_code,
+            StatusCode::Ok,
+            "Catch-all route for other methods"
+        );
+
+        assert!(router.find(Method::Get, "/unknown").is_none(), "No route");
+    }
+
+    #[tokio::test]
+    async fn test_methods() {
+        let router = Router::new()
+            .methods(&[Method::Get, Method::Post], "/both", |ctx| {
+                Box::pin(async move { ResponseBuilder::ok().body(ctx.request.method.to_string()) })
+            })
+            .any("/both", |_| {
+                Box::pin(async { ResponseBuilder::not_found() })
+            });
+        let config = ServerConfig::default();
+
+        for method in [Method::Get, Method::Post] {
+            let request =
+                Request::try_from(format!("{} /both HTTP/1.1\r\n\r\n", method).as_str()).unwrap();
+            let (handler, tail) = router.find(method, "/both").unwrap();
+            let context = RouteContext {
+                request: &request,
+                config: &config,
+                path: "/both",
+                tail,
+            };
+
+            assert_eq!(
+                handler(context).await.build().body,
+                Some(method.to_string().into_bytes().into()),
+                "{} dispatched to the shared handler",
+                method
+            );
+        }
+
+        let request = Request::try_from("PUT /both HTTP/1.1\r\n\r\n").unwrap();
+        let (handler, tail) = router.find(Method::Put, "/both").unwrap();
+        let context = RouteContext {
+            request: &request,
+            config: &config,
+            path: "/both",
+            tail,
+        };
+        assert_eq!(
+            handler(context).await.build().status_code,
+            StatusCode::NotFound,
+            "Other methods are not registered"
+        );
+
+        assert_eq!(
+            format!("{:?}", router),
+            "[\"GET /both\", \"POST /both\", \"* /both\"]",
+            "One route per method"
+        );
+    }
+
+    #[test]
+    fn test_middleware() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let router = router()
+            .middleware(move |_, _| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                ControlFlow::Continue(())
+            })
+            .middleware(|request, _| match request.header("X-Block") {
+                Some(_) => ControlFlow::Break(ResponseBuilder::bad_request()),
+                None => ControlFlow::Continue(()),
+            })
+            .middleware(|_, _| ControlFlow::Break(ResponseBuilder::not_found()));
+        let config = ServerConfig::default();
+
+        let request = Request::try_from("GET / HTTP/1.1\r\nX-Block: 1\r\n\r\n").unwrap();
+        let response = match router.run_middleware(&request, &config) {
+            ControlFlow::Break(response) => response.build(),
+            ControlFlow::Continue(()) => panic!("Request should be short-circuited"),
+        };
+        assert_eq!(
+            response.status_code,
+            StatusCode::BadRequest,
+            "First short-circuiting middleware wins"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "Earlier middleware ran");
+
+        assert!(
+            Router::new()
+                .run_middleware(&request, &config)
+                .is_continue(),
+            "No middleware"
+        );
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let router = Router::new()
+            .cache_control("/assets/logo.svg", "no-cache")
+            .cache_control("/assets/*", "public, max-age=31536000")
+            .cache_control("/api/*", "no-store");
+
+        assert_eq!(
+            router.cache_control_for("/assets/app.js"),
+            Some("public, max-age=31536000"),
+            "Prefix pattern"
+        );
+        assert_eq!(
+            router.cache_control_for("/assets/logo.svg"),
+            Some("no-cache"),
+            "First matching pattern wins"
+        );
+        assert_eq!(router.cache_control_for("/api/users"), Some("no-store"));
+        assert_eq!(router.cache_control_for("/other"), None, "No policy");
+
+        let response = router
+            .apply_cache_control("/api/users", ResponseBuilder::ok())
+            .build();
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Cache-Control".to_string(), "no-store".to_string()),
+                ("Content-Length".to_string(), "0".to_string()),
+            ],
+            "Policy applied"
+        );
+
+        let response = router
+            .apply_cache_control(
+                "/api/users",
+                ResponseBuilder::ok().with(("Cache-Control", "private")),
+            )
+            .build();
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Cache-Control".to_string(), "private".to_string()),
+                ("Content-Length".to_string(), "0".to_string()),
+            ],
+            "Handler's own header kept"
+        );
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        let router = router();
+
+        assert_eq!(
+            router.allowed_methods("/files/a"),
+            vec![
+                Method::Get,
+                Method::Head,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Patch,
+                Method::Options,
+                Method::Trace,
+            ],
+            "Catch-all route"
+        );
+        assert!(router.allowed_methods("/unknown").is_empty(), "No route");
+
+        let router = Router::new()
+            .route(Method::Get, "/a", |_| {
+                Box::pin(async { ResponseBuilder::ok() })
+            })
+            .route(Method::Delete, "/a", |_| {
+                Box::pin(async { ResponseBuilder::ok() })
+            });
+        assert_eq!(
+            router.allowed_methods("/a"),
+            vec![Method::Get, Method::Delete],
+            "Method-specific routes"
+        );
+        assert!(router.has_route_for(Method::Get, "/a"), "GET route");
+        assert!(!router.has_route_for(Method::Post, "/a"), "No POST route");
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(
+            router().patterns(),
+            vec!["/", "/user-agent", "/files/*", "/echo/*"],
+            "Registered patterns without duplicates"
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let router = router();
+
+        assert_eq!(
+            router.suggestions("/user-agnet"),
+            vec!["/user-agent"],
+            "Typo"
+        );
+        assert_eq!(
+            router.suggestions("/ech/abc"),
+            vec!["/echo/*"],
+            "Prefix typo"
+        );
+        assert_eq!(
+            router.suggestions("/file/a.txt"),
+            vec!["/files/*"],
+            "Missing letter"
+        );
+        assert!(
+            router.suggestions("/completely/different").is_empty(),
+            "No close route"
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("/echo/", "/ech/"), 1);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{io, sync::Arc, time::Duration};
+
+use tokio::{
+    net::{lookup_host, TcpListener, TcpSocket},
+    sync::watch,
+    task::JoinSet,
+};
+
+use crate::{config::ServerConfig, handlers::handle_connection};
+
+/// # Bind a listener for every configured address.
+///
+/// Fails if any of the addresses can't be bound.
+pub async fn bind(config: &ServerConfig) -> io::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::new();
+
+    for (host, port) in config.listen_addrs() {
+        listeners.push(bind_with_backlog((host.as_str(), port), config.backlog).await?);
+    }
+
+    Ok(listeners)
+}
+
+/// # Bind a listener with the given accept queue length.
+///
+/// Like `TcpListener::bind`, the host is resolved and the first address that can be bound is
+/// used.
+pub async fn bind_with_backlog(addr: (&str, u16), backlog: u32) -> io::Result<TcpListener> {
+    let mut last_err = None;
+
+    for addr in lookup_host(addr).await? {
+        let socket = match addr.is_ipv4() {
+            true => TcpSocket::new_v4()?,
+            false => TcpSocket::new_v6()?,
+        };
+
+        // Allow restarting the server while connections of the previous run are in TIME_WAIT
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+
+        match socket.bind(addr).and_then(|_| socket.listen(backlog)) {
+            Ok(listener) => return Ok(listener),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+/// # Signal to shut the server down gracefully.
+///
+/// Clones share the same signal. Once it's triggered, the accept loops stop accepting new
+/// connections and the open connections are closed after the response to their current
+/// request.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Wait until the signal is triggered
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+
+        // The sender lives as long as `self`, so waiting can't fail
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Accept connections on all listeners concurrently.
+///
+/// Every listener gets its own accept loop sharing the same configuration. Returns when all
+/// accept loops are finished, which happens once the [`Shutdown`] signal of the configuration
+/// is triggered and the connections that were open by then are closed.
+pub async fn serve(listeners: Vec<TcpListener>, config: Arc<ServerConfig>) {
+    let accept_loops = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(accept_loop(listener, config.clone())))
+        .collect::<Vec<_>>();
+
+    for accept_loop in accept_loops {
+        accept_loop.await.expect("Accept loop panicked");
+    }
+}
+
+/// Pause after a failed accept before accepting again
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(10);
+
+async fn accept_loop(listener: TcpListener, config: Arc<ServerConfig>) {
+    let mut connections = JoinSet::new();
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = config.shutdown.triggered() => break,
+            // Forget the connections that are already closed
+            Some(_) = connections.join_next() => continue,
+        };
+
+        let (stream, _) = match accepted {
+            Ok(connection) => connection,
+            Err(err) => {
+                // Errors such as running out of file descriptors persist for a while, so don't
+                // retry right away
+                eprintln!("Failed to accept a connection: {}", err);
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let config = config.clone();
+        connections.spawn(async move {
+            // A failed connection must not take the server down
+            if let Err(err) = handle_connection(stream, &config).await {
+                eprintln!("{}", err);
+            }
+        });
+    }
+
+    // Stop listening right away, but let the open connections finish their requests
+    drop(listener);
+    while connections.join_next().await.is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    #[tokio::test]
+    async fn test_serve_multiple_listeners() {
+        let listeners = vec![
+            TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        ];
+        let addrs = listeners
+            .iter()
+            .map(|listener| listener.local_addr().unwrap())
+            .collect::<Vec<_>>();
+
+        tokio::spawn(serve(listeners, Arc::new(ServerConfig::default())));
+
+        for addr in addrs {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET /echo/abc HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await.unwrap();
+
+            assert_eq!(
+                response,
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc",
+                "Response from {}",
+                addr
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_with_backlog() {
+        let listener = bind_with_backlog(("127.0.0.1", 0), 16).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(addr.ip().is_loopback(), "Bound to the loopback");
+        assert_ne!(addr.port(), 0, "Port assigned");
+
+        tokio::spawn(serve(vec![listener], Arc::new(ServerConfig::default())));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(
+            response.starts_with(b"HTTP/1.1 200 OK\r\n"),
+            "Connection accepted"
+        );
+
+        assert!(
+            bind_with_backlog(("not a host", 0), 16).await.is_err(),
+            "Unresolvable host"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = Arc::new(ServerConfig {
+            shutdown_token: Some("secret".to_string()),
+            ..Default::default()
+        });
+
+        let server = tokio::spawn(serve(vec![listener], config.clone()));
+
+        // An idle keep-alive connection doesn't hold the shutdown up
+        let mut idle = TcpStream::connect(addr).await.unwrap();
+        idle.write_all(b"GET /echo/a HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = [0; 1024];
+        let len = idle.read(&mut response).await.unwrap();
+        assert!(
+            response[..len].starts_with(b"HTTP/1.1 200 OK\r\n"),
+            "Idle connection"
+        );
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"POST /admin/shutdown HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 13\r\n\
+             Connection: close\r\n\r\nShutting down",
+            "Shutdown response closes the connection"
+        );
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(1), server).await;
+        assert!(matches!(result, Ok(Ok(()))), "Server stopped");
+
+        let mut rest = Vec::new();
+        idle.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty(), "Idle connection closed");
+        assert!(
+            TcpStream::connect(addr).await.is_err(),
+            "No more connections accepted"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fmt::Display,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// # Traffic of a single connection.
+///
+/// Counts every byte read from and written to the connection (request heads, bodies and
+/// framing alike) and the number of requests read, across all requests of a keep-alive
+/// connection. The counters are atomic so they can be shared with the stream recording them.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    pub bytes_read: AtomicU64,
+    pub bytes_written: AtomicU64,
+    pub requests: AtomicU64,
+}
+
+impl ConnectionStats {
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn add_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Display for ConnectionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Connection closed: {} requests, {} bytes read, {} bytes written",
+            self.requests(),
+            self.bytes_read(),
+            self.bytes_written()
+        )
+    }
+}
+
+/// # Stream recording the bytes passing through it in [`ConnectionStats`].
+#[derive(Debug)]
+pub struct CountingStream<S> {
+    inner: S,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(inner: S, stats: Arc<ConnectionStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountingStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = poll {
+            let read = (buf.filled().len() - filled) as u64;
+            self.stats.bytes_read.fetch_add(read, Ordering::Relaxed);
+        }
+
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountingStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(written)) = poll {
+            self.stats
+                .bytes_written
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_counting_stream() {
+        let stats = Arc::new(ConnectionStats::default());
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut stream = CountingStream::new(server, stats.clone());
+
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 16];
+        let read = stream.read(&mut buf).await.unwrap();
+        assert_eq!(read, 5, "Bytes received");
+
+        stream.write_all(b"hello world").await.unwrap();
+        stats.add_request();
+
+        assert_eq!(stats.bytes_read(), 5, "Bytes read");
+        assert_eq!(stats.bytes_written(), 11, "Bytes written");
+        assert_eq!(
+            stats.to_string(),
+            "Connection closed: 1 requests, 5 bytes read, 11 bytes written",
+            "Summary"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{file_store::BoxFuture, http::Request};
+
+/// Any bidirectional byte stream a connection can be served over
+pub trait RawStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> RawStream for T {}
+
+/// # Connection taken over by an upgrade handler.
+///
+/// Handed to the handler after the `101 Switching Protocols` response has been written.
+pub struct Upgraded {
+    /// The raw connection
+    pub stream: Box<dyn RawStream>,
+    /// Bytes the client sent after the upgrade request that were already read from the
+    /// connection. They belong to the new protocol and must be processed before reading from
+    /// the stream.
+    pub leftover: Vec<u8>,
+}
+
+/// Handler taking over a connection upgraded to its protocol. It receives the upgrade request
+/// and owns the connection until it returns.
+pub type UpgradeHandler = Arc<dyn Fn(Request, Upgraded) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// # Protocols a connection can be upgraded to.
+///
+/// When a request carries `Connection: Upgrade` and an `Upgrade` header naming a registered
+/// protocol, the server answers `101 Switching Protocols` and hands the connection to the
+/// protocol's handler. Protocol names are case-insensitive.
+#[derive(Clone, Default)]
+pub struct Upgrades {
+    handlers: HashMap<String, UpgradeHandler>,
+}
+
+impl Upgrades {
+    /// Register the handler of a protocol
+    pub fn register(&mut self, protocol: &str, handler: UpgradeHandler) {
+        self.handlers.insert(protocol.to_ascii_lowercase(), handler);
+    }
+
+    /// # Find the protocol a request asks to upgrade to.
+    ///
+    /// Returns the protocol name as sent by the client and its handler, or `None` if the
+    /// request is not an upgrade request or none of the offered protocols is registered.
+    pub fn find(&self, request: &Request) -> Option<(String, UpgradeHandler)> {
+        let connection_upgrade = request.header("Connection").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+        });
+
+        if !connection_upgrade {
+            return None;
+        }
+
+        // The client may offer several protocols in order of preference
+        request
+            .header("Upgrade")?
+            .split(',')
+            .map(str::trim)
+            .find_map(|protocol| {
+                self.handlers
+                    .get(&protocol.to_ascii_lowercase())
+                    .map(|handler| (protocol.to_string(), handler.clone()))
+            })
+    }
+}
+
+impl Debug for Upgrades {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_handler() -> UpgradeHandler {
+        Arc::new(|_, _| Box::pin(async {}))
+    }
+
+    #[test]
+    fn test_find() {
+        let mut upgrades = Upgrades::default();
+        upgrades.register("raw-echo", noop_handler());
+
+        let request = Request::try_from(
+            "GET / HTTP/1.1\r\nConnection: keep-alive, Upgrade\r\nUpgrade: h2c, Raw-Echo\r\n\r\n",
+        )
+        .unwrap();
+        let (protocol, _) = upgrades.find(&request).unwrap();
+        assert_eq!(
+            protocol, "Raw-Echo",
+            "Registered protocol among the offered ones"
+        );
+
+        let request = Request::try_from("GET / HTTP/1.1\r\nUpgrade: raw-echo\r\n\r\n").unwrap();
+        assert!(
+            upgrades.find(&request).is_none(),
+            "Missing Connection: Upgrade"
+        );
+
+        let request =
+            Request::try_from("GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n")
+                .unwrap();
+        assert!(upgrades.find(&request).is_none(), "Unregistered protocol");
+    }
+}